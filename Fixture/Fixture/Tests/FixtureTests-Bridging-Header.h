@@ -0,0 +1 @@
+#import "TestHelpers.h"
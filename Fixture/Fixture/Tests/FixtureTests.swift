@@ -7,4 +7,9 @@ struct FixtureTests {
         // Write your test here and use APIs like `#expect(...)` to check expected conditions.
     }
 
+    @Test func objectiveCHelper() {
+        // TestHelpers is Objective-C, seen through the bridging header
+        #expect(TestHelpers.expectedGreeting() == "Hello, world!")
+    }
+
 }
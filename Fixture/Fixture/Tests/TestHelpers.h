@@ -0,0 +1,11 @@
+#import <Foundation/Foundation.h>
+
+NS_ASSUME_NONNULL_BEGIN
+
+@interface TestHelpers : NSObject
+
++ (NSString *)expectedGreeting;
+
+@end
+
+NS_ASSUME_NONNULL_END
@@ -0,0 +1,9 @@
+#import "TestHelpers.h"
+
+@implementation TestHelpers
+
++ (NSString *)expectedGreeting {
+    return @"Hello, world!";
+}
+
+@end
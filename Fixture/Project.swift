@@ -22,5 +22,20 @@ let project = Project(
             ],
             dependencies: []
         ),
+        .target(
+            name: "FixtureTests",
+            destinations: .iOS,
+            product: .unitTests,
+            bundleId: "dev.tuist.FixtureTests",
+            buildableFolders: [
+                "Fixture/Tests",
+            ],
+            dependencies: [.target(name: "Fixture")],
+            settings: .settings(
+                base: [
+                    "SWIFT_OBJC_BRIDGING_HEADER": "$(SRCROOT)/Fixture/Tests/FixtureTests-Bridging-Header.h",
+                ]
+            )
+        ),
     ]
 )
@@ -0,0 +1,19 @@
+import ProjectDescription
+
+// A project with nothing but tests, like shared test utilities, so its
+// bundle has no app to run in
+let project = Project(
+    name: "TestSupport",
+    targets: [
+        .target(
+            name: "TestSupportTests",
+            destinations: .iOS,
+            product: .unitTests,
+            bundleId: "dev.tuist.TestSupportTests",
+            buildableFolders: [
+                "Tests",
+            ],
+            dependencies: []
+        ),
+    ]
+)
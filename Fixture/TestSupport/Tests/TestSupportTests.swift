@@ -0,0 +1,9 @@
+import Testing
+
+struct TestSupportTests {
+
+    @Test func runsWithoutTestHost() {
+        #expect([1, 2, 3].reduce(0, +) == 6)
+    }
+
+}
@@ -0,0 +1,9 @@
+import ProjectDescription
+
+let workspace = Workspace(
+    name: "Fixture",
+    projects: [
+        ".",
+        "TestSupport",
+    ]
+)
@@ -22,7 +22,7 @@ echo -e "${GREEN}✓ Catalyst CLI built successfully${NC}"
 echo ""
 
 echo -e "${BLUE}Step 2: Cleaning previous Bazel artifacts...${NC}"
-rm -rf Fixture/bazel-* Fixture/WORKSPACE Fixture/BUILD Fixture/.bazelrc 2>/dev/null || true
+rm -rf Fixture/bazel-* Fixture/WORKSPACE Fixture/BUILD Fixture/.bazelrc Fixture/catalyst.bazelrc 2>/dev/null || true
 echo -e "${GREEN}✓ Cleaned previous artifacts${NC}"
 echo ""
 
@@ -32,7 +32,7 @@ cd Fixture
 cd ..
 echo ""
 
-if [ -f "Fixture/WORKSPACE" ] && [ -f "Fixture/BUILD" ] && [ -f "Fixture/.bazelrc" ]; then
+if [ -f "Fixture/WORKSPACE" ] && [ -f "Fixture/BUILD" ] && [ -f "Fixture/catalyst.bazelrc" ]; then
     echo -e "${GREEN}✓ Bazel files generated successfully${NC}"
     echo ""
 
@@ -40,6 +40,7 @@ if [ -f "Fixture/WORKSPACE" ] && [ -f "Fixture/BUILD" ] && [ -f "Fixture/.bazelr
     echo "  - Fixture/WORKSPACE"
     echo "  - Fixture/BUILD"
     echo "  - Fixture/.bazelrc"
+    echo "  - Fixture/catalyst.bazelrc"
     echo ""
 
     echo -e "${BLUE}BUILD file contents:${NC}"
@@ -64,4 +65,4 @@ echo "Catalyst cache location:"
 echo "  ~/Library/Caches/catalyst"
 echo ""
 echo "To view cached graph:"
-echo "  cat ~/Library/Caches/catalyst/graph.json | jq"
+echo "  cat ~/Library/Caches/catalyst/projects/*/graph.json | jq"
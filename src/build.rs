@@ -0,0 +1,446 @@
+//! `catalyst build` and `catalyst test`, and rebuilding on changes with `--watch`.
+
+use anyhow::{Context, Result};
+use notify::Watcher;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use crate::cache::{
+    get_catalyst_cache_dir, get_project_cache_dir, load_graph, read_cached_graph,
+    GeneratedManifest, GraphCacheKey, PackageStamps, GRAPH_CACHE_FILE,
+};
+use crate::command::{bazel_color_args, set_cancelled, CommandExt};
+use crate::config::{Config, GenerateOptions};
+use crate::generate::generate_project;
+use crate::graph::{normalize_lexically, PathResolver, TuistGraph};
+use crate::index::TargetIndex;
+use crate::output::{dry_run, PhaseTimer, Progress};
+use crate::report::BuildReport;
+use crate::tuist::{
+    buildable_folder_paths, collect_manifest_files, hash_source_stamps, read_graph_file,
+    TUIST_MANIFEST_FILES,
+};
+
+pub fn build_project(
+    project_dir: &Path,
+    options: &GenerateOptions,
+    report: &mut BuildReport,
+) -> Result<()> {
+    let cache_dir = get_catalyst_cache_dir()?;
+    let project_cache_dir = get_project_cache_dir(&cache_dir, project_dir)?;
+    if !options.force
+        && !options.no_cache
+        && !dry_run()
+        && BuildStamp::is_current(project_dir, &project_cache_dir, options)
+    {
+        info!("Project is up to date: {}", project_dir.display());
+        report.up_to_date = true;
+        report.add_projects(&load_graph(project_dir, &cache_dir, options, false)?)?;
+        return Ok(());
+    }
+
+    let written = generate_project(project_dir, options, report)?;
+    report.files_written = written.clone();
+    if dry_run() {
+        info!("Dry run; skipping the Bazel build");
+        return Ok(());
+    }
+
+    // Step 4: Run Bazel build
+    let graph = load_graph(project_dir, &cache_dir, options, false)?;
+    run_bazel_build(
+        &options.bazel,
+        &options.bazel_startup_args(),
+        &options.workspace_dir(project_dir, &graph),
+        &options.bazel_build_args(),
+        report,
+    )?;
+
+    info!("Build completed successfully!");
+    if let Some(summary) = &report.summary {
+        summary.print();
+    }
+
+    if let Some(stamp) = BuildStamp::compute(project_dir, &project_cache_dir, options, written) {
+        stamp.save(&project_cache_dir)?;
+    }
+
+    Ok(())
+}
+
+/// How long `build --watch` waits for a burst of saves to settle before it
+/// rebuilds.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// What the watch loop is woken up by.
+enum WatchEvent {
+    Changed(Vec<PathBuf>),
+    /// Ctrl-C
+    Stop,
+}
+
+/// Builds the project like `catalyst build`, then again whenever one of its
+/// sources or Tuist manifests changes, until a message on `interrupts`, e.g.
+/// from a Ctrl-C handler. A change during a build cancels it and starts over;
+/// a failed build is reported and waits for the next change. Unchanged
+/// projects and the cached graph are reused, so a rebuild only regenerates
+/// what the change touched.
+pub fn watch_project(
+    project_dir: &Path,
+    options: &GenerateOptions,
+    interrupts: mpsc::Receiver<()>,
+) -> Result<()> {
+    let (sender, events) = mpsc::channel();
+    let stop = sender.clone();
+    std::thread::spawn(move || {
+        if interrupts.recv().is_ok() {
+            set_cancelled(true);
+            let _ = stop.send(WatchEvent::Stop);
+        }
+    });
+
+    let mut watcher = notify::RecommendedWatcher::new(
+        move |event: notify::Result<notify::Event>| {
+            // Reads and permission changes don't affect the build
+            let Ok(event) = event else { return };
+            if matches!(
+                event.kind,
+                notify::EventKind::Create(_)
+                    | notify::EventKind::Remove(_)
+                    | notify::EventKind::Modify(
+                        notify::event::ModifyKind::Data(_)
+                            | notify::event::ModifyKind::Name(_)
+                            | notify::event::ModifyKind::Any
+                    )
+            ) {
+                let _ = sender.send(WatchEvent::Changed(event.paths));
+            }
+        },
+        // The bazel-* symlinks lead into Bazel's output base
+        notify::Config::default().with_follow_symlinks(false),
+    )
+    .context("Failed to start watching files")?;
+
+    let mut watched = WatchedPaths::default();
+    watched.update(&mut watcher, project_dir)?;
+    loop {
+        set_cancelled(false);
+        let worker = {
+            let project_dir = project_dir.to_path_buf();
+            let options = options.clone();
+            std::thread::spawn(move || {
+                build_project(&project_dir, &options, &mut BuildReport::default())
+            })
+        };
+
+        let mut changed = false;
+        while !worker.is_finished() {
+            match events.recv_timeout(Duration::from_millis(100)) {
+                Ok(WatchEvent::Stop) => {
+                    let _ = worker.join();
+                    return Ok(());
+                }
+                Ok(WatchEvent::Changed(paths)) if watched.is_relevant(&paths) => {
+                    changed = true;
+                    set_cancelled(true);
+                }
+                _ => {}
+            }
+        }
+        let result = worker
+            .join()
+            .map_err(|_| anyhow::anyhow!("The build panicked"))?;
+        watched.update(&mut watcher, project_dir)?;
+
+        if changed {
+            info!("\nFiles changed; restarting the build...");
+        } else {
+            if let Err(err) = result {
+                eprintln!("Error: {:#}", err);
+            }
+            info!("\nWatching for changes (Ctrl-C to stop)...");
+            loop {
+                match events.recv() {
+                    Ok(WatchEvent::Changed(paths)) if watched.is_relevant(&paths) => break,
+                    Ok(WatchEvent::Changed(_)) => {}
+                    Ok(WatchEvent::Stop) | Err(_) => return Ok(()),
+                }
+            }
+        }
+
+        // Editors and formatters save several files in a row
+        loop {
+            match events.recv_timeout(WATCH_DEBOUNCE) {
+                Ok(WatchEvent::Changed(_)) => {}
+                Ok(WatchEvent::Stop) => return Ok(()),
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+        }
+    }
+}
+
+/// The directories `build --watch` watches: the buildable folders of the
+/// cached graph, recursively, and the directories holding Tuist manifests.
+#[derive(Default)]
+struct WatchedPaths {
+    watches: Vec<(PathBuf, notify::RecursiveMode)>,
+    folders: Vec<PathBuf>,
+    /// Files catalyst generated, whose changes are its own
+    generated: Vec<PathBuf>,
+}
+
+impl WatchedPaths {
+    /// Watches the paths of the project's current graph instead of the old
+    /// ones. Without a cached graph, e.g. when `tuist graph` failed, only the
+    /// manifests are watched.
+    fn update(&mut self, watcher: &mut impl Watcher, project_dir: &Path) -> Result<()> {
+        let project_dir = normalize_lexically(project_dir);
+        let project_cache_dir = get_project_cache_dir(&get_catalyst_cache_dir()?, &project_dir)?;
+        let graph = read_cached_graph(
+            &project_dir,
+            &project_cache_dir.join(GRAPH_CACHE_FILE),
+            &project_cache_dir.join(GraphCacheKey::FILE_NAME),
+        );
+
+        let folders: Vec<PathBuf> = graph
+            .as_ref()
+            .map(|graph| {
+                let resolver = PathResolver::new(&project_dir, &graph.path);
+                buildable_folder_paths(graph)
+                    .into_iter()
+                    .map(|folder| PathBuf::from(resolver.resolve(&resolver.root, folder)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let mut manifests = Vec::new();
+        collect_manifest_files(&project_dir, false, &mut manifests)?;
+
+        let mut watches: Vec<(PathBuf, notify::RecursiveMode)> = folders
+            .iter()
+            .map(|folder| (folder.clone(), notify::RecursiveMode::Recursive))
+            .collect();
+        // catalyst.toml and new manifests show up in the project directory
+        let manifest_dirs = manifests
+            .iter()
+            .filter_map(|manifest| manifest.parent())
+            .chain([project_dir.as_path()]);
+        for dir in manifest_dirs {
+            let watch = (dir.to_path_buf(), notify::RecursiveMode::NonRecursive);
+            if !watches.contains(&watch) {
+                watches.push(watch);
+            }
+        }
+
+        for (path, _) in &self.watches {
+            let _ = watcher.unwatch(path);
+        }
+        for (path, mode) in &watches {
+            if let Err(err) = watcher.watch(path, *mode) {
+                verbose!("Not watching {}: {}", path.display(), err);
+            }
+        }
+        verbose!("Watching {} directories for changes", watches.len());
+
+        self.watches = watches;
+        self.folders = folders;
+        self.generated = GeneratedManifest::load(&project_cache_dir)?.files;
+        Ok(())
+    }
+
+    /// Whether a change to `paths` calls for a rebuild.
+    fn is_relevant(&self, paths: &[PathBuf]) -> bool {
+        paths.iter().any(|path| {
+            if self.generated.contains(path) {
+                return false;
+            }
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            self.folders.iter().any(|folder| path.starts_with(folder))
+                || TUIST_MANIFEST_FILES.contains(&name.as_ref())
+                || name == Config::FILE_NAME
+                || path.components().any(|c| c.as_os_str() == "Tuist")
+        })
+    }
+}
+
+/// Fingerprint of the last successful build. When it still matches, neither
+/// the graph, the sources, the options, nor the generated files changed, so
+/// the build can be skipped.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+struct BuildStamp {
+    graph: GraphCacheKey,
+    /// Hash of the options that affect generation and the Bazel invocation
+    options: String,
+    /// Hash of the size and modification time of every file in the
+    /// buildable folders and of the xcconfig files settings were read from
+    sources: String,
+    generated: Vec<PathBuf>,
+    /// Hash of the contents of the generated files
+    generated_contents: String,
+}
+
+impl BuildStamp {
+    const FILE_NAME: &'static str = "build-stamp.json";
+
+    /// Computes the stamp for the project's current state. Returns `None` when
+    /// the cached graph is stale or a generated file is missing.
+    fn compute(
+        project_dir: &Path,
+        project_cache_dir: &Path,
+        options: &GenerateOptions,
+        generated: Vec<PathBuf>,
+    ) -> Option<Self> {
+        let graph = match &options.graph {
+            Some(path) => read_graph_file(path).ok()?,
+            None => read_cached_graph(
+                project_dir,
+                &project_cache_dir.join("graph.json"),
+                &project_cache_dir.join(GraphCacheKey::FILE_NAME),
+            )?,
+        };
+
+        let mut hasher = DefaultHasher::new();
+        format!(
+            "{:?}",
+            GenerateOptions {
+                force: false,
+                default_run_target: None,
+                ..options.clone()
+            }
+        )
+        .hash(&mut hasher);
+        let options = format!("{:016x}", hasher.finish());
+
+        let mut hasher = DefaultHasher::new();
+        for path in &generated {
+            path.hash(&mut hasher);
+            fs::read(path).ok()?.hash(&mut hasher);
+        }
+        let generated_contents = format!("{:016x}", hasher.finish());
+
+        Some(Self {
+            graph: GraphCacheKey::compute(project_dir, &graph).ok()?,
+            options,
+            sources: hash_source_stamps(&graph, &PackageStamps::load(project_cache_dir)),
+            generated,
+            generated_contents,
+        })
+    }
+
+    /// Whether the stamp recorded by the last build matches the current state.
+    fn is_current(project_dir: &Path, project_cache_dir: &Path, options: &GenerateOptions) -> bool {
+        let Some(stored) = fs::read_to_string(project_cache_dir.join(Self::FILE_NAME))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Self>(&content).ok())
+        else {
+            return false;
+        };
+
+        Self::compute(
+            project_dir,
+            project_cache_dir,
+            options,
+            stored.generated.clone(),
+        )
+        .is_some_and(|current| current == stored)
+    }
+
+    fn save(&self, project_cache_dir: &Path) -> Result<()> {
+        fs::write(
+            project_cache_dir.join(Self::FILE_NAME),
+            serde_json::to_string_pretty(self)?,
+        )
+        .context("Failed to write build stamp")
+    }
+}
+
+pub fn run_bazel_build(
+    bazel: &Path,
+    startup_args: &[String],
+    project_dir: &Path,
+    build_args: &[String],
+    report: &mut BuildReport,
+) -> Result<()> {
+    info!("\nRunning Bazel build...");
+    let _timer = PhaseTimer::start("bazel build");
+
+    let _spinner = Progress::spinner("Building with Bazel");
+    let teed = Command::new(bazel)
+        .args(startup_args)
+        .arg("build")
+        .args(bazel_color_args())
+        .args(build_args)
+        .arg("//...")
+        .current_dir(project_dir)
+        .logged()
+        .status_teed()
+        .with_context(|| format!("Failed to execute {} build", bazel.display()))?;
+    report.bazel_exit_code = teed.status.code();
+
+    teed.check_bazel_build(None)
+}
+
+pub fn run_bazel_test(
+    bazel: &Path,
+    startup_args: &[String],
+    project_dir: &Path,
+    label: &str,
+    build_args: &[String],
+    report: &mut BuildReport,
+) -> Result<()> {
+    info!("\nRunning Bazel tests...");
+    let _timer = PhaseTimer::start("bazel test");
+
+    let _spinner = Progress::spinner("Testing with Bazel");
+    let teed = Command::new(bazel)
+        .args(startup_args)
+        .arg("test")
+        .arg("--test_output=errors")
+        .args(bazel_color_args())
+        .args(build_args)
+        .arg(label)
+        .current_dir(project_dir)
+        .logged()
+        .status_teed()
+        .with_context(|| format!("Failed to execute {} test", bazel.display()))?;
+    report.bazel_exit_code = teed.status.code();
+    teed.check_bazel("Tests failed")?;
+
+    info!("All tests passed!");
+
+    Ok(())
+}
+
+/// The label of the unit or UI test target called `name`, in the workspace
+/// generated for the project in `project_dir`.
+pub fn find_test_label(
+    graph: &TuistGraph,
+    name: &str,
+    project_dir: &Path,
+    options: &GenerateOptions,
+) -> Result<String> {
+    let mut projects = graph.projects()?;
+    let resolver = PathResolver::new(project_dir, &graph.path);
+    for project in &mut projects {
+        project.resolve_paths(&resolver);
+    }
+    let index = TargetIndex::new(&options.package_root(project_dir, graph), &projects);
+
+    for project in &projects {
+        for target in project.targets.values() {
+            let is_test = matches!(target.product.as_str(), "unit_tests" | "ui_tests");
+            if is_test && target.name.eq_ignore_ascii_case(name) {
+                let package = index.package(&project.path).unwrap_or_default();
+                return Ok(format!("//{}:{}", package, target.name.to_lowercase()));
+            }
+        }
+    }
+
+    anyhow::bail!("No test target named {} found in project", name)
+}
@@ -0,0 +1,1535 @@
+//! The BUILD file of a project: its libraries, bundles, and resources.
+
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::{Config, GenerateOptions, Platform, SourceOrder};
+use crate::generate::project_test_suite;
+use crate::graph::{
+    synthesized_kind, synthesized_name, synthesized_symbol, synthesized_target, SdkReference,
+    TuistProject, TuistTarget, TUIST_DERIVED_SOURCES,
+};
+use crate::index::{PrebuiltKind, TargetIndex};
+use crate::plist::{
+    debug_config_setting_rule, resolve_entitlements, resolve_info_plist, PlistKind,
+};
+use crate::starlark::{render_families, starlark_string};
+use crate::workspace::write_generated_file;
+
+pub(crate) fn generate_build_file(
+    project: &TuistProject,
+    index: &TargetIndex,
+    package_dir: &Path,
+    options: &GenerateOptions,
+    plist_template: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+    let mut build_content = String::new();
+    // Vendored frameworks inside this project, for targets anywhere in the
+    // workspace to depend on. Their import rules and local_provisioning_profile
+    // are loaded from apple.bzl
+    let mut apple_bzl_rules = BTreeSet::new();
+    for import in index.imports_of(&project.path) {
+        if import.kind != PrebuiltKind::Library {
+            apple_bzl_rules.insert(import.rule());
+        }
+        build_content.push_str(&import.render());
+    }
+    // rules_apple macros referenced by the generated targets, per platform,
+    // for the load statements
+    let mut apple_rules: BTreeMap<Platform, BTreeSet<String>> = BTreeMap::new();
+    // apple_resource_bundle and apple_resource_group live in a different file
+    // than the platform rules
+    let mut resource_rules: BTreeSet<&str> = BTreeSet::new();
+    // Set when a bundle's ID or Info.plist differs per configuration, which is
+    // selected on the Debug config_setting
+    let mut uses_debug_config_setting = false;
+    // With several platforms in the workspace there's no single platform to
+    // build libraries for at the top level, so `//...` leaves them to the
+    // bundles that use them, which build them for their own platform
+    let manual = index.platforms.len() > 1;
+    let synthesized = project.synthesized_sources();
+
+    for target in project.targets.values() {
+        if !index.generates(project, target) {
+            continue;
+        }
+        let target_name_lower = target.name.to_lowercase();
+        let platform = target.platform();
+        uses_debug_config_setting |= matches!(
+            target.product.as_str(),
+            "app" | "app_extension" | "unit_tests" | "ui_tests" | "framework"
+        ) && target.has_configuration_plists();
+        let minimum_os_version = target.minimum_os_version(&options.minimum_os_version);
+        // Device families only exist on iOS
+        let families = (platform == Platform::Ios)
+            .then(|| render_families(&target.families(&options.families)));
+        let tags = target_tags(target, project, options);
+        let declared_module_name = target.declared_module_name();
+        let module_name = swift_identifier(declared_module_name);
+        if module_name != declared_module_name {
+            warning!(
+                "{} has the module name {}, which isn't a valid Swift identifier; using {}",
+                target.name,
+                declared_module_name,
+                module_name
+            );
+        }
+        let library_tags: Vec<String> = manual
+            .then(|| starlark_string("manual"))
+            .into_iter()
+            .chain(tags.iter().cloned())
+            .collect();
+        let mut apple_rule = |kind: &str| {
+            let rule = platform.rule(kind);
+            apple_rules
+                .entry(platform)
+                .or_default()
+                .insert(rule.clone());
+            rule
+        };
+
+        // Extract Swift and Objective-C sources and resources from buildableFolders
+        let mut source_files: Vec<String> = Vec::new();
+        let mut objc_srcs: Vec<String> = Vec::new();
+        let mut objc_hdrs: Vec<String> = Vec::new();
+        let mut resources = Resources::default();
+        // Fixtures and golden files tests read at runtime, whatever their type
+        let mut test_data = Resources::default();
+        let is_test = matches!(target.product.as_str(), "unit_tests" | "ui_tests");
+        // Bazel packages can't reference files outside their directory
+        let mut outside_project: Vec<&str> = Vec::new();
+        // Swift and Objective-C files, for check_imports
+        let mut import_sources: Vec<&str> = Vec::new();
+        let excluded = options
+            .exclude
+            .get(&target.name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        // Extensions of the resources, for the synthesized accessors
+        let mut resource_extensions: BTreeSet<String> = BTreeSet::new();
+        // Prebuilt `.bundle` directories, which ship whole
+        let mut bundle_imports: BTreeSet<PathBuf> = BTreeSet::new();
+
+        for folder in &target.buildable_folders {
+            let folder_path = Path::new(&folder.path).strip_prefix(&project.path).ok();
+            for file in &folder.resolved_files {
+                if folder.excludes(&file.path) {
+                    verbose!(
+                        "Excluding {} from {}, as its manifest does",
+                        file.path,
+                        target.name
+                    );
+                    continue;
+                }
+                // Reported by check_missing_files
+                if !Path::new(&file.path).exists() {
+                    continue;
+                }
+                // Make path relative to project directory
+                let Ok(rel_path) = Path::new(&file.path).strip_prefix(&project.path) else {
+                    outside_project.push(&file.path);
+                    continue;
+                };
+                if let Some(pattern) = excluded
+                    .iter()
+                    .find(|pattern| glob_matches(pattern, rel_path))
+                {
+                    verbose!(
+                        "Excluding {} from {}, as {} in {} says",
+                        rel_path.display(),
+                        target.name,
+                        pattern,
+                        Config::FILE_NAME
+                    );
+                    continue;
+                }
+
+                if let Some(bundle) = bundle_directory(rel_path) {
+                    bundle_imports.insert(bundle);
+                    continue;
+                }
+                if let Some(dir) = resource_directory(rel_path) {
+                    if let Some(ext) = dir.extension() {
+                        resource_extensions.insert(ext.to_string_lossy().into_owned());
+                    }
+                    if is_test {
+                        test_data.add_directory(dir.clone());
+                    }
+                    resources.add_directory(dir);
+                    continue;
+                }
+                let ext = rel_path
+                    .extension()
+                    .map(|ext| ext.to_string_lossy())
+                    .unwrap_or_default();
+                if ext == "swift" {
+                    source_files.push(starlark_string(rel_path.display()));
+                    import_sources.push(&file.path);
+                } else if ext == "h" {
+                    objc_hdrs.push(starlark_string(rel_path.display()));
+                    import_sources.push(&file.path);
+                } else if OBJC_EXTENSIONS.contains(&ext.as_ref()) {
+                    objc_srcs.push(starlark_string(rel_path.display()));
+                    import_sources.push(&file.path);
+                } else {
+                    if RESOURCE_EXTENSIONS.contains(&ext.as_ref()) {
+                        resource_extensions.insert(ext.to_string());
+                        // Files in a subdirectory of the folder keep it in
+                        // the bundle, for lookups by subdirectory, unless
+                        // they're compiled
+                        let nested_in = folder_path.filter(|folder| {
+                            !options.flatten_resources
+                                && !COMPILED_RESOURCE_EXTENSIONS.contains(&ext.as_ref())
+                                && rel_path.parent().is_some_and(|parent| parent != *folder)
+                        });
+                        match nested_in {
+                            Some(folder) => resources.add_structured(folder, rel_path),
+                            None => resources.add_file(rel_path),
+                        }
+                    }
+                    if is_test {
+                        test_data.add_file(rel_path);
+                    }
+                }
+            }
+        }
+
+        // Tuist's resource synthesizers generate typed accessors for the
+        // resources at `tuist generate`, which the target's code calls like
+        // its own
+        let target_synthesized: Vec<&PathBuf> = synthesized
+            .iter()
+            .filter(|path| synthesized_target(path) == Some(synthesized_name(&target.name)))
+            .collect();
+        for path in &target_synthesized {
+            if let Ok(rel_path) = path.strip_prefix(&project.path) {
+                source_files.push(starlark_string(rel_path.display()));
+            }
+        }
+        for (parser, extensions) in project.resource_synthesizers() {
+            let prefix = format!("Tuist{}+", synthesized_kind(parser));
+            let generated = target_synthesized.iter().any(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(&prefix))
+            });
+            let has_resources = extensions
+                .iter()
+                .any(|ext| resource_extensions.contains(*ext));
+            if generated || !has_resources {
+                continue;
+            }
+            // Only code that calls the accessors needs them
+            let Some(symbol) = synthesized_symbol(parser, &module_name) else {
+                continue;
+            };
+            let user = import_sources
+                .iter()
+                .filter(|path| path.ends_with(".swift"))
+                .find(|path| fs::read_to_string(path).is_ok_and(|source| source.contains(&symbol)));
+            if let Some(user) = user {
+                warning!(
+                    "{} uses {}, which Tuist's {} resource synthesizer generates as {}{}.swift, but {} doesn't hold it; run `tuist generate --no-open` to write it, or {} fails to compile",
+                    target.name,
+                    symbol,
+                    parser,
+                    prefix,
+                    target.name,
+                    Path::new(&project.path).join(TUIST_DERIVED_SOURCES).display(),
+                    user
+                );
+            }
+        }
+
+        // Tuist doesn't guarantee the order of resolved files, so sort them to
+        // keep the generated BUILD file stable between runs, unless the build
+        // depends on the graph's order
+        for files in [&mut source_files, &mut objc_srcs, &mut objc_hdrs] {
+            match options.source_order {
+                SourceOrder::Sorted => {
+                    files.sort();
+                    files.dedup();
+                }
+                SourceOrder::Graph => {
+                    let mut seen = BTreeSet::new();
+                    files.retain(|file| seen.insert(file.clone()));
+                }
+            }
+        }
+
+        // Graphs from Tuist releases that don't resolve the files of buildable
+        // folders leave only the folders to glob. When the graph does list
+        // their files, a target without sources really has none, and a glob
+        // would only hide that.
+        let resolved_files = target
+            .buildable_folders
+            .iter()
+            .map(|folder| folder.resolved_files.len())
+            .sum::<usize>();
+        let source_globs: Vec<String> = target
+            .buildable_folders
+            .iter()
+            .filter(|_| resolved_files == 0)
+            .filter_map(|folder| Path::new(&folder.path).strip_prefix(&project.path).ok())
+            .map(|folder| starlark_string(folder.join("**/*.swift").display()))
+            .collect();
+        let has_sources =
+            !source_files.is_empty() || !objc_srcs.is_empty() || !source_globs.is_empty();
+        if !has_sources && target.product != "bundle" {
+            let manifest = Path::new(&project.path).join("Project.swift");
+            if resolved_files > 0 {
+                anyhow::bail!(
+                    "{} has no sources: its buildable folders hold {} file(s), but no Swift or Objective-C ones, and {} targets need at least one to build; add one, like an app's delegate, to a buildable folder in {}",
+                    target.name,
+                    resolved_files,
+                    target.product,
+                    manifest.display()
+                );
+            }
+            anyhow::bail!(
+                "{} has no sources: the Tuist graph lists no Swift or Objective-C files and no buildable folders for it; check its buildableFolders in {}",
+                target.name,
+                manifest.display()
+            );
+        }
+
+        if !outside_project.is_empty() {
+            warning!(
+                "{} has {} file(s) outside its project directory {}, which are left out of its Bazel targets:\n  {}",
+                target.name,
+                outside_project.len(),
+                project.path,
+                outside_project.join("\n  ")
+            );
+        }
+
+        // Get dependencies. SDK dependencies become linkopts below.
+        for dep in &target.dependencies {
+            if let Some((_, prebuilt)) = dep.prebuilt() {
+                if let Some(Err(reason)) = index.imports.get(&prebuilt.path) {
+                    warning!(
+                        "dropping prebuilt dependency {} of {}; {}",
+                        prebuilt.path,
+                        target.name,
+                        reason
+                    );
+                }
+            }
+        }
+        let mut deps: Vec<String> = target
+            .dependencies
+            .iter()
+            .filter_map(|dep| index.dependency_label(dep, project, platform))
+            .map(starlark_string)
+            .collect();
+        // The graph may list a dependency twice, e.g. directly and through
+        // its project, and Bazel rejects duplicate labels
+        deps.sort();
+        deps.dedup();
+        index.check_imports(target, project, &import_sources);
+        for dep in target.dependencies.iter().filter(|dep| dep.is_unlinked()) {
+            if let Some(name) = dep.target_name() {
+                verbose!(
+                    "{} doesn't link {} (status: none); leaving it out of its deps",
+                    target.name,
+                    name
+                );
+            }
+        }
+        let copts = target.swift_copts(options.configuration);
+        // Resource bundles ship with the target's own resources
+        for label in index.dependency_labels_with_product(target, project, "bundle") {
+            resources.add_label(&label);
+        }
+        for bundle in &bundle_imports {
+            let name = bundle_import_name(&target_name_lower, bundle);
+            resource_rules.insert("apple_bundle_import");
+            build_content.push_str(&format!(
+                "apple_bundle_import(\n    name = {},\n    bundle_imports = glob([{}]),\n)\n\n",
+                starlark_string(&name),
+                starlark_string(bundle.join("**").display())
+            ));
+            resources.add_label(&format!(":{}", name));
+        }
+        resources.sort();
+        test_data.sort();
+        let resource_group = format!("{}_resources", target_name_lower);
+        if let Some(rule) = resources.render_structured_group(&resource_group) {
+            resource_rules.insert("apple_resource_group");
+            build_content.push_str(&rule);
+            resources.add_label(&format!(":{}", resource_group));
+            resources.sort();
+        }
+
+        // Objective-C code gets its own objc_library that the swift_library
+        // depends on. Swift sees it through the bridging header and publishes
+        // a `<Module>-Swift.h` header for Objective-C dependents; the
+        // objc_library itself can't import that header, as that would be a
+        // dependency cycle.
+        let objc_label = (!objc_srcs.is_empty() || !objc_hdrs.is_empty())
+            .then(|| format!(":{}_objc", target_name_lower));
+        if let Some(label) = &objc_label {
+            build_content.push_str(
+                &ObjcLibrary {
+                    name: &label[1..],
+                    module_name: &module_name,
+                    srcs: &objc_srcs,
+                    hdrs: &objc_hdrs,
+                    deps: &deps,
+                    sdks: &target.sdk_dependencies().collect::<Vec<_>>(),
+                    testonly: is_test,
+                    tags: &library_tags,
+                }
+                .render(),
+            );
+            deps.push(starlark_string(label));
+        }
+        deps.sort();
+        deps.dedup();
+        // The objc_library links the SDK for the whole target when there is one
+        let mut linkopts = if objc_label.is_some() {
+            Vec::new()
+        } else {
+            target.sdk_linkopts()
+        };
+        // OTHER_LDFLAGS apply where the target is linked: the bundle rule of
+        // bundled products, and otherwise the swift_library, whose linkopts
+        // reach the binaries linking it
+        let (ldflags, unsupported) = target.linker_flags(options.configuration);
+        if !unsupported.is_empty() {
+            warning!(
+                "{} has OTHER_LDFLAGS referencing build settings, which are left out of its linkopts: {}",
+                target.name,
+                unsupported.join(", ")
+            );
+        }
+        let bundled = matches!(
+            target.product.as_str(),
+            "app" | "app_extension" | "unit_tests" | "ui_tests" | "framework"
+        );
+        let bundle_linkopts = if bundled {
+            ldflags
+        } else {
+            linkopts.extend(ldflags);
+            Vec::new()
+        };
+        let bridging_header = target
+            .bridging_header(Path::new(&project.path))
+            .and_then(|path| {
+                path.strip_prefix(&project.path)
+                    .ok()
+                    .map(|rel| rel.display().to_string())
+            });
+        let generated_header =
+            index.generated_header(target, project, options, objc_label.is_some());
+
+        match target.product.as_str() {
+            "app" => {
+                // Generate swift_library for the app sources
+                build_content.push_str(
+                    &SwiftLibrary {
+                        name: format!("{}_lib", target_name_lower),
+                        module_name: &module_name,
+                        srcs: &source_files,
+                        fallback_globs: &source_globs,
+                        deps: &deps,
+                        copts: &copts,
+                        linkopts: &linkopts,
+                        bridging_header: bridging_header.as_deref(),
+                        generated_header: generated_header.as_deref(),
+                        data: None,
+                        testonly: false,
+                        tags: &library_tags,
+                    }
+                    .render(),
+                );
+
+                let infoplist = resolve_info_plist(
+                    target,
+                    project,
+                    package_dir,
+                    PlistKind::App(plist_template),
+                    &mut written,
+                )?;
+                let entitlements =
+                    resolve_entitlements(target, project, package_dir, &mut written)?;
+                let profile = target.provisioning_profile(options);
+                let profile_rule = format!("{}_profile", target_name_lower);
+                if let Some(rule) = profile.as_ref().and_then(|p| p.render_rule(&profile_rule)) {
+                    apple_bzl_rules.insert("local_provisioning_profile");
+                    build_content.push_str(&rule);
+                }
+                let extensions: Vec<String> = index
+                    .dependency_labels_with_product(target, project, "app_extension")
+                    .iter()
+                    .map(starlark_string)
+                    .collect();
+
+                // Generate the platform's application rule, e.g. ios_application
+                let rule = apple_rule("application");
+                build_content.push_str(&format!(
+                    "{}(\n    name = {},\n    bundle_id = {},\n    bundle_name = {},\n",
+                    rule,
+                    starlark_string(&target_name_lower),
+                    target.bundle_id_value().render(),
+                    starlark_string(target.product_name())
+                ));
+                if let Some(families) = &families {
+                    build_content.push_str(&format!("    families = {},\n", families));
+                }
+                build_content.push_str(&format!("    infoplists = {},\n", infoplist.render_list()));
+                // actool compiles the icon set and lists it in the Info.plist
+                // as CFBundleIcons
+                if let Some(icon_set) = target.app_icon_set(Path::new(&project.path)) {
+                    build_content.push_str(&format!(
+                        "    app_icons = glob([{}]),\n",
+                        starlark_string(format!("{}/**", icon_set.display()))
+                    ));
+                }
+                build_content.push_str(&format!(
+                    "    minimum_os_version = {},\n",
+                    starlark_string(minimum_os_version)
+                ));
+                if let Some(entitlements) = entitlements {
+                    build_content.push_str(&format!(
+                        "    entitlements = {},\n",
+                        starlark_string(entitlements)
+                    ));
+                }
+                if let Some(profile) = &profile {
+                    build_content.push_str(&profile.attribute(&profile_rule));
+                }
+                if !extensions.is_empty() {
+                    build_content
+                        .push_str(&format!("    extensions = [{}],\n", extensions.join(", ")));
+                }
+                let frameworks: Vec<String> = index
+                    .shared_framework_labels(target, project)
+                    .iter()
+                    .map(starlark_string)
+                    .collect();
+                if !frameworks.is_empty() {
+                    build_content
+                        .push_str(&format!("    frameworks = [{}],\n", frameworks.join(", ")));
+                }
+                if platform == Platform::Ios {
+                    if let Some(watch_application) = index.watch_application(target, project) {
+                        build_content.push_str(&format!(
+                            "    watch_application = {},\n",
+                            starlark_string(watch_application)
+                        ));
+                    }
+                }
+                if let Some(resources) = resources.render() {
+                    build_content.push_str(&format!("    resources = {},\n", resources));
+                }
+                build_content.push_str(&linkopts_attribute(&bundle_linkopts));
+                build_content.push_str(&tags_attribute(&tags));
+                build_content.push_str(&format!(
+                    "    deps = [{}],\n",
+                    starlark_string(format!(":{}_lib", target_name_lower))
+                ));
+                build_content.push_str(")\n\n");
+            }
+            "app_extension" => {
+                build_content.push_str(
+                    &SwiftLibrary {
+                        name: format!("{}_lib", target_name_lower),
+                        module_name: &module_name,
+                        srcs: &source_files,
+                        fallback_globs: &source_globs,
+                        deps: &deps,
+                        copts: &copts,
+                        linkopts: &linkopts,
+                        bridging_header: bridging_header.as_deref(),
+                        generated_header: generated_header.as_deref(),
+                        data: None,
+                        testonly: false,
+                        tags: &library_tags,
+                    }
+                    .render(),
+                );
+
+                let infoplist = resolve_info_plist(
+                    target,
+                    project,
+                    package_dir,
+                    PlistKind::Extension,
+                    &mut written,
+                )?;
+                let entitlements =
+                    resolve_entitlements(target, project, package_dir, &mut written)?;
+                let profile = target.provisioning_profile(options);
+                let profile_rule = format!("{}_profile", target_name_lower);
+                if let Some(rule) = profile.as_ref().and_then(|p| p.render_rule(&profile_rule)) {
+                    apple_bzl_rules.insert("local_provisioning_profile");
+                    build_content.push_str(&rule);
+                }
+
+                let rule = apple_rule("extension");
+                build_content.push_str(&format!(
+                    "{}(\n    name = {},\n    bundle_id = {},\n    bundle_name = {},\n",
+                    rule,
+                    starlark_string(&target_name_lower),
+                    target.bundle_id_value().render(),
+                    starlark_string(target.product_name())
+                ));
+                if let Some(families) = &families {
+                    build_content.push_str(&format!("    families = {},\n", families));
+                }
+                build_content.push_str(&format!("    infoplists = {},\n", infoplist.render_list()));
+                build_content.push_str(&format!(
+                    "    minimum_os_version = {},\n",
+                    starlark_string(minimum_os_version)
+                ));
+                if let Some(entitlements) = entitlements {
+                    build_content.push_str(&format!(
+                        "    entitlements = {},\n",
+                        starlark_string(entitlements)
+                    ));
+                }
+                if let Some(profile) = &profile {
+                    build_content.push_str(&profile.attribute(&profile_rule));
+                }
+                if let Some(resources) = resources.render() {
+                    build_content.push_str(&format!("    resources = {},\n", resources));
+                }
+                build_content.push_str(&linkopts_attribute(&bundle_linkopts));
+                build_content.push_str(&tags_attribute(&tags));
+                build_content.push_str(&format!(
+                    "    deps = [{}],\n",
+                    starlark_string(format!(":{}_lib", target_name_lower))
+                ));
+                build_content.push_str("    visibility = [\"//visibility:public\"],\n");
+                build_content.push_str(")\n\n");
+            }
+            "unit_tests" => {
+                // Generate test target
+                build_content.push_str(
+                    &SwiftLibrary {
+                        name: format!("{}_lib", target_name_lower),
+                        module_name: &module_name,
+                        srcs: &source_files,
+                        fallback_globs: &source_globs,
+                        deps: &deps,
+                        copts: &copts,
+                        linkopts: &linkopts,
+                        bridging_header: bridging_header.as_deref(),
+                        generated_header: generated_header.as_deref(),
+                        data: resources.render(),
+                        testonly: true,
+                        tags: &library_tags,
+                    }
+                    .render(),
+                );
+
+                // Generate the platform's unit test rule
+                // Without an app, the bundle runs as a logic test, like the
+                // tests of a library in a project of its own
+                let test_host = index.test_host(target, project, &["Tests"], options);
+                if test_host.is_none() {
+                    verbose!(
+                        "{} has no app to host it; running it without a test host",
+                        target.name
+                    );
+                }
+                let rule = apple_rule("unit_test");
+                build_content.push_str(&format!(
+                    "{}(\n    name = {},\n    bundle_id = {},\n",
+                    rule,
+                    starlark_string(&target_name_lower),
+                    target.bundle_id_value().render()
+                ));
+                build_content.push_str(&format!(
+                    "    minimum_os_version = {},\n",
+                    starlark_string(minimum_os_version)
+                ));
+                if let Some(test_host) = test_host {
+                    build_content.push_str(&format!(
+                        "    test_host = {},\n",
+                        starlark_string(test_host)
+                    ));
+                }
+                if let Some(data) = test_data.render() {
+                    build_content.push_str(&format!("    data = {},\n", data));
+                }
+                build_content.push_str(&linkopts_attribute(&bundle_linkopts));
+                build_content.push_str(&tags_attribute(&tags));
+                build_content.push_str(&format!(
+                    "    deps = [{}],\n",
+                    starlark_string(format!(":{}_lib", target_name_lower))
+                ));
+                build_content.push_str(")\n\n");
+            }
+            "ui_tests" => {
+                // UI tests drive the host app from outside, so the app is only
+                // the test host and not a compile-time dependency
+                let mut test_deps: Vec<String> = target
+                    .dependencies
+                    .iter()
+                    .filter(|dep| !index.has_product(dep, project, "app"))
+                    .filter_map(|dep| index.dependency_label(dep, project, platform))
+                    .map(starlark_string)
+                    .chain(objc_label.iter().map(starlark_string))
+                    .collect();
+                test_deps.sort();
+                test_deps.dedup();
+
+                build_content.push_str(
+                    &SwiftLibrary {
+                        name: format!("{}_lib", target_name_lower),
+                        module_name: &module_name,
+                        srcs: &source_files,
+                        fallback_globs: &source_globs,
+                        deps: &test_deps,
+                        copts: &copts,
+                        linkopts: &linkopts,
+                        bridging_header: bridging_header.as_deref(),
+                        generated_header: generated_header.as_deref(),
+                        data: resources.render(),
+                        testonly: true,
+                        tags: &library_tags,
+                    }
+                    .render(),
+                );
+
+                // UI tests drive an app, so there's no running them without one
+                let Some(test_host) =
+                    index.test_host(target, project, &["UITests", "Tests"], options)
+                else {
+                    anyhow::bail!(
+                        "{} has no app to test: add the app to its dependencies in {}, or set test_host in its [targets.{}] table of {}",
+                        target.name,
+                        Path::new(&project.path).join("Project.swift").display(),
+                        target.name,
+                        Config::FILE_NAME
+                    );
+                };
+
+                let rule = apple_rule("ui_test");
+                build_content.push_str(&format!(
+                    "{}(\n    name = {},\n    bundle_id = {},\n",
+                    rule,
+                    starlark_string(&target_name_lower),
+                    target.bundle_id_value().render()
+                ));
+                build_content.push_str(&format!(
+                    "    minimum_os_version = {},\n",
+                    starlark_string(minimum_os_version)
+                ));
+                build_content.push_str(&format!(
+                    "    test_host = {},\n",
+                    starlark_string(test_host)
+                ));
+                if let Some(data) = test_data.render() {
+                    build_content.push_str(&format!("    data = {},\n", data));
+                }
+                build_content.push_str(&linkopts_attribute(&bundle_linkopts));
+                build_content.push_str(&tags_attribute(&tags));
+                build_content.push_str(&format!(
+                    "    deps = [{}],\n",
+                    starlark_string(format!(":{}_lib", target_name_lower))
+                ));
+                build_content.push_str(")\n\n");
+            }
+            "framework" => {
+                // The swift_library keeps the plain target name so dependents can
+                // link it directly; the ios_framework packages it as a bundle.
+                build_content.push_str(
+                    &SwiftLibrary {
+                        name: target_name_lower.clone(),
+                        module_name: &module_name,
+                        srcs: &source_files,
+                        fallback_globs: &source_globs,
+                        deps: &deps,
+                        copts: &copts,
+                        linkopts: &linkopts,
+                        bridging_header: bridging_header.as_deref(),
+                        generated_header: generated_header.as_deref(),
+                        data: None,
+                        testonly: false,
+                        tags: &library_tags,
+                    }
+                    .render(),
+                );
+
+                let infoplist = resolve_info_plist(
+                    target,
+                    project,
+                    package_dir,
+                    PlistKind::Framework,
+                    &mut written,
+                )?;
+
+                let rule = apple_rule("framework");
+                build_content.push_str(&format!(
+                    "{}(\n    name = {},\n    bundle_id = {},\n",
+                    rule,
+                    starlark_string(format!("{}_framework", target_name_lower)),
+                    target.bundle_id_value().render()
+                ));
+                build_content.push_str(&format!(
+                    "    bundle_name = {},\n",
+                    starlark_string(target.product_name())
+                ));
+                if let Some(families) = &families {
+                    build_content.push_str(&format!("    families = {},\n", families));
+                }
+                build_content.push_str(&format!("    infoplists = {},\n", infoplist.render_list()));
+                build_content.push_str(&format!(
+                    "    minimum_os_version = {},\n",
+                    starlark_string(minimum_os_version)
+                ));
+                if let Some(resources) = resources.render() {
+                    build_content.push_str(&format!("    resources = {},\n", resources));
+                }
+                build_content.push_str(&linkopts_attribute(&bundle_linkopts));
+                build_content.push_str(&tags_attribute(&tags));
+                build_content.push_str(&format!(
+                    "    deps = [{}],\n",
+                    starlark_string(format!(":{}", target_name_lower))
+                ));
+                build_content.push_str("    visibility = [\"//visibility:public\"],\n)\n\n");
+            }
+            "static_library" | "static_framework" => {
+                // Static products have no bundle of their own, so resources ride
+                // along as data and end up in the consuming app
+                build_content.push_str(
+                    &SwiftLibrary {
+                        name: target_name_lower.clone(),
+                        module_name: &module_name,
+                        srcs: &source_files,
+                        fallback_globs: &source_globs,
+                        deps: &deps,
+                        copts: &copts,
+                        linkopts: &linkopts,
+                        bridging_header: bridging_header.as_deref(),
+                        generated_header: generated_header.as_deref(),
+                        data: resources.render(),
+                        testonly: false,
+                        tags: &library_tags,
+                    }
+                    .render(),
+                );
+
+                let rule = apple_rule("static_framework");
+                build_content.push_str(&format!(
+                    "{}(\n    name = {},\n    bundle_name = {},\n",
+                    rule,
+                    starlark_string(format!("{}_framework", target_name_lower)),
+                    starlark_string(target.product_name())
+                ));
+                build_content.push_str(&format!(
+                    "    minimum_os_version = {},\n",
+                    starlark_string(minimum_os_version)
+                ));
+                build_content.push_str(&tags_attribute(&tags));
+                build_content.push_str(&format!(
+                    "    deps = [{}],\n",
+                    starlark_string(format!(":{}", target_name_lower))
+                ));
+                build_content.push_str("    visibility = [\"//visibility:public\"],\n)\n\n");
+            }
+            "bundle" => {
+                // Resource bundles carry no code; dependents pick them up
+                // through their resources
+                if !source_files.is_empty() || objc_label.is_some() {
+                    warning!(
+                        "{} is a resource bundle; its sources are left out",
+                        target.name
+                    );
+                }
+
+                resource_rules.insert("apple_resource_bundle");
+                build_content.push_str(&format!(
+                    "apple_resource_bundle(\n    name = {},\n    bundle_name = {},\n",
+                    starlark_string(&target_name_lower),
+                    starlark_string(target.product_name())
+                ));
+                if !target.bundle_id.is_empty() {
+                    build_content.push_str(&format!(
+                        "    bundle_id = {},\n",
+                        starlark_string(&target.bundle_id)
+                    ));
+                }
+                if let Some(resources) = resources.render() {
+                    build_content.push_str(&format!("    resources = {},\n", resources));
+                }
+                build_content.push_str(&tags_attribute(&tags));
+                build_content.push_str("    visibility = [\"//visibility:public\"],\n)\n\n");
+            }
+            _ => {
+                // Default to library
+                build_content.push_str(
+                    &SwiftLibrary {
+                        name: target_name_lower.clone(),
+                        module_name: &module_name,
+                        srcs: &source_files,
+                        fallback_globs: &source_globs,
+                        deps: &deps,
+                        copts: &copts,
+                        linkopts: &linkopts,
+                        bridging_header: bridging_header.as_deref(),
+                        generated_header: generated_header.as_deref(),
+                        data: resources.render(),
+                        testonly: false,
+                        tags: &library_tags,
+                    }
+                    .render(),
+                );
+            }
+        }
+    }
+
+    if let Some(suite) = project_test_suite(project, index) {
+        build_content.push_str(&suite);
+    }
+
+    let mut header = String::new();
+    for (platform, rules) in &apple_rules {
+        let symbols: Vec<String> = rules.iter().map(starlark_string).collect();
+        header.push_str(&format!(
+            "load(\"@build_bazel_rules_apple//apple:{}.bzl\", {})\n",
+            platform.prefix(),
+            symbols.join(", ")
+        ));
+    }
+    if !apple_bzl_rules.is_empty() {
+        let symbols: Vec<String> = apple_bzl_rules.iter().map(starlark_string).collect();
+        header.push_str(&format!(
+            "load(\"@build_bazel_rules_apple//apple:apple.bzl\", {})\n",
+            symbols.join(", ")
+        ));
+    }
+    if !resource_rules.is_empty() {
+        let symbols: Vec<String> = resource_rules.iter().map(starlark_string).collect();
+        header.push_str(&format!(
+            "load(\"@build_bazel_rules_apple//apple:resources.bzl\", {})\n",
+            symbols.join(", ")
+        ));
+    }
+    header.push_str("load(\"@build_bazel_rules_swift//swift:swift.bzl\", \"swift_library\")\n\n");
+    if uses_debug_config_setting {
+        header.push_str(&debug_config_setting_rule());
+    }
+
+    if let Some(extra) = read_build_extra(project)? {
+        build_content.push_str(&format!("# From {}\n{}", BUILD_EXTRA_FILE, extra));
+        if !extra.ends_with('\n') {
+            build_content.push('\n');
+        }
+    }
+
+    let build_path = options.build_file(package_dir);
+    write_generated_file(&build_path, header + &build_content)
+        .context("Failed to write BUILD file")?;
+
+    info!("Generated: {}", build_path.display());
+    written.push(build_path);
+
+    Ok(written)
+}
+
+/// Hand-written rules appended verbatim to the generated BUILD file of the
+/// project whose directory holds this file, e.g. a genrule catalyst can't
+/// infer from the graph.
+const BUILD_EXTRA_FILE: &str = "BUILD.catalyst-extra";
+
+/// The contents of the project's `BUILD.catalyst-extra`, if it has one.
+pub(crate) fn read_build_extra(project: &TuistProject) -> Result<Option<String>> {
+    let path = Path::new(&project.path).join(BUILD_EXTRA_FILE);
+    match fs::read_to_string(&path) {
+        Ok(content) => Ok(Some(content)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Turns `name` into a valid Swift identifier the way Xcode derives module
+/// names, replacing the characters an identifier can't hold, like `-` and
+/// `.`, with underscores and prefixing one that starts with a digit.
+pub(crate) fn swift_identifier(name: &str) -> String {
+    let mut identifier: String = name
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+/// The modules a Swift or Objective-C source file imports: Swift `import`
+/// declarations, with their attributes and access modifiers, and Objective-C
+/// `@import` and framework-style `#import <Module/Header.h>` lines. Only the
+/// top-level module of a submodule or declaration import counts.
+pub(crate) fn imported_modules(source: &str) -> impl Iterator<Item = &str> {
+    source.lines().filter_map(|line| {
+        let line = line.trim_start();
+        let path = if let Some(rest) = line.strip_prefix("@import ") {
+            rest
+        } else if let Some(rest) = line
+            .strip_prefix("#import <")
+            .or_else(|| line.strip_prefix("#include <"))
+        {
+            rest.split_once('/')?.0
+        } else {
+            let mut words = line.split_whitespace().skip_while(|word| {
+                word.starts_with('@')
+                    || matches!(
+                        *word,
+                        "public" | "package" | "internal" | "fileprivate" | "private"
+                    )
+            });
+            if words.next()? != "import" {
+                return None;
+            }
+            let path = words.next()?;
+            if matches!(
+                path,
+                "struct" | "class" | "enum" | "protocol" | "typealias" | "func" | "let" | "var"
+            ) {
+                words.next()?
+            } else {
+                path
+            }
+        };
+        let module = path
+            .split(|c: char| c == '.' || c == ';' || c.is_whitespace())
+            .next()?;
+        (!module.is_empty() && module.chars().all(|c| c.is_alphanumeric() || c == '_'))
+            .then_some(module)
+    })
+}
+
+/// The tags of the rules generated for `target`, rendered: the kind of
+/// product, like `unit_test` or `ios_app`, the name of its project, and the
+/// extra tags its `[targets.<name>]` table in `catalyst.toml` lists.
+fn target_tags(
+    target: &TuistTarget,
+    project: &TuistProject,
+    options: &GenerateOptions,
+) -> Vec<String> {
+    let product = match target.product.as_str() {
+        "app" => format!("{}_app", target.platform().prefix()),
+        "app_extension" => "app_extension".to_string(),
+        "unit_tests" => "unit_test".to_string(),
+        "ui_tests" => "ui_test".to_string(),
+        "framework" => "framework".to_string(),
+        "bundle" => "resource_bundle".to_string(),
+        _ => "library".to_string(),
+    };
+    let extra = options
+        .targets
+        .get(&target.name)
+        .map(|overrides| overrides.tags.as_slice())
+        .unwrap_or_default();
+
+    let mut tags: Vec<String> = Vec::new();
+    for tag in [&product, &project.name].into_iter().chain(extra) {
+        let tag = starlark_string(tag);
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// The `linkopts` attribute line of a bundle rule, or nothing without any.
+fn linkopts_attribute(linkopts: &[String]) -> String {
+    if linkopts.is_empty() {
+        String::new()
+    } else {
+        let linkopts: Vec<String> = linkopts.iter().map(starlark_string).collect();
+        format!("    linkopts = [{}],\n", linkopts.join(", "))
+    }
+}
+
+/// The `tags` attribute line for rendered tags, or nothing without any.
+fn tags_attribute(tags: &[String]) -> String {
+    if tags.is_empty() {
+        String::new()
+    } else {
+        format!("    tags = [{}],\n", tags.join(", "))
+    }
+}
+
+/// How a bundle finds its provisioning profile for device builds. Simulator
+/// builds don't need one.
+#[derive(Debug, PartialEq)]
+pub(crate) enum ProvisioningProfile {
+    /// A `.mobileprovision` file, relative to the project directory
+    File(PathBuf),
+    /// A profile installed on the building machine, which rules_apple's
+    /// `local_provisioning_profile` looks up by name
+    Local {
+        name: String,
+        team_id: Option<String>,
+    },
+}
+
+impl ProvisioningProfile {
+    /// The `local_provisioning_profile` rule `name` declares for an installed
+    /// profile, if one is needed.
+    fn render_rule(&self, name: &str) -> Option<String> {
+        let Self::Local {
+            name: profile_name,
+            team_id,
+        } = self
+        else {
+            return None;
+        };
+        let mut rule = format!(
+            "local_provisioning_profile(\n    name = {},\n    profile_name = {},\n",
+            starlark_string(name),
+            starlark_string(profile_name)
+        );
+        if let Some(team_id) = team_id {
+            rule.push_str(&format!("    team_id = {},\n", starlark_string(team_id)));
+        }
+        rule.push_str(")\n\n");
+        Some(rule)
+    }
+
+    /// The `provisioning_profile` attribute pointing at the profile, given
+    /// the name of its rule.
+    fn attribute(&self, rule_name: &str) -> String {
+        let label = match self {
+            Self::File(path) => starlark_string(path.display()),
+            Self::Local { .. } => starlark_string(format!(":{}", rule_name)),
+        };
+        format!("    provisioning_profile = {},\n", label)
+    }
+}
+
+/// The attributes of a generated swift_library rule.
+struct SwiftLibrary<'a> {
+    name: String,
+    module_name: &'a str,
+    srcs: &'a [String],
+    /// Rendered patterns globbed for `srcs` when the target has no resolved
+    /// Swift sources
+    fallback_globs: &'a [String],
+    deps: &'a [String],
+    /// Swift compiler flags, e.g. `-DDEBUG`
+    copts: &'a [String],
+    /// Linker flags for the binaries the library ends up in
+    linkopts: &'a [String],
+    /// Objective-C header imported into the module, relative to the package
+    bridging_header: Option<&'a str>,
+    /// Name of the Objective-C header generated for the Swift interface
+    generated_header: Option<&'a str>,
+    /// Rendered value for the `data` attribute
+    data: Option<String>,
+    testonly: bool,
+    /// Rendered tags, including `manual` for rules `//...` should skip
+    tags: &'a [String],
+}
+
+impl SwiftLibrary<'_> {
+    fn render(&self) -> String {
+        let mut rule = format!(
+            "swift_library(\n    name = {},\n",
+            starlark_string(&self.name)
+        );
+
+        if !self.srcs.is_empty() {
+            rule.push_str(&format!(
+                "    srcs = [\n        {},\n    ],\n",
+                self.srcs.join(",\n        ")
+            ));
+        } else {
+            rule.push_str(&format!(
+                "    srcs = glob([{}]),\n",
+                self.fallback_globs.join(", ")
+            ));
+        }
+
+        rule.push_str(&format!(
+            "    module_name = {},\n",
+            starlark_string(self.module_name)
+        ));
+
+        let mut copts: Vec<String> = self.copts.iter().map(starlark_string).collect();
+        if let Some(header) = self.bridging_header {
+            copts.push("\"-import-objc-header\"".to_string());
+            copts.push(starlark_string(format!("$(location {})", header)));
+        }
+        if !copts.is_empty() {
+            rule.push_str(&format!("    copts = [{}],\n", copts.join(", ")));
+        }
+
+        if !self.linkopts.is_empty() {
+            let linkopts: Vec<String> = self.linkopts.iter().map(starlark_string).collect();
+            rule.push_str(&format!("    linkopts = [{}],\n", linkopts.join(", ")));
+        }
+
+        if let Some(header) = self.bridging_header {
+            rule.push_str(&format!(
+                "    swiftc_inputs = [{}],\n",
+                starlark_string(header)
+            ));
+        }
+
+        if let Some(header) = self.generated_header {
+            rule.push_str("    generates_header = True,\n");
+            rule.push_str(&format!(
+                "    generated_header_name = {},\n",
+                starlark_string(header)
+            ));
+        }
+
+        if let Some(data) = &self.data {
+            rule.push_str(&format!("    data = {},\n", data));
+        }
+
+        if self.testonly {
+            rule.push_str("    testonly = True,\n");
+        }
+
+        rule.push_str(&tags_attribute(self.tags));
+
+        if !self.deps.is_empty() {
+            rule.push_str(&format!("    deps = [{}],\n", self.deps.join(", ")));
+        }
+
+        rule.push_str("    visibility = [\"//visibility:public\"],\n)\n\n");
+        rule
+    }
+}
+
+/// The attributes of a generated objc_library rule.
+struct ObjcLibrary<'a> {
+    name: &'a str,
+    module_name: &'a str,
+    srcs: &'a [String],
+    hdrs: &'a [String],
+    deps: &'a [String],
+    /// The target's SDK dependencies, which the objc_library links for it
+    sdks: &'a [&'a SdkReference],
+    testonly: bool,
+    /// Rendered tags, including `manual` for rules `//...` should skip
+    tags: &'a [String],
+}
+
+impl ObjcLibrary<'_> {
+    fn render(&self) -> String {
+        let mut rule = format!(
+            "objc_library(\n    name = {},\n",
+            starlark_string(self.name)
+        );
+
+        if !self.srcs.is_empty() {
+            rule.push_str(&format!(
+                "    srcs = [\n        {},\n    ],\n",
+                self.srcs.join(",\n        ")
+            ));
+        }
+
+        if !self.hdrs.is_empty() {
+            rule.push_str(&format!(
+                "    hdrs = [\n        {},\n    ],\n",
+                self.hdrs.join(",\n        ")
+            ));
+        }
+
+        // Swift code in the same target imports it as `<Module>_ObjC`
+        rule.push_str(&format!(
+            "    module_name = {},\n",
+            starlark_string(format!("{}_ObjC", self.module_name))
+        ));
+        rule.push_str("    enable_modules = True,\n");
+
+        if self.testonly {
+            rule.push_str("    testonly = True,\n");
+        }
+
+        rule.push_str(&tags_attribute(self.tags));
+
+        if !self.deps.is_empty() {
+            rule.push_str(&format!("    deps = [{}],\n", self.deps.join(", ")));
+        }
+
+        // objc_library has no attribute for weakly linked libraries, so they
+        // stay linker flags
+        let frameworks = |weak: bool| -> Vec<String> {
+            self.sdks
+                .iter()
+                .filter(|sdk| sdk.is_weak() == weak)
+                .filter_map(|sdk| sdk.framework())
+                .map(starlark_string)
+                .collect()
+        };
+        let dylibs: Vec<String> = self
+            .sdks
+            .iter()
+            .filter(|sdk| !sdk.is_weak())
+            .filter_map(|sdk| sdk.dylib())
+            .map(starlark_string)
+            .collect();
+        let linkopts: Vec<String> = self
+            .sdks
+            .iter()
+            .filter(|sdk| sdk.is_weak() && sdk.dylib().is_some())
+            .flat_map(|sdk| sdk.linkopts())
+            .map(starlark_string)
+            .collect();
+        for (attr, values) in [
+            ("sdk_frameworks", frameworks(false)),
+            ("weak_sdk_frameworks", frameworks(true)),
+            ("sdk_dylibs", dylibs),
+            ("linkopts", linkopts),
+        ] {
+            if !values.is_empty() {
+                rule.push_str(&format!("    {} = [{}],\n", attr, values.join(", ")));
+            }
+        }
+
+        rule.push_str("    visibility = [\"//visibility:public\"],\n)\n\n");
+        rule
+    }
+}
+
+/// Objective-C source and header extensions.
+pub(crate) const OBJC_EXTENSIONS: &[&str] = &["m", "mm", "h"];
+
+/// File extensions bundled as resources.
+pub(crate) const RESOURCE_EXTENSIONS: &[&str] = &[
+    "storyboard",
+    "xib",
+    "strings",
+    "stringsdict",
+    "xcstrings",
+    "json",
+    "plist",
+    "png",
+    "jpg",
+    "jpeg",
+    "gif",
+    "pdf",
+    "svg",
+    "ttf",
+    "otf",
+    "mp3",
+    "wav",
+    "caf",
+    "m4a",
+    "mp4",
+    "mov",
+    "txt",
+    "html",
+    "css",
+    "js",
+];
+
+/// Resource extensions that rules_apple compiles, which only works for
+/// resources that don't keep their directory structure.
+const COMPILED_RESOURCE_EXTENSIONS: &[&str] =
+    &["storyboard", "xib", "strings", "stringsdict", "xcstrings"];
+
+/// Directory extensions whose whole contents form a single resource.
+/// Localization directories are globbed whole too: rules_apple places files
+/// under a `.lproj` directory into the matching `.lproj` of the bundle, so
+/// their paths have to keep that directory.
+const RESOURCE_DIRECTORY_EXTENSIONS: &[&str] = &["xcassets", "scnassets", "lproj"];
+
+/// If `path` is, or lives inside, a prebuilt `.bundle`, like the resources of
+/// a third-party SDK, returns the outermost one, which ships whole through an
+/// `apple_bundle_import`.
+pub(crate) fn bundle_directory(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .filter(|dir| dir.extension().is_some_and(|ext| ext == "bundle"))
+        .last()
+        .map(Path::to_path_buf)
+}
+
+/// Name of the `apple_bundle_import` of `bundle` in the target named
+/// `target_name` (lowercased), e.g. `app_paymentssdk_bundle`.
+pub(crate) fn bundle_import_name(target_name: &str, bundle: &Path) -> String {
+    let stem: String = bundle
+        .file_stem()
+        .map(|stem| stem.to_string_lossy())
+        .unwrap_or_default()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{}_{}_bundle", target_name, stem)
+}
+
+/// If `rel_path` is, or lives inside, a directory resource such as an asset
+/// catalog, returns that directory.
+pub(crate) fn resource_directory(rel_path: &Path) -> Option<PathBuf> {
+    let mut dir = PathBuf::new();
+    for component in rel_path.components() {
+        dir.push(component);
+        let is_resource_dir = dir.extension().is_some_and(|ext| {
+            RESOURCE_DIRECTORY_EXTENSIONS.contains(&ext.to_string_lossy().as_ref())
+        });
+        if is_resource_dir {
+            return Some(dir);
+        }
+    }
+    None
+}
+
+/// Resources collected for a target: loose files are listed individually and
+/// directory resources are globbed, since Bazel can't reference a directory as
+/// a single source file.
+#[derive(Debug, Default)]
+struct Resources {
+    files: Vec<String>,
+    directories: Vec<String>,
+    /// Files that keep their path below their buildable folder in the
+    /// bundle, with `flatten_resources = false`
+    structured: Vec<String>,
+    /// The buildable folders of `structured`, stripped from their paths
+    strip_prefixes: Vec<String>,
+}
+
+impl Resources {
+    fn add_file(&mut self, rel_path: &Path) {
+        self.files.push(starlark_string(rel_path.display()));
+    }
+
+    /// Adds the label of a target providing resources, e.g. a resource bundle.
+    fn add_label(&mut self, label: &str) {
+        self.files.push(starlark_string(label));
+    }
+
+    fn add_structured(&mut self, folder: &Path, rel_path: &Path) {
+        self.structured.push(starlark_string(rel_path.display()));
+        let prefix = starlark_string(folder.display());
+        if !self.strip_prefixes.contains(&prefix) {
+            self.strip_prefixes.push(prefix);
+        }
+    }
+
+    fn add_directory(&mut self, rel_path: PathBuf) {
+        let pattern = starlark_string(format!("{}/**", rel_path.display()));
+        if !self.directories.contains(&pattern) {
+            self.directories.push(pattern);
+        }
+    }
+
+    /// Sorts and deduplicates the entries so the rendered value is stable.
+    fn sort(&mut self) {
+        self.files.sort();
+        self.files.dedup();
+        self.directories.sort();
+        self.structured.sort();
+        self.structured.dedup();
+        self.strip_prefixes.sort();
+    }
+
+    /// Renders an `apple_resource_group` named `name` that bundles the
+    /// structured files below their buildable folder, if there are any.
+    fn render_structured_group(&self, name: &str) -> Option<String> {
+        if self.structured.is_empty() {
+            return None;
+        }
+        Some(format!(
+            "apple_resource_group(\n    name = {},\n    structured_resources = [{}],\n    strip_structured_resources_prefixes = [{}],\n)\n\n",
+            starlark_string(name),
+            self.structured.join(", "),
+            self.strip_prefixes.join(", ")
+        ))
+    }
+
+    /// Renders the attribute value, e.g. `["a.json"] + glob(["A.xcassets/**"])`.
+    fn render(&self) -> Option<String> {
+        let files = (!self.files.is_empty()).then(|| format!("[{}]", self.files.join(", ")));
+        let globs = (!self.directories.is_empty())
+            .then(|| format!("glob([{}])", self.directories.join(", ")));
+
+        match (files, globs) {
+            (Some(files), Some(globs)) => Some(format!("{} + {}", files, globs)),
+            (files, globs) => files.or(globs),
+        }
+    }
+}
+
+/// Whether `path`, or a directory it's in, matches a glob of `catalyst.toml`:
+/// `*` and `?` match within a path component and `**` any number of them.
+pub(crate) fn glob_matches(pattern: &str, path: &Path) -> bool {
+    let pattern: Vec<&str> = pattern
+        .split('/')
+        .filter(|component| !component.is_empty() && *component != ".")
+        .collect();
+    let path: Vec<String> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+        .collect();
+    (1..=path.len()).any(|len| components_match(&pattern, &path[..len]))
+}
+
+fn components_match(pattern: &[&str], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| components_match(rest, &path[skip..])),
+        Some((first, rest)) => path.split_first().is_some_and(|(component, remaining)| {
+            let first: Vec<char> = first.chars().collect();
+            let component: Vec<char> = component.chars().collect();
+            wildcard_match(&first, &component) && components_match(rest, remaining)
+        }),
+    }
+}
+
+/// Whether a name, like a target's, matches a glob with `*` and `?`.
+pub(crate) fn name_matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    wildcard_match(&pattern, &name)
+}
+
+fn wildcard_match(pattern: &[char], text: &[char]) -> bool {
+    match pattern.split_first() {
+        None => text.is_empty(),
+        Some(('*', rest)) => (0..=text.len()).any(|skip| wildcard_match(rest, &text[skip..])),
+        Some(('?', rest)) => !text.is_empty() && wildcard_match(rest, &text[1..]),
+        Some((c, rest)) => text.first() == Some(c) && wildcard_match(rest, &text[1..]),
+    }
+}
+
+/// Whether `id` is a reverse-DNS bundle identifier: at least two
+/// dot-separated components of ASCII letters, digits, and hyphens.
+pub(crate) fn is_valid_bundle_id(id: &str) -> bool {
+    let components: Vec<&str> = id.split('.').collect();
+    components.len() >= 2
+        && components.iter().all(|component| {
+            !component.is_empty()
+                && component
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn swift_identifier_replaces_hyphens_and_dots() {
+        assert_eq!(swift_identifier("My-Feature"), "My_Feature");
+        assert_eq!(swift_identifier("Core.Networking"), "Core_Networking");
+    }
+
+    #[test]
+    fn swift_identifier_prefixes_a_leading_digit() {
+        assert_eq!(swift_identifier("3DTouch"), "_3DTouch");
+    }
+}
@@ -0,0 +1,582 @@
+//! The catalyst cache: the cached graph, package stamps, and generated files.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::config::{GenerateOptions, Platform};
+use crate::graph::{TuistGraph, TuistProject};
+use crate::index::TargetIndex;
+use crate::output::{verbosity, PhaseTimer, Verbosity};
+use crate::tuist::{hash_buildable_folders, hash_manifest_files, read_graph_file, run_tuist_graph};
+
+/// Fingerprint of everything the Tuist graph is derived from. A cached graph is
+/// reused only while all three parts are unchanged.
+#[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct GraphCacheKey {
+    catalyst_version: String,
+    /// Hash of the Tuist manifest files
+    manifests: String,
+    /// Hash of each manifest file, by path relative to the project directory,
+    /// to tell which ones changed
+    #[serde(default)]
+    manifest_files: BTreeMap<String, String>,
+    /// Hash of the file listing of every buildable folder in the graph, since
+    /// Tuist resolves those folders' contents when it builds the graph
+    buildable_folders: String,
+}
+
+impl GraphCacheKey {
+    pub(crate) const FILE_NAME: &'static str = "graph-key.json";
+
+    pub(crate) fn compute(project_dir: &Path, graph: &TuistGraph) -> Result<Self> {
+        let (manifests, manifest_files) = hash_manifest_files(project_dir)?;
+        Ok(Self {
+            catalyst_version: env!("CARGO_PKG_VERSION").to_string(),
+            manifests,
+            manifest_files,
+            buildable_folders: hash_buildable_folders(graph),
+        })
+    }
+
+    /// Why a graph cached with the `stored` key is stale, naming the manifest
+    /// files that were added, removed, or changed since.
+    fn differences(&self, stored: &Self) -> Vec<String> {
+        let mut reasons = Vec::new();
+        if self.catalyst_version != stored.catalyst_version {
+            reasons.push(format!(
+                "catalyst was updated from {} to {}",
+                stored.catalyst_version, self.catalyst_version
+            ));
+        }
+        if self.manifests != stored.manifests {
+            let paths: BTreeSet<&String> = self
+                .manifest_files
+                .keys()
+                .chain(stored.manifest_files.keys())
+                .collect();
+            let changes: Vec<String> = paths
+                .into_iter()
+                .filter_map(|path| {
+                    let change = match (
+                        self.manifest_files.get(path),
+                        stored.manifest_files.get(path),
+                    ) {
+                        (Some(_), None) => "added",
+                        (None, Some(_)) => "removed",
+                        (Some(current), Some(cached)) if current != cached => "changed",
+                        _ => return None,
+                    };
+                    Some(format!("{} ({})", path, change))
+                })
+                .collect();
+            // Keys from older releases don't hash each file
+            if changes.is_empty() {
+                reasons.push("manifests changed".to_string());
+            } else {
+                reasons.push(format!("manifests changed: {}", changes.join(", ")));
+            }
+        }
+        if self.buildable_folders != stored.buildable_folders {
+            reasons.push("files were added to or removed from buildable folders".to_string());
+        }
+        if reasons.is_empty() && self != stored {
+            reasons.push("the cached key is from an older catalyst".to_string());
+        }
+        reasons
+    }
+}
+
+/// The Tuist graph as last read, in the project's cache directory.
+pub(crate) const GRAPH_CACHE_FILE: &str = "graph.json";
+
+/// Loads the Tuist graph for `project_dir`. The graph stored in the project's
+/// cache directory is reused unless `no_cache` is set or the key recorded with
+/// it no longer matches; otherwise `tuist graph` runs and the cache is updated.
+pub fn load_tuist_graph(
+    project_dir: &Path,
+    cache_dir: &Path,
+    tuist: &Path,
+    no_cache: bool,
+) -> Result<TuistGraph> {
+    let project_cache_dir = get_project_cache_dir(cache_dir, project_dir)?;
+    let graph_path = project_cache_dir.join(GRAPH_CACHE_FILE);
+    let key_path = project_cache_dir.join(GraphCacheKey::FILE_NAME);
+
+    if !no_cache {
+        let timer = PhaseTimer::start("graph cache lookup");
+        let graph = read_cached_graph(project_dir, &graph_path, &key_path);
+        drop(timer);
+        if verbosity() >= Verbosity::Verbose {
+            explain_graph_cache(project_dir, &graph_path, &key_path, graph.is_some());
+        }
+        if let Some(graph) = graph {
+            info!("Reusing cached Tuist graph: {}", graph_path.display());
+            return Ok(graph);
+        }
+    }
+
+    let graph = run_tuist_graph(tuist, project_dir)?;
+
+    let _timer = PhaseTimer::start("graph cache update");
+    fs::create_dir_all(&project_cache_dir)?;
+    let key = GraphCacheKey::compute(project_dir, &graph)?;
+    fs::write(&graph_path, serde_json::to_string_pretty(&graph)?)
+        .context("Failed to write graph cache")?;
+    fs::write(&key_path, serde_json::to_string_pretty(&key)?)
+        .context("Failed to write graph cache key")?;
+
+    verbose!("Saved graph metadata to: {}", graph_path.display());
+
+    Ok(graph)
+}
+
+/// The project's Tuist graph: the one `options.graph` points at, or else
+/// the output of `tuist graph`, reused from catalyst's cache when it's
+/// current unless `no_cache` is set. A graph read from a file is never
+/// cached, so it can't pass for tuist's output in a later run.
+pub fn load_graph(
+    project_dir: &Path,
+    cache_dir: &Path,
+    options: &GenerateOptions,
+    no_cache: bool,
+) -> Result<TuistGraph> {
+    match &options.graph {
+        Some(path) => {
+            if path == Path::new("-") {
+                info!("Reading Tuist graph from stdin");
+            } else {
+                info!("Reading Tuist graph: {}", path.display());
+            }
+            read_graph_file(path)
+        }
+        None => load_tuist_graph(project_dir, cache_dir, &options.tuist, no_cache),
+    }
+}
+
+/// Prints the key of the cached graph next to the current one, and whether the
+/// graph is reused, with the reasons when it isn't: to debug catalyst running
+/// tuist when nothing changed, or reusing a graph after a manifest edit.
+fn explain_graph_cache(project_dir: &Path, graph_path: &Path, key_path: &Path, hit: bool) {
+    let stored: Option<GraphCacheKey> = fs::read_to_string(key_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    let graph: Option<TuistGraph> = fs::read_to_string(graph_path)
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok());
+    let (Some(stored), Some(graph)) = (stored, graph) else {
+        verbose!(
+            "Graph cache miss: no cached graph in {}",
+            graph_path.display()
+        );
+        return;
+    };
+    let current = match GraphCacheKey::compute(project_dir, &graph) {
+        Ok(current) => current,
+        Err(err) => {
+            verbose!("Graph cache miss: can't hash the manifests: {:#}", err);
+            return;
+        }
+    };
+
+    verbose!(
+        "Graph cache key: manifests {} (cached {}), buildable folders {} (cached {})",
+        current.manifests,
+        stored.manifests,
+        current.buildable_folders,
+        stored.buildable_folders
+    );
+    if hit {
+        verbose!("Graph cache hit: graph reused");
+        return;
+    }
+    verbose!(
+        "Graph cache miss: {}",
+        current.differences(&stored).join("; ")
+    );
+}
+
+/// Returns the cached graph if it is still fresh. Any problem reading the
+/// cache is treated as a miss.
+pub(crate) fn read_cached_graph(
+    project_dir: &Path,
+    graph_path: &Path,
+    key_path: &Path,
+) -> Option<TuistGraph> {
+    let stored: GraphCacheKey = serde_json::from_str(&fs::read_to_string(key_path).ok()?).ok()?;
+    let graph: TuistGraph = serde_json::from_str(&fs::read_to_string(graph_path).ok()?).ok()?;
+    let current = GraphCacheKey::compute(project_dir, &graph).ok()?;
+
+    (stored == current).then_some(graph)
+}
+
+/// Environment variable that moves the catalyst cache, e.g. to a scratch
+/// volume on CI machines whose home directory isn't writable.
+pub const CACHE_DIR_ENV: &str = "CATALYST_CACHE_DIR";
+
+static CACHE_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// Overrides the cache directory, as `--cache-dir` does. Takes precedence
+/// over `CATALYST_CACHE_DIR`.
+pub fn set_cache_dir(dir: Option<PathBuf>) {
+    *CACHE_DIR.lock().unwrap_or_else(|err| err.into_inner()) = dir;
+}
+
+/// The catalyst cache directory: the one set with `set_cache_dir`, then
+/// `CATALYST_CACHE_DIR`, and otherwise `catalyst` in the user's cache
+/// directory. Fails when the directory can't be created or written to.
+pub fn get_catalyst_cache_dir() -> Result<PathBuf> {
+    let configured = CACHE_DIR
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone();
+    let (cache_dir, source) = match configured {
+        Some(dir) => (dir, "--cache-dir"),
+        None => match std::env::var_os(CACHE_DIR_ENV).filter(|dir| !dir.is_empty()) {
+            Some(dir) => (PathBuf::from(dir), CACHE_DIR_ENV),
+            None => (
+                dirs::cache_dir()
+                    .context("Failed to determine cache directory")?
+                    .join("catalyst"),
+                "the user cache directory",
+            ),
+        },
+    };
+
+    // Fail up front rather than on the first cache write, halfway through
+    let probe = cache_dir.join(format!(".write-check-{}", std::process::id()));
+    fs::create_dir_all(&cache_dir)
+        .and_then(|()| fs::write(&probe, b""))
+        .and_then(|()| fs::remove_file(&probe))
+        .with_context(|| {
+            format!(
+                "Cache directory {} (from {}) isn't writable; pass --cache-dir or set {} to a writable directory",
+                cache_dir.display(),
+                source,
+                CACHE_DIR_ENV
+            )
+        })?;
+
+    Ok(cache_dir)
+}
+
+/// Cache directory for a single project, keyed by a hash of its canonical path.
+pub(crate) fn get_project_cache_dir(cache_dir: &Path, project_dir: &Path) -> Result<PathBuf> {
+    let canonical = project_dir.canonicalize().with_context(|| {
+        format!(
+            "Failed to resolve project directory {}",
+            project_dir.display()
+        )
+    })?;
+
+    let mut hasher = DefaultHasher::new();
+    canonical.hash(&mut hasher);
+
+    Ok(cache_dir
+        .join("projects")
+        .join(format!("{:016x}", hasher.finish())))
+}
+
+/// What each project's BUILD file was generated from, so projects that
+/// didn't change since the last generation keep their files as they are.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct PackageStamps {
+    /// Keyed by project path
+    pub(crate) packages: BTreeMap<String, PackageStamp>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub(crate) struct PackageStamp {
+    /// Hash of everything the project's generated files are derived from
+    pub(crate) inputs: String,
+    pub(crate) files: Vec<PathBuf>,
+    /// Hash of the contents of `files`, so edited or deleted files are
+    /// regenerated
+    pub(crate) contents: String,
+    /// The xcconfig files the project's settings were read from
+    #[serde(default)]
+    pub(crate) xcconfigs: Vec<PathBuf>,
+}
+
+impl PackageStamps {
+    const FILE_NAME: &'static str = "packages.json";
+
+    pub(crate) fn load(project_cache_dir: &Path) -> Self {
+        fs::read_to_string(project_cache_dir.join(Self::FILE_NAME))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    pub(crate) fn save(&self, project_cache_dir: &Path) -> Result<()> {
+        fs::create_dir_all(project_cache_dir)?;
+        fs::write(
+            project_cache_dir.join(Self::FILE_NAME),
+            serde_json::to_string_pretty(self)?,
+        )
+        .context("Failed to write package stamps")
+    }
+
+    /// The files generated for `project` last time, if its inputs still
+    /// hash to `inputs` and the files are untouched.
+    pub(crate) fn unchanged_files(&self, project: &str, inputs: &str) -> Option<&[PathBuf]> {
+        let stamp = self.packages.get(project)?;
+        (stamp.inputs == inputs && hash_file_contents(&stamp.files)? == stamp.contents)
+            .then_some(stamp.files.as_slice())
+    }
+}
+
+/// Hashes the paths and contents of `files`, or `None` if one is missing.
+pub(crate) fn hash_file_contents(files: &[PathBuf]) -> Option<String> {
+    let mut hasher = DefaultHasher::new();
+    for path in files {
+        path.hash(&mut hasher);
+        fs::read(path).ok()?.hash(&mut hasher);
+    }
+    Some(format!("{:016x}", hasher.finish()))
+}
+
+/// Hashes everything a project's generated files depend on besides its own
+/// definition: the generation options, the targets of every project, which
+/// the project's dependency labels and platform settings are derived from,
+/// the vendored frameworks, and the shared frameworks apps embed.
+pub(crate) fn workspace_inputs_hash(
+    projects: &[TuistProject],
+    index: &TargetIndex,
+    options: &GenerateOptions,
+) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    // Settings that don't end up in BUILD files stay out of the hash
+    format!(
+        "{:?}",
+        GenerateOptions {
+            force: false,
+            no_cache: false,
+            graph: None,
+            bazel_args: Vec::new(),
+            platform: None,
+            jobs: None,
+            defines: BTreeMap::new(),
+            update_gitignore: false,
+            strict: false,
+            keep_going: false,
+            fail_on_warnings: false,
+            add_target: None,
+            bazel_version: String::new(),
+            default_run_target: None,
+            bazel_output_base: None,
+            ..options.clone()
+        }
+    )
+    .hash(&mut hasher);
+    let mut targets: Vec<(&str, &str, &str, Platform)> = projects
+        .iter()
+        .flat_map(|project| {
+            project.targets.values().map(|target| {
+                (
+                    project.path.as_str(),
+                    target.name.as_str(),
+                    target.product.as_str(),
+                    target.platform(),
+                )
+            })
+        })
+        .collect();
+    targets.sort();
+    targets.hash(&mut hasher);
+    // Vendored frameworks are imported in the package that contains them,
+    // whichever project depends on them
+    for (path, import) in &index.imports {
+        path.hash(&mut hasher);
+        import.hash(&mut hasher);
+    }
+    // Whether an app embeds a framework depends on the other apps using it
+    index.shared_frameworks.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The files catalyst wrote into a project, so `clean` removes exactly those.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub(crate) struct GeneratedManifest {
+    pub(crate) files: Vec<PathBuf>,
+}
+
+impl GeneratedManifest {
+    const FILE_NAME: &'static str = "manifest.json";
+
+    pub(crate) fn load(project_cache_dir: &Path) -> Result<Self> {
+        let path = project_cache_dir.join(Self::FILE_NAME);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    /// Merges `written` into the stored manifest. Files from earlier runs are
+    /// kept so a later, smaller generation doesn't orphan them.
+    pub(crate) fn record(project_cache_dir: &Path, written: &[PathBuf]) -> Result<()> {
+        let mut manifest = Self::load(project_cache_dir)?;
+        for path in written {
+            // Don't canonicalize: that would resolve symlinks we created to the
+            // real source files they point at.
+            let path = std::path::absolute(path).unwrap_or_else(|_| path.clone());
+            if !manifest.files.contains(&path) {
+                manifest.files.push(path);
+            }
+        }
+
+        fs::create_dir_all(project_cache_dir)?;
+        fs::write(
+            project_cache_dir.join(Self::FILE_NAME),
+            serde_json::to_string_pretty(&manifest)?,
+        )
+        .context("Failed to write generated file manifest")
+    }
+
+    /// Whether catalyst wrote `path` in this or an earlier run.
+    pub(crate) fn contains(&self, path: &Path) -> bool {
+        std::path::absolute(path).is_ok_and(|path| self.files.contains(&path))
+    }
+}
+
+/// Prints where the project's cache lives and the files in it, with their
+/// size and age, and with `contents` the cached Tuist graph.
+pub fn show_cache(project_dir: &Path, contents: bool) -> Result<()> {
+    let cache_dir = get_catalyst_cache_dir()?;
+    let project_cache_dir = get_project_cache_dir(&cache_dir, project_dir)?;
+    println!("Cache directory: {}", cache_dir.display());
+
+    let Ok(entries) = fs::read_dir(&project_cache_dir) else {
+        println!(
+            "No cache for {} yet; `catalyst generate` or `catalyst build` creates it",
+            project_dir.display()
+        );
+        return Ok(());
+    };
+    println!("Project cache: {}", project_cache_dir.display());
+
+    let mut files: Vec<(String, fs::Metadata)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata
+                .is_file()
+                .then(|| (entry.file_name().to_string_lossy().into_owned(), metadata))
+        })
+        .collect();
+    files.sort_by(|a, b| a.0.cmp(&b.0));
+    let width = files.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, metadata) in &files {
+        let age = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map_or_else(|| "unknown".to_string(), format_age);
+        println!(
+            "  {:width$}  {:>9}  modified {}",
+            name,
+            format_size(metadata.len()),
+            age,
+            width = width
+        );
+    }
+
+    if contents {
+        let graph_path = project_cache_dir.join(GRAPH_CACHE_FILE);
+        match fs::read_to_string(&graph_path) {
+            Ok(graph) => println!("\n{}", graph),
+            Err(_) => println!("\nNo cached Tuist graph yet"),
+        }
+    }
+
+    Ok(())
+}
+
+/// A file size like `12.3 KB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+/// How long ago something happened, like `5 minutes ago`.
+fn format_age(age: Duration) -> String {
+    let seconds = age.as_secs();
+    let (count, unit) = match seconds {
+        0..60 => return "just now".to_string(),
+        60..3600 => (seconds / 60, "minute"),
+        3600..86400 => (seconds / 3600, "hour"),
+        _ => (seconds / 86400, "day"),
+    };
+    format!(
+        "{} {}{} ago",
+        count,
+        unit,
+        if count == 1 { "" } else { "s" }
+    )
+}
+
+pub fn clean_project(project_dir: &Path, dry_run: bool, clear_cache: bool) -> Result<()> {
+    let cache_dir = get_catalyst_cache_dir()?;
+    let project_cache_dir = get_project_cache_dir(&cache_dir, project_dir)?;
+    let manifest = GeneratedManifest::load(&project_cache_dir)?;
+
+    let existing: Vec<&PathBuf> = manifest
+        .files
+        .iter()
+        .filter(|p| p.symlink_metadata().is_ok())
+        .collect();
+    if existing.is_empty() {
+        println!("No generated files to remove in {}", project_dir.display());
+    }
+
+    for path in existing {
+        if dry_run {
+            println!("Would remove: {}", path.display());
+        } else {
+            fs::remove_file(path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            println!("Removed: {}", path.display());
+        }
+    }
+
+    if !dry_run {
+        let manifest_path = project_cache_dir.join(GeneratedManifest::FILE_NAME);
+        if manifest_path.exists() {
+            fs::remove_file(&manifest_path)?;
+        }
+    }
+
+    if clear_cache && cache_dir.exists() {
+        if dry_run {
+            println!("Would remove cache directory: {}", cache_dir.display());
+        } else {
+            fs::remove_dir_all(&cache_dir).with_context(|| {
+                format!("Failed to remove cache directory {}", cache_dir.display())
+            })?;
+            println!("Removed cache directory: {}", cache_dir.display());
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,382 @@
+//! Running tuist, bazel and simctl: logging, timeouts, and teed output.
+
+use anyhow::Result;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::error::{with_output_tail, CatalystError};
+use crate::output::suspend_progress;
+
+static CANCELLED: AtomicBool = AtomicBool::new(false);
+
+/// Stops the command catalyst is running through `status_teed`, e.g. the
+/// Bazel build `build --watch` restarts when a file changes, and any it
+/// starts until `set_cancelled(false)`.
+pub fn set_cancelled(cancelled: bool) {
+    CANCELLED.store(cancelled, Ordering::Relaxed);
+}
+
+fn cancelled() -> bool {
+    CANCELLED.load(Ordering::Relaxed)
+}
+
+static COMMAND_TIMEOUT: Mutex<Option<Duration>> = Mutex::new(None);
+
+/// Limits how long each tuist, bazel and simctl command catalyst runs may
+/// take, as `--command-timeout` does: one still running after `timeout` is
+/// killed and fails with `CatalystError::TimedOut`. `None`, the default,
+/// waits as long as it takes.
+pub fn set_command_timeout(timeout: Option<Duration>) {
+    *COMMAND_TIMEOUT
+        .lock()
+        .unwrap_or_else(|err| err.into_inner()) = timeout;
+}
+
+fn command_timeout() -> Option<Duration> {
+    *COMMAND_TIMEOUT
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+}
+
+/// Lines of a failed command's stderr carried in the error, so failures are
+/// self-contained in logs that don't capture the terminal.
+const STDERR_TAIL_LINES: usize = 20;
+
+pub(crate) trait CommandExt {
+    /// Prints the full command line with `--verbose`, before running it.
+    fn logged(&mut self) -> &mut Self;
+
+    /// Runs the command like `status()`, but tees its stderr: every line is
+    /// still forwarded to catalyst's stderr as it arrives, and the last ones
+    /// are kept for the error message. A piped stdout is forwarded to
+    /// catalyst's stderr too. The command is killed when `set_cancelled` is
+    /// called, or when it runs past the command timeout.
+    fn status_teed(&mut self) -> Result<TeedStatus>;
+
+    /// Runs the command like `output()`, killing it when it runs past the
+    /// command timeout.
+    fn output_timed(&mut self) -> Result<Output>;
+
+    /// Runs the command like `status()`, killing it when it runs past the
+    /// command timeout.
+    fn status_timed(&mut self) -> Result<ExitStatus>;
+}
+
+impl CommandExt for Command {
+    fn logged(&mut self) -> &mut Self {
+        verbose!(
+            "Running: {} {}",
+            self.get_program().to_string_lossy(),
+            self.get_args()
+                .map(|arg| arg.to_string_lossy())
+                .collect::<Vec<_>>()
+                .join(" ")
+        );
+        self
+    }
+
+    fn status_teed(&mut self) -> Result<TeedStatus> {
+        let mut child = self.stderr(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().map(|stdout| {
+            std::thread::spawn(move || {
+                for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                    suspend_progress(|| eprintln!("{}", line));
+                }
+            })
+        });
+        let tail = Arc::new(Mutex::new(VecDeque::with_capacity(STDERR_TAIL_LINES)));
+        let mut stderr = BufReader::new(child.stderr.take().expect("stderr is piped"));
+        let stderr = std::thread::spawn({
+            let tail = Arc::clone(&tail);
+            move || {
+                let mut line = Vec::new();
+                while stderr.read_until(b'\n', &mut line).unwrap_or(0) > 0 {
+                    // Progress output has to reach the terminal even if a write fails
+                    let _ = suspend_progress(|| std::io::stderr().write_all(&line));
+                    let text = strip_ansi(&String::from_utf8_lossy(&line));
+                    let text = text.trim_end();
+                    if !text.is_empty() {
+                        let mut tail = tail.lock().unwrap_or_else(|err| err.into_inner());
+                        if tail.len() == STDERR_TAIL_LINES {
+                            tail.pop_front();
+                        }
+                        tail.push_back(text.to_string());
+                    }
+                    line.clear();
+                }
+            }
+        });
+
+        // Killing the Bazel client makes its server abandon the command.
+        // Processes the killed one started may hold its output open, so the
+        // output threads aren't waited for then.
+        let tail_lines = || -> Vec<String> {
+            let tail = tail.lock().unwrap_or_else(|err| err.into_inner());
+            tail.iter().cloned().collect()
+        };
+        let status = match wait_killable(self, &mut child, true, tail_lines)? {
+            Some(status) => status,
+            None => {
+                return Ok(TeedStatus {
+                    status: child.wait()?,
+                    tail: Vec::new(),
+                })
+            }
+        };
+        if let Some(stdout) = stdout {
+            let _ = stdout.join();
+        }
+        let _ = stderr.join();
+        Ok(TeedStatus {
+            status,
+            tail: tail_lines(),
+        })
+    }
+
+    fn output_timed(&mut self) -> Result<Output> {
+        let mut child = self
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let read = |pipe: Option<Box<dyn Read + Send>>| {
+            let buffer = Arc::new(Mutex::new(Vec::new()));
+            let reader = pipe.map(|mut pipe| {
+                let buffer = Arc::clone(&buffer);
+                std::thread::spawn(move || {
+                    let mut chunk = [0; 8192];
+                    while let Ok(n @ 1..) = pipe.read(&mut chunk) {
+                        buffer
+                            .lock()
+                            .unwrap_or_else(|err| err.into_inner())
+                            .extend_from_slice(&chunk[..n]);
+                    }
+                })
+            });
+            (reader, buffer)
+        };
+        let (stdout_reader, stdout) = read(child.stdout.take().map(|pipe| Box::new(pipe) as _));
+        let (stderr_reader, stderr) = read(child.stderr.take().map(|pipe| Box::new(pipe) as _));
+        let take = |buffer: &Mutex<Vec<u8>>| {
+            std::mem::take(&mut *buffer.lock().unwrap_or_else(|err| err.into_inner()))
+        };
+
+        let status = wait_killable(self, &mut child, false, || {
+            let stderr = String::from_utf8_lossy(&take(&stderr)).into_owned();
+            let lines: Vec<String> = stderr
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(str::to_string)
+                .collect();
+            lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].to_vec()
+        })?
+        .expect("only cancellable commands are cancelled");
+        for reader in [stdout_reader, stderr_reader].into_iter().flatten() {
+            let _ = reader.join();
+        }
+        Ok(Output {
+            status,
+            stdout: take(&stdout),
+            stderr: take(&stderr),
+        })
+    }
+
+    fn status_timed(&mut self) -> Result<ExitStatus> {
+        let mut child = self.spawn()?;
+        Ok(wait_killable(self, &mut child, false, Vec::new)?
+            .expect("only cancellable commands are cancelled"))
+    }
+}
+
+/// Waits for `child`, started from `command`, polling so it can be killed
+/// when it runs past the command timeout, or, if `cancellable`, when
+/// `set_cancelled` is called. Returns `None` when it was cancelled, and
+/// fails with `CatalystError::TimedOut`, carrying `tail` of its output, when
+/// it timed out.
+fn wait_killable(
+    command: &Command,
+    child: &mut Child,
+    cancellable: bool,
+    tail: impl FnOnce() -> Vec<String>,
+) -> Result<Option<ExitStatus>> {
+    let started = Instant::now();
+    let timeout = command_timeout();
+    // Most commands finish quickly, so the polling starts out fast
+    let mut interval = Duration::from_millis(5);
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status));
+        }
+        if cancellable && cancelled() {
+            child.kill()?;
+            return Ok(None);
+        }
+        if let Some(timeout) = timeout.filter(|timeout| started.elapsed() >= *timeout) {
+            child.kill()?;
+            let _ = child.wait();
+            return Err(CatalystError::TimedOut {
+                command: command_name(command),
+                timeout,
+                output: tail(),
+            }
+            .into());
+        }
+        std::thread::sleep(interval);
+        interval = (interval * 2).min(Duration::from_millis(100));
+    }
+}
+
+/// How a command is named in errors: the executable's file name and the
+/// subcommands before the first flag, e.g. `bazel build` or
+/// `xcrun simctl boot`.
+fn command_name(command: &Command) -> String {
+    let program = Path::new(command.get_program());
+    let program = program
+        .file_name()
+        .unwrap_or(program.as_os_str())
+        .to_string_lossy();
+    let subcommands = command
+        .get_args()
+        .map(|arg| arg.to_string_lossy())
+        .take_while(|arg| !arg.starts_with('-'))
+        .take(2);
+    std::iter::once(program)
+        .chain(subcommands)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// The exit status of a command run with `status_teed`.
+pub(crate) struct TeedStatus {
+    pub(crate) status: ExitStatus,
+    /// The last lines the command wrote to stderr
+    tail: Vec<String>,
+}
+
+impl TeedStatus {
+    /// Fails with `message` followed by the command's last stderr lines when
+    /// the command didn't succeed.
+    pub(crate) fn check(&self, message: impl std::fmt::Display) -> Result<()> {
+        if self.status.success() {
+            return Ok(());
+        }
+        anyhow::bail!("{}", with_output_tail(&message.to_string(), &self.tail))
+    }
+
+    /// Like `check`, for a Bazel command, with a hint on top when a download
+    /// failed its checksum.
+    pub(crate) fn check_bazel(&self, message: impl std::fmt::Display) -> Result<()> {
+        self.check(message)
+            .map_err(|err| match checksum_hint(&self.tail) {
+                Some(hint) => err.context(hint),
+                None => err,
+            })
+    }
+
+    /// Fails with `CatalystError::BazelBuildFailed` when the build of
+    /// `target`, or of `//...`, didn't succeed.
+    pub(crate) fn check_bazel_build(self, target: Option<&str>) -> Result<()> {
+        if self.status.success() {
+            return Ok(());
+        }
+        let hint = checksum_hint(&self.tail);
+        let err = anyhow::Error::from(CatalystError::BazelBuildFailed {
+            code: self.status.code(),
+            target: target.map(str::to_string),
+            output: self.tail,
+        });
+        Err(match hint {
+            Some(hint) => err.context(hint),
+            None => err,
+        })
+    }
+}
+
+/// What to do when Bazel's output says a download didn't match its
+/// checksum (`Checksum was <actual> but wanted <expected>`), which happens
+/// when a release archive was re-uploaded or a proxy rewrites downloads.
+/// Names the rule set's `catalyst.toml` settings when the download is one
+/// catalyst declares.
+fn checksum_hint(output: &[String]) -> Option<String> {
+    let line = output
+        .iter()
+        .find(|line| line.contains("Checksum was ") && line.contains(" but wanted "))?;
+    let actual = line
+        .split("Checksum was ")
+        .nth(1)?
+        .split_whitespace()
+        .next()?;
+    // Bazel names the archive's URL or repository on the same line or
+    // just before it
+    let rule_sets = [
+        "rules_apple",
+        "rules_swift",
+        "apple_support",
+        "rules_xcodeproj",
+    ];
+    let rule_set = rule_sets
+        .into_iter()
+        .find(|name| line.contains(name))
+        .or_else(|| {
+            rule_sets
+                .into_iter()
+                .find(|name| output.iter().any(|line| line.contains(name)))
+        });
+
+    Some(match rule_set {
+        Some(name) => {
+            let source = if name == "rules_xcodeproj" {
+                String::new()
+            } else {
+                format!(", or fetch it from a mirror with `{}_source`", name)
+            };
+            format!(
+                "The {} download doesn't match the sha256 catalyst declares for it (it's {}). \
+                 If you trust it, e.g. because the release archive changed or a proxy rewrites downloads, \
+                 set `{}_sha256 = \"{}\"` in catalyst.toml; or pin another release with `{}_version` and `{}_sha256`{}. \
+                 The next run regenerates WORKSPACE with them",
+                name, actual, name, actual, name, name, source
+            )
+        }
+        None => format!(
+            "A download doesn't match the checksum Bazel expected; it's {}. \
+             If it's a Bazel rule set catalyst declares, set its `<rule set>_sha256` in catalyst.toml, \
+             e.g. `rules_apple_sha256 = \"{}\"`",
+            actual, actual
+        ),
+    })
+}
+
+/// Removes terminal escape sequences such as colors from `text`.
+fn strip_ansi(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\u{1b}' {
+            stripped.push(c);
+        } else if chars.next() == Some('[') {
+            // A CSI sequence ends with a byte in @..~
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+    stripped
+}
+
+/// Bazel only colors its output on a terminal, which piped stderr isn't, so
+/// ask for colors explicitly when catalyst's own stderr is one.
+pub(crate) fn bazel_color_args() -> &'static [&'static str] {
+    if std::io::stderr().is_terminal() {
+        &["--color=yes"]
+    } else {
+        &[]
+    }
+}
@@ -0,0 +1,1013 @@
+//! `catalyst.toml`, the generation options, and the rule sets pinned by default.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+use crate::graph::{detect_workspace_root, normalize_lexically, TuistGraph, TuistProject};
+use crate::output::dry_run;
+use crate::simulator::DEFAULT_SIMULATOR;
+use crate::starlark::starlark_string;
+use crate::workspace::write_file;
+
+/// Versions of the Bazel rule sets referenced by the generated files. Both the
+/// WORKSPACE and MODULE.bazel generators read these so the two modes stay in sync.
+const RULES_APPLE_VERSION: &str = "3.5.1";
+const RULES_APPLE_SHA256: &str = "b4df908ec14868369021182ab191dbd1f40830c9b300650d5dc389e0b9266c8d";
+/// The rules_swift version rules_apple pins. In WORKSPACE mode it is pulled in
+/// through `apple_rules_dependencies()`, so keep it aligned with RULES_APPLE_VERSION.
+const RULES_SWIFT_VERSION: &str = "1.18.0";
+const APPLE_SUPPORT_VERSION: &str = "1.15.1";
+/// Only declared with `--with-xcodeproj`. No checksum is pinned, so WORKSPACE
+/// mode declares its archive without one unless catalyst.toml sets
+/// `rules_xcodeproj_sha256`.
+const RULES_XCODEPROJ_VERSION: &str = "2.10.0";
+/// The Bazel release written to `.bazelversion`, one that the rule sets above
+/// support. Bump it together with RULES_APPLE_VERSION.
+const BAZEL_VERSION: &str = "7.4.1";
+
+/// Release archive of a bazelbuild rule set, e.g. rules_apple.3.5.1.tar.gz.
+fn release_archive_url(name: &str, version: &str) -> String {
+    format!(
+        "https://github.com/bazelbuild/{name}/releases/download/{version}/{name}.{version}.tar.gz"
+    )
+}
+
+/// Deployment target used when a Tuist target doesn't declare one.
+pub const DEFAULT_MINIMUM_OS_VERSION: &str = "15.0";
+pub(crate) const DEFAULT_WATCHOS_MINIMUM_OS_VERSION: &str = "8.0";
+pub(crate) const DEFAULT_MACOS_MINIMUM_OS_VERSION: &str = "12.0";
+
+/// How a command's result is printed to stdout.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Prose for people
+    Human,
+    /// A single JSON object summarizing the run, for scripts
+    Json,
+}
+
+/// Project defaults read from a `catalyst.toml` in the project directory or
+/// one of its ancestors.
+///
+/// Every field is optional. Values given on the command line take precedence
+/// over the file, and the file takes precedence over catalyst's built-in
+/// defaults.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Simulator device used by `catalyst run`
+    pub simulator: Option<String>,
+    /// App target `catalyst run` launches when `--target` isn't given
+    pub default_run_target: Option<String>,
+    /// iOS runtime version of that simulator, e.g. `17.5`
+    pub simulator_os: Option<String>,
+    pub bazel_mode: Option<BazelMode>,
+    /// `BUILD.bazel` to generate `BUILD.bazel` and `WORKSPACE.bazel`
+    pub build_file_name: Option<BuildFileName>,
+    /// Deployment target for targets that don't declare one
+    pub minimum_os_version: Option<String>,
+    pub configuration: Option<BuildConfiguration>,
+    /// `ios-device` or `ios-simulator`, for builds; Bazel's default otherwise
+    pub platform: Option<BuildPlatform>,
+    /// Device families for targets that don't set TARGETED_DEVICE_FAMILY
+    pub families: Option<Vec<DeviceFamily>>,
+    pub rules_apple_version: Option<String>,
+    /// Required in WORKSPACE mode when `rules_apple_version` is overridden
+    pub rules_apple_sha256: Option<String>,
+    /// Fetch rules_apple from a git remote or a local checkout instead of
+    /// its GitHub release
+    pub rules_apple_source: Option<RuleSource>,
+    pub rules_swift_version: Option<String>,
+    /// Required in WORKSPACE mode when `rules_swift_version` is overridden
+    pub rules_swift_sha256: Option<String>,
+    pub rules_swift_source: Option<RuleSource>,
+    pub apple_support_version: Option<String>,
+    /// Required in WORKSPACE mode when `apple_support_version` is overridden
+    pub apple_support_sha256: Option<String>,
+    pub apple_support_source: Option<RuleSource>,
+    pub rules_xcodeproj_version: Option<String>,
+    /// Required in WORKSPACE mode with `--with-xcodeproj`
+    pub rules_xcodeproj_sha256: Option<String>,
+    /// Extra lines appended to the generated .bazelrc
+    pub bazelrc: Vec<String>,
+    /// Bazel remote cache URL, e.g. `grpcs://cache.example.com`
+    pub remote_cache: Option<String>,
+    /// Script Bazel runs for the build stamp values
+    pub workspace_status_command: Option<String>,
+    /// Files to leave out of a target, by target name: globs relative to the
+    /// project directory, like `Sources/**/*+macOS.swift`
+    pub exclude: BTreeMap<String, Vec<String>>,
+    /// Projects catalyst writes no BUILD file for, as globs of their name or
+    /// their package path, like `Vendor/*`
+    pub exclude_projects: Vec<String>,
+    /// Targets catalyst generates no rules for, as globs of their name
+    pub exclude_targets: Vec<String>,
+    /// Settings for single targets, by target name, that win over the graph
+    pub targets: BTreeMap<String, TargetOverrides>,
+    /// Bazel release pinned in the generated `.bazelversion`
+    pub bazel_version: Option<String>,
+    /// Bazel executable, e.g. `bazelisk` or `tools/bazel`
+    pub bazel: Option<PathBuf>,
+    /// Tuist executable
+    pub tuist: Option<PathBuf>,
+    /// buildifier executable, for `format_build_files`
+    pub buildifier: Option<PathBuf>,
+    /// Run buildifier over the generated BUILD and WORKSPACE files
+    pub format_build_files: Option<bool>,
+    /// Seconds a tuist, bazel, or simctl command may run before it's killed
+    pub command_timeout: Option<u64>,
+    /// `static` links frameworks that several apps depend on into each app
+    pub shared_frameworks: Option<SharedFrameworks>,
+    /// `graph` keeps source files in the order the Tuist graph lists them
+    pub source_order: Option<SourceOrder>,
+    /// `false` keeps the subdirectories of resources in buildable folders
+    pub flatten_resources: Option<bool>,
+    /// Info.plist that apps without one get, relative to the directory of
+    /// `catalyst.toml`, with `{{bundle_id}}`, `{{name}}`, `{{version}}`, and
+    /// `{{build}}` filled in
+    pub info_plist_template: Option<PathBuf>,
+    /// `--define` values for every build and test, by key
+    pub defines: BTreeMap<String, String>,
+    /// Directory the Bazel workspace is rooted at, relative to the directory
+    /// of `catalyst.toml`, for projects that live outside it
+    pub workspace_root: Option<PathBuf>,
+    /// Directory the configuration was loaded from, which its relative paths
+    /// are resolved against; `None` for the defaults
+    #[serde(skip)]
+    pub dir: Option<PathBuf>,
+}
+
+/// A `[targets.<name>]` table of `catalyst.toml`. Each value given replaces
+/// what the Tuist graph says about the target, which in turn replaces the
+/// global defaults; `tags` add to the ones catalyst derives.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TargetOverrides {
+    pub families: Option<Vec<DeviceFamily>>,
+    pub minimum_os_version: Option<String>,
+    pub bundle_id: Option<String>,
+    /// Name of the bundle and its executable, like Xcode's PRODUCT_NAME
+    pub product_name: Option<String>,
+    /// Info.plist to bundle, relative to the project directory
+    pub infoplist: Option<PathBuf>,
+    /// Added to the `tags` of the target's rules
+    pub tags: Vec<String>,
+    /// `.mobileprovision` to sign the bundle with for devices, relative to
+    /// the project directory
+    pub provisioning_profile: Option<PathBuf>,
+    /// `false` leaves a test target out of `//:all_tests`, e.g. a flaky one
+    pub all_tests: Option<bool>,
+    /// Bazel label of the app that hosts a test target, for test targets that
+    /// don't depend on one, e.g. `//App:app`
+    pub test_host: Option<String>,
+    /// Name of the Objective-C header the target's Swift code generates,
+    /// instead of `<Module>-Swift.h`
+    pub generated_header_name: Option<String>,
+}
+
+impl Config {
+    pub const FILE_NAME: &'static str = "catalyst.toml";
+
+    /// Loads the nearest `catalyst.toml`, or the defaults if there is none.
+    pub fn load(project_dir: &Path) -> Result<Self> {
+        match Self::find(project_dir) {
+            Some(path) => {
+                let content = fs::read_to_string(&path)
+                    .with_context(|| format!("Failed to read {}", path.display()))?;
+                let mut config: Self = toml::from_str(&content)
+                    .with_context(|| format!("Invalid configuration in {}", path.display()))?;
+                config.dir = path.parent().map(Path::to_path_buf);
+                info!("Using configuration: {}", path.display());
+                Ok(config)
+            }
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Resolves a path the configuration gives against the directory it was
+    /// loaded from, which may be above the project directory.
+    fn resolve(&self, path: &Path) -> PathBuf {
+        match &self.dir {
+            Some(dir) => dir.join(path),
+            None => path.to_path_buf(),
+        }
+    }
+
+    fn find(project_dir: &Path) -> Option<PathBuf> {
+        let start = project_dir.canonicalize().ok()?;
+        start
+            .ancestors()
+            .map(|dir| dir.join(Self::FILE_NAME))
+            .find(|path| path.is_file())
+    }
+}
+
+/// Settings that control how Bazel files are generated.
+#[derive(Debug, Clone)]
+pub struct GenerateOptions {
+    pub bazel_mode: BazelMode,
+    /// Names of the generated BUILD and WORKSPACE files
+    pub build_file_name: BuildFileName,
+    /// Fallback deployment target for targets without one in the graph
+    pub minimum_os_version: String,
+    pub configuration: BuildConfiguration,
+    /// Whether iOS bundles are built for the simulator or devices; `None`
+    /// leaves it to Bazel's defaults and the `.bazelrc`
+    pub platform: Option<BuildPlatform>,
+    /// Device families for targets that don't set TARGETED_DEVICE_FAMILY
+    pub families: Vec<DeviceFamily>,
+    /// Passed verbatim to `bazel build`, from the arguments after `--`
+    pub bazel_args: Vec<String>,
+    /// Caps Bazel's `--jobs` and the threads generating BUILD files; `None`
+    /// leaves both at their defaults
+    pub jobs: Option<u32>,
+    /// Passed to every build and test as `--define=KEY=VALUE`
+    pub defines: BTreeMap<String, String>,
+    /// Where generated files go; `None` writes them into the workspace root
+    pub output_dir: Option<PathBuf>,
+    /// The directory Bazel packages are relative to, relative to the project
+    /// directory; `None` detects it from the graph
+    pub workspace_root: Option<PathBuf>,
+    /// Skip the cached Tuist graph and always run `tuist graph`
+    pub no_cache: bool,
+    /// Read the Tuist graph from this JSON file, or stdin for `-`, instead
+    /// of running `tuist graph`
+    pub graph: Option<PathBuf>,
+    /// Build even when nothing changed since the last successful build, and
+    /// regenerate the BUILD files of unchanged projects
+    pub force: bool,
+    /// Add the generated files to the project's .gitignore
+    pub update_gitignore: bool,
+    pub rules_apple: RuleSet,
+    pub rules_swift: RuleSet,
+    pub apple_support: RuleSet,
+    /// Declare rules_xcodeproj and generate an `xcodeproj` target
+    pub with_xcodeproj: bool,
+    pub rules_xcodeproj: RuleSet,
+    /// Fail when files listed in the graph are missing, rather than warn
+    pub strict: bool,
+    /// Generate the other projects when some fail, and report the failures
+    /// at the end
+    pub keep_going: bool,
+    /// Fail generation when it printed any warning
+    pub fail_on_warnings: bool,
+    /// Regenerate only the packages of this newly added target and of its
+    /// dependents, leaving the other BUILD files as they are
+    pub add_target: Option<String>,
+    /// Bazel release for `.bazelversion`, which Bazelisk picks up
+    pub bazel_version: String,
+    /// Executables catalyst runs
+    pub bazel: PathBuf,
+    pub tuist: PathBuf,
+    pub buildifier: PathBuf,
+    /// Format the generated Starlark files with buildifier, when it's installed
+    pub format_build_files: bool,
+    /// Additional lines for the generated .bazelrc
+    pub extra_bazelrc: Vec<String>,
+    /// `--remote_cache` for every build
+    pub remote_cache: Option<String>,
+    /// `--workspace_status_command` for every build
+    pub workspace_status_command: Option<String>,
+    /// Globs of files left out of each target, by target name
+    pub exclude: BTreeMap<String, Vec<String>>,
+    /// Globs of the projects and targets that are built another way
+    pub exclude_projects: Vec<String>,
+    pub exclude_targets: Vec<String>,
+    /// Per-target settings that take precedence over the graph
+    pub targets: BTreeMap<String, TargetOverrides>,
+    /// How frameworks that several apps depend on are linked
+    pub shared_frameworks: SharedFrameworks,
+    /// Whether source files are sorted or kept in the graph's order
+    pub source_order: SourceOrder,
+    /// Copy resources to the bundle root rather than keeping the
+    /// subdirectories of their buildable folder
+    pub flatten_resources: bool,
+    /// Template of the Info.plist synthesized for apps, relative to the
+    /// project directory; `None` uses catalyst's own
+    pub info_plist_template: Option<PathBuf>,
+    /// App target `catalyst run` launches when none is requested
+    pub default_run_target: Option<String>,
+    /// Bazel's `--output_base`, to keep concurrent builds apart; `None`
+    /// leaves it to Bazel
+    pub bazel_output_base: Option<PathBuf>,
+}
+
+impl GenerateOptions {
+    /// Startup options, which go before the command, of every Bazel command
+    /// catalyst runs.
+    pub fn bazel_startup_args(&self) -> Vec<String> {
+        self.bazel_output_base
+            .iter()
+            .map(|base| format!("--output_base={}", base.display()))
+            .collect()
+    }
+
+    /// Arguments added to every `bazel build` catalyst runs.
+    pub fn bazel_build_args(&self) -> Vec<String> {
+        let mut args = vec![format!("--config={}", self.configuration.bazelrc_config())];
+        args.extend(
+            self.platform
+                .into_iter()
+                .flat_map(BuildPlatform::bazel_args),
+        );
+        args.extend(self.jobs.map(|jobs| format!("--jobs={}", jobs)));
+        args.extend(
+            self.defines
+                .iter()
+                .map(|(key, value)| format!("--define={}={}", key, value)),
+        );
+        args.extend(self.bazel_args.iter().cloned());
+        args
+    }
+
+    /// The directory that holds the generated Bazel workspace and where Bazel
+    /// is invoked from.
+    pub fn workspace_dir(&self, project_dir: &Path, graph: &TuistGraph) -> PathBuf {
+        self.output_dir
+            .clone()
+            .unwrap_or_else(|| self.source_root(project_dir, graph))
+    }
+
+    /// The directory the sources of the Bazel workspace are rooted at:
+    /// `workspace_root` when it's set, or else the project directory, widened
+    /// to the closest directory that holds every project of the graph when
+    /// some live outside it, like sibling checkouts a Workspace.swift refers
+    /// to.
+    pub(crate) fn source_root(&self, project_dir: &Path, graph: &TuistGraph) -> PathBuf {
+        match &self.workspace_root {
+            Some(root) => normalize_lexically(&project_dir.join(root)),
+            None => detect_workspace_root(project_dir, graph)
+                .unwrap_or_else(|| project_dir.to_path_buf()),
+        }
+    }
+
+    /// The directory Bazel packages are relative to: the graph's root, unless
+    /// the workspace is rooted above the project directory.
+    pub(crate) fn package_root(&self, project_dir: &Path, graph: &TuistGraph) -> PathBuf {
+        let source_root = normalize_lexically(&self.source_root(project_dir, graph));
+        if source_root == normalize_lexically(project_dir) {
+            normalize_lexically(&project_dir.join(&graph.path))
+        } else {
+            source_root
+        }
+    }
+
+    /// The generated BUILD file of the package in `dir`.
+    pub(crate) fn build_file(&self, dir: &Path) -> PathBuf {
+        dir.join(self.build_file_name.build())
+    }
+
+    /// The built-in defaults with the settings from `catalyst.toml` applied.
+    pub fn from_config(config: &Config) -> Self {
+        let defaults = Self::default();
+
+        Self {
+            bazel_mode: config.bazel_mode.unwrap_or(defaults.bazel_mode),
+            build_file_name: config.build_file_name.unwrap_or(defaults.build_file_name),
+            minimum_os_version: config
+                .minimum_os_version
+                .clone()
+                .unwrap_or(defaults.minimum_os_version),
+            configuration: config.configuration.unwrap_or(defaults.configuration),
+            platform: config.platform,
+            families: config.families.clone().unwrap_or(defaults.families),
+            rules_apple: RuleSet::configured(
+                defaults.rules_apple,
+                &config.rules_apple_version,
+                &config.rules_apple_sha256,
+            )
+            .with_source(config.rules_apple_source.clone()),
+            rules_swift: RuleSet::configured(
+                defaults.rules_swift,
+                &config.rules_swift_version,
+                &config.rules_swift_sha256,
+            )
+            .with_source(config.rules_swift_source.clone()),
+            apple_support: RuleSet::configured(
+                defaults.apple_support,
+                &config.apple_support_version,
+                &config.apple_support_sha256,
+            )
+            .with_source(config.apple_support_source.clone()),
+            rules_xcodeproj: RuleSet::configured(
+                defaults.rules_xcodeproj,
+                &config.rules_xcodeproj_version,
+                &config.rules_xcodeproj_sha256,
+            ),
+            bazel_version: config
+                .bazel_version
+                .clone()
+                .unwrap_or(defaults.bazel_version),
+            bazel: config.bazel.clone().unwrap_or(defaults.bazel),
+            tuist: config.tuist.clone().unwrap_or(defaults.tuist),
+            buildifier: config.buildifier.clone().unwrap_or(defaults.buildifier),
+            format_build_files: config
+                .format_build_files
+                .unwrap_or(defaults.format_build_files),
+            extra_bazelrc: config.bazelrc.clone(),
+            remote_cache: config.remote_cache.clone(),
+            workspace_status_command: config.workspace_status_command.clone(),
+            exclude: config.exclude.clone(),
+            exclude_projects: config.exclude_projects.clone(),
+            exclude_targets: config.exclude_targets.clone(),
+            targets: config.targets.clone(),
+            shared_frameworks: config
+                .shared_frameworks
+                .unwrap_or(defaults.shared_frameworks),
+            workspace_root: config
+                .workspace_root
+                .as_deref()
+                .map(|root| config.resolve(root)),
+            source_order: config.source_order.unwrap_or(defaults.source_order),
+            flatten_resources: config
+                .flatten_resources
+                .unwrap_or(defaults.flatten_resources),
+            defines: config.defines.clone(),
+            info_plist_template: config
+                .info_plist_template
+                .as_deref()
+                .map(|template| config.resolve(template)),
+            default_run_target: config.default_run_target.clone(),
+            ..defaults
+        }
+    }
+}
+
+impl Default for GenerateOptions {
+    fn default() -> Self {
+        Self {
+            bazel_mode: BazelMode::Workspace,
+            build_file_name: BuildFileName::Build,
+            minimum_os_version: DEFAULT_MINIMUM_OS_VERSION.to_string(),
+            configuration: BuildConfiguration::Debug,
+            platform: None,
+            families: vec![DeviceFamily::Iphone, DeviceFamily::Ipad],
+            bazel_args: Vec::new(),
+            jobs: None,
+            defines: BTreeMap::new(),
+            output_dir: None,
+            workspace_root: None,
+            no_cache: false,
+            graph: None,
+            force: false,
+            update_gitignore: true,
+            rules_apple: RuleSet::new("rules_apple", RULES_APPLE_VERSION, Some(RULES_APPLE_SHA256)),
+            // In WORKSPACE mode these come from apple_rules_dependencies(), so
+            // their checksums are only needed once the versions are overridden
+            rules_swift: RuleSet::new("rules_swift", RULES_SWIFT_VERSION, None),
+            apple_support: RuleSet::new("apple_support", APPLE_SUPPORT_VERSION, None),
+            with_xcodeproj: false,
+            strict: false,
+            keep_going: false,
+            fail_on_warnings: false,
+            add_target: None,
+            bazel_version: BAZEL_VERSION.to_string(),
+            bazel: PathBuf::from("bazel"),
+            tuist: PathBuf::from("tuist"),
+            buildifier: PathBuf::from("buildifier"),
+            format_build_files: false,
+            rules_xcodeproj: RuleSet::new("rules_xcodeproj", RULES_XCODEPROJ_VERSION, None),
+            extra_bazelrc: Vec::new(),
+            remote_cache: None,
+            workspace_status_command: None,
+            exclude: BTreeMap::new(),
+            exclude_projects: Vec::new(),
+            exclude_targets: Vec::new(),
+            targets: BTreeMap::new(),
+            shared_frameworks: SharedFrameworks::Dynamic,
+            source_order: SourceOrder::Sorted,
+            flatten_resources: true,
+            info_plist_template: None,
+            default_run_target: None,
+            bazel_output_base: None,
+        }
+    }
+}
+
+/// Where a rule set is fetched from instead of its GitHub release, for
+/// builds that can't reach GitHub. Written in `catalyst.toml` as
+/// `{ git = "<remote>", commit = "<sha>" }` or `{ path = "<checkout>" }`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum RuleSource {
+    Git {
+        git: String,
+        commit: String,
+    },
+    /// A checkout on disk, absolute or relative to the workspace directory
+    Local {
+        path: PathBuf,
+    },
+}
+
+/// A Bazel rule set pinned to a release.
+#[derive(Debug, Clone)]
+pub struct RuleSet {
+    pub(crate) name: &'static str,
+    pub(crate) version: String,
+    /// `None` when no checksum is known for `version`
+    sha256: Option<String>,
+    /// Whether the version differs from the one catalyst pins
+    overridden: bool,
+    /// `None` fetches the release archive
+    pub(crate) source: Option<RuleSource>,
+}
+
+impl RuleSet {
+    fn new(name: &'static str, version: &str, sha256: Option<&str>) -> Self {
+        Self {
+            name,
+            version: version.to_string(),
+            sha256: sha256.map(str::to_string),
+            overridden: false,
+            source: None,
+        }
+    }
+
+    fn with_source(self, source: Option<RuleSource>) -> Self {
+        Self { source, ..self }
+    }
+
+    /// Whether the WORKSPACE has to declare the rule set itself rather than
+    /// leaving it to apple_rules_dependencies().
+    pub(crate) fn is_customized(&self) -> bool {
+        self.overridden || self.source.is_some()
+    }
+
+    /// The repository rule for a WORKSPACE file: an `http_archive` of the
+    /// release, or a `git_repository`/`local_repository` for a custom source.
+    pub(crate) fn repository(&self, repo_name: &str) -> Result<String> {
+        match &self.source {
+            None => self.http_archive(repo_name),
+            Some(RuleSource::Git { git, commit }) => Ok(format!(
+                "git_repository(\n    name = {},\n    commit = {},\n    remote = {},\n)\n",
+                starlark_string(repo_name),
+                starlark_string(commit),
+                starlark_string(git)
+            )),
+            Some(RuleSource::Local { path }) => Ok(format!(
+                "local_repository(\n    name = {},\n    path = {},\n)\n",
+                starlark_string(repo_name),
+                starlark_string(path.display())
+            )),
+        }
+    }
+
+    /// The MODULE.bazel override that points the `bazel_dep` at a custom
+    /// source, if there is one.
+    pub(crate) fn module_override(&self) -> Option<String> {
+        match self.source.as_ref()? {
+            RuleSource::Git { git, commit } => Some(format!(
+                "git_override(\n    module_name = {},\n    commit = {},\n    remote = {},\n)\n",
+                starlark_string(self.name),
+                starlark_string(commit),
+                starlark_string(git)
+            )),
+            RuleSource::Local { path } => Some(format!(
+                "local_path_override(\n    module_name = {},\n    path = {},\n)\n",
+                starlark_string(self.name),
+                starlark_string(path.display())
+            )),
+        }
+    }
+
+    /// Applies the version and checksum from `catalyst.toml`. The built-in
+    /// checksum only applies to the built-in version, so overriding the
+    /// version drops it.
+    fn configured(default: Self, version: &Option<String>, sha256: &Option<String>) -> Self {
+        match version {
+            Some(version) if *version != default.version => Self {
+                version: version.clone(),
+                sha256: sha256.clone(),
+                overridden: true,
+                ..default
+            },
+            _ => Self {
+                sha256: sha256.clone().or(default.sha256),
+                ..default
+            },
+        }
+    }
+
+    /// The `http_archive` rule for a WORKSPACE file. Fails rather than writing
+    /// an overridden version without its checksum. The built-in version of a
+    /// rule set catalyst has no checksum for is declared without one; Bazel
+    /// fetches it anyway and prints the sha256 to pin.
+    pub(crate) fn http_archive(&self, repo_name: &str) -> Result<String> {
+        let sha256 = match &self.sha256 {
+            Some(sha256) => format!("    sha256 = {},\n", starlark_string(sha256)),
+            None if self.overridden => anyhow::bail!(
+                "{name}_version is set to {version} but {name}_sha256 is missing; add the archive's sha256 to {file}",
+                name = self.name,
+                version = self.version,
+                file = Config::FILE_NAME
+            ),
+            None => {
+                verbose!(
+                    "catalyst doesn't pin a checksum for {} {}; set {}_sha256 in {} to pin one",
+                    self.name,
+                    self.version,
+                    self.name,
+                    Config::FILE_NAME
+                );
+                String::new()
+            }
+        };
+
+        Ok(format!(
+            "http_archive(\n    name = {},\n{}    url = {},\n)\n",
+            starlark_string(repo_name),
+            sha256,
+            starlark_string(self.archive_url())
+        ))
+    }
+
+    fn archive_url(&self) -> String {
+        match self.name {
+            "rules_xcodeproj" => format!(
+                "https://github.com/MobileNativeFoundation/rules_xcodeproj/releases/download/{}/release.tar.gz",
+                self.version
+            ),
+            _ => release_archive_url(self.name, &self.version),
+        }
+    }
+}
+
+/// Tuist build configuration, mapped to a Bazel compilation mode through a
+/// config of the same name in the generated .bazelrc.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildConfiguration {
+    /// Tuist's Debug configuration, `--compilation_mode=dbg`
+    Debug,
+    /// Tuist's Release configuration, `--compilation_mode=opt`
+    Release,
+}
+
+impl BuildConfiguration {
+    pub(crate) fn bazelrc_config(self) -> &'static str {
+        match self {
+            Self::Debug => "debug",
+            Self::Release => "release",
+        }
+    }
+}
+
+/// What iOS bundles are built for, picked with the `--ios_multi_cpus` it
+/// adds to Bazel builds.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum BuildPlatform {
+    /// The simulator of the host's architecture, `sim_arm64` or `x86_64`
+    IosSimulator,
+    /// Devices, `arm64`, e.g. for archiving
+    IosDevice,
+}
+
+impl BuildPlatform {
+    fn bazel_args(self) -> Vec<String> {
+        let cpu = match self {
+            Self::IosSimulator if std::env::consts::ARCH == "aarch64" => "sim_arm64",
+            Self::IosSimulator => "x86_64",
+            Self::IosDevice => "arm64",
+        };
+        vec![format!("--ios_multi_cpus={}", cpu)]
+    }
+}
+
+/// An iOS device family a bundle supports.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeviceFamily {
+    Iphone,
+    Ipad,
+}
+
+impl DeviceFamily {
+    /// Maps a `TARGETED_DEVICE_FAMILY` value; other platforms' values are ignored.
+    pub(crate) fn from_setting(value: &str) -> Option<Self> {
+        match value.trim() {
+            "1" => Some(Self::Iphone),
+            "2" => Some(Self::Ipad),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Iphone => "iphone",
+            Self::Ipad => "ipad",
+        }
+    }
+
+    /// The `TARGETED_DEVICE_FAMILY` value for the family.
+    pub(crate) fn setting(self) -> &'static str {
+        match self {
+            Self::Iphone => "1",
+            Self::Ipad => "2",
+        }
+    }
+}
+
+/// The Apple platform a target is built for, which picks the rules_apple
+/// rules it's generated with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Platform {
+    Ios,
+    Watchos,
+    Macos,
+}
+
+impl Platform {
+    /// The prefix of the platform's rules_apple rules and flags, e.g. `ios`
+    /// for `ios_application` and `--ios_minimum_os`.
+    pub(crate) fn prefix(self) -> &'static str {
+        match self {
+            Self::Ios => "ios",
+            Self::Watchos => "watchos",
+            Self::Macos => "macos",
+        }
+    }
+
+    /// The rules_apple rule of the given kind, e.g. `watchos_application`.
+    pub(crate) fn rule(self, kind: &str) -> String {
+        format!("{}_{}", self.prefix(), kind)
+    }
+}
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BazelMode {
+    /// Legacy WORKSPACE file with http_archive rules
+    Workspace,
+    /// bzlmod MODULE.bazel file with bazel_dep entries
+    Module,
+}
+
+/// How a framework that several apps depend on is linked into them.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SharedFrameworks {
+    /// Embedded once as a dynamic framework each app lists in `frameworks`
+    Dynamic,
+    /// Linked statically into each app, like a library
+    Static,
+}
+
+/// The order of the files in the generated `srcs` and `hdrs`.
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SourceOrder {
+    /// By path, so regenerated BUILD files don't change
+    Sorted,
+    /// In the order the Tuist graph lists them, for order-sensitive builds
+    Graph,
+}
+
+/// The names of the generated BUILD and WORKSPACE files. Bazel reads
+/// `BUILD.bazel` in place of a `BUILD` next to it, so only one of the two may
+/// exist in a package.
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuildFileName {
+    #[serde(rename = "BUILD")]
+    #[value(name = "BUILD")]
+    Build,
+    #[serde(rename = "BUILD.bazel")]
+    #[value(name = "BUILD.bazel")]
+    BuildBazel,
+}
+
+impl BuildFileName {
+    pub(crate) fn build(self) -> &'static str {
+        match self {
+            Self::Build => "BUILD",
+            Self::BuildBazel => "BUILD.bazel",
+        }
+    }
+
+    pub(crate) fn workspace(self) -> &'static str {
+        match self {
+            Self::Build => "WORKSPACE",
+            Self::BuildBazel => "WORKSPACE.bazel",
+        }
+    }
+
+    /// The other naming convention.
+    pub(crate) fn other(self) -> Self {
+        match self {
+            Self::Build => Self::BuildBazel,
+            Self::BuildBazel => Self::Build,
+        }
+    }
+}
+
+/// Applies the `[targets.<name>]` tables of `catalyst.toml` to the targets
+/// they name, warning about names that aren't in the graph.
+pub(crate) fn apply_target_overrides(
+    projects: &mut [TuistProject],
+    overrides: &BTreeMap<String, TargetOverrides>,
+) -> Result<()> {
+    for (name, target_overrides) in overrides {
+        let mut known = false;
+        for project in projects.iter_mut() {
+            let project_path = PathBuf::from(&project.path);
+            if let Some(target) = project.targets.get_mut(name) {
+                target.apply_overrides(target_overrides, &project_path)?;
+                known = true;
+            }
+        }
+        if !known {
+            warning!(
+                "{} has settings for {}, which isn't a target of the graph",
+                Config::FILE_NAME,
+                name
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Writes a `catalyst.toml` with every setting commented out at its default.
+pub fn init_project(project_dir: &Path, force: bool) -> Result<()> {
+    let config_path = project_dir.join(Config::FILE_NAME);
+    if config_path.exists() && !force {
+        anyhow::bail!(
+            "{} already exists; pass --force to overwrite it",
+            config_path.display()
+        );
+    }
+
+    let content = format!(
+        r#"# catalyst configuration. Command-line flags take precedence over these values.
+
+# Simulator device used by `catalyst run`
+# simulator = "{DEFAULT_SIMULATOR}"
+# simulator_os = "17.5"
+
+# App target `catalyst run` launches without --target, in projects with several apps
+# default_run_target = "MyApp"
+
+# How Bazel dependencies are declared: "workspace" (WORKSPACE) or "module" (MODULE.bazel)
+# bazel_mode = "workspace"
+
+# Name of the generated BUILD files: "BUILD" or "BUILD.bazel" (which also
+# renames WORKSPACE to WORKSPACE.bazel)
+# build_file_name = "BUILD"
+
+# Deployment target for targets that don't declare one
+# minimum_os_version = "{DEFAULT_MINIMUM_OS_VERSION}"
+
+# Build configuration: "debug" or "release"
+# configuration = "debug"
+
+# What builds target: "ios-simulator" or "ios-device"; Bazel's default when unset
+# platform = "ios-simulator"
+
+# Device families for targets that don't set TARGETED_DEVICE_FAMILY
+# families = ["iphone", "ipad"]
+
+# How a framework several apps depend on is linked: "dynamic" embeds it,
+# "static" links its code into each app
+# shared_frameworks = "dynamic"
+
+# Order of the source files in srcs: "sorted" by path, or "graph" to keep the
+# order the Tuist graph lists them in
+# source_order = "sorted"
+
+# Whether resources go to the root of the bundle; false keeps the
+# subdirectories of their buildable folder, for lookups by subdirectory
+# flatten_resources = true
+
+# Info.plist for apps that don't have one, instead of catalyst's minimal one;
+# {{{{bundle_id}}}}, {{{{name}}}}, {{{{version}}}}, and {{{{build}}}} are filled in
+# info_plist_template = "Templates/App-Info.plist"
+
+# Directory the Bazel workspace is rooted at, relative to the directory of
+# catalyst.toml, for projects outside it; detected from the graph by default
+# workspace_root = ".."
+
+# Rule set versions. In WORKSPACE mode an overridden version needs its sha256 too.
+# rules_apple_version = "{RULES_APPLE_VERSION}"
+# rules_apple_sha256 = "{RULES_APPLE_SHA256}"
+# rules_swift_version = "{RULES_SWIFT_VERSION}"
+# rules_swift_sha256 = ""
+# apple_support_version = "{APPLE_SUPPORT_VERSION}"
+# apple_support_sha256 = ""
+# Fetch a rule set from a git mirror or a local checkout instead of GitHub
+# rules_apple_source = {{ git = "https://git.example.com/rules_apple.git", commit = "<sha>" }}
+# rules_swift_source = {{ path = "/opt/mirrors/rules_swift" }}
+# apple_support_source = {{ git = "https://git.example.com/apple_support.git", commit = "<sha>" }}
+# rules_xcodeproj_version = "{RULES_XCODEPROJ_VERSION}"
+# rules_xcodeproj_sha256 = ""
+
+# Extra lines appended to the generated .bazelrc
+# bazelrc = ["build --disk_cache=~/.cache/bazel-disk"]
+
+# Shared remote cache and build stamping for every build
+# remote_cache = "grpcs://cache.example.com"
+# workspace_status_command = "tools/workspace_status.sh"
+
+# Bazel release pinned in the generated .bazelversion
+# bazel_version = "{BAZEL_VERSION}"
+
+# Executables to run, e.g. a Bazelisk install or a wrapper script
+# bazel = "bazel"
+# tuist = "tuist"
+
+# Format the generated BUILD and WORKSPACE files with buildifier, when it's
+# installed; skipped with a warning otherwise
+# format_build_files = false
+# buildifier = "buildifier"
+
+# Kill a tuist, bazel, or simctl command that runs longer, e.g. a hung build on CI
+# command_timeout = 1800
+
+# Projects and targets built another way, which catalyst generates nothing for;
+# targets that depend on them still get their labels. Projects match by name or
+# package path, targets by name
+# exclude_projects = ["Vendor/*"]
+# exclude_targets = ["Legacy*"]
+
+# --define values for every build and test, for select()s in hand-written BUILD
+# snippets; --define KEY=VALUE on the command line replaces a key's value
+# [defines]
+# feature_new_onboarding = "1"
+
+# Files to leave out of a target, as globs relative to its project directory
+# [exclude]
+# App = ["App/Sources/**/*+macOS.swift"]
+
+# Settings for a single target, which win over the Tuist graph
+# [targets.App]
+# families = ["iphone"]
+# minimum_os_version = "17.0"
+# bundle_id = "com.example.App"
+# infoplist = "App/Info.plist"
+# tags = ["smoke"]
+# provisioning_profile = "Profiles/App.mobileprovision"
+# generated_header_name = "AcmeApp-Swift.h"
+#
+# [targets.FlakyTests]
+# all_tests = false
+"#
+    );
+
+    write_file(&config_path, content)
+        .with_context(|| format!("Failed to write {}", config_path.display()))?;
+    if !dry_run() {
+        println!("Created {}", config_path.display());
+    }
+
+    let missing: Vec<&str> = ["tuist", "bazel"]
+        .into_iter()
+        .filter(|tool| which::which(tool).is_err())
+        .collect();
+    if !missing.is_empty() {
+        warning!(
+            "{} not found on PATH; run `catalyst doctor` for details",
+            missing.join(" and ")
+        );
+    }
+
+    println!("\nNext steps:");
+    println!("  1. Adjust {} for your project", Config::FILE_NAME);
+    println!("  2. Run `catalyst doctor` to check your setup");
+    println!("  3. Run `catalyst build` to generate the Bazel files and build");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_in_the_configuration_are_relative_to_its_directory() {
+        let workspace = tempfile::tempdir().unwrap();
+        let workspace_dir = workspace.path().canonicalize().unwrap();
+        let project_dir = workspace_dir.join("Apps/App");
+        fs::create_dir_all(&project_dir).unwrap();
+        fs::write(
+            workspace_dir.join("Apps").join(Config::FILE_NAME),
+            "workspace_root = \"..\"\ninfo_plist_template = \"Templates/Info.plist\"\n",
+        )
+        .unwrap();
+
+        let options = GenerateOptions::from_config(&Config::load(&project_dir).unwrap());
+        assert_eq!(
+            options
+                .workspace_root
+                .map(|root| normalize_lexically(&root)),
+            Some(workspace_dir.clone())
+        );
+        assert_eq!(
+            options.info_plist_template,
+            Some(workspace_dir.join("Apps/Templates/Info.plist"))
+        );
+    }
+}
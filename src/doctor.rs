@@ -0,0 +1,92 @@
+//! `catalyst doctor`, checking the tools catalyst runs.
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::cache::get_catalyst_cache_dir;
+use crate::command::CommandExt;
+use crate::config::Config;
+use crate::simulator::list_simulators;
+
+fn print_check(ok: bool, name: &str, detail: &str) {
+    let (color, mark) = if ok {
+        ("\x1b[32m", "✓")
+    } else {
+        ("\x1b[31m", "✗")
+    };
+    println!("{}{}\x1b[0m {:<10} {}", color, mark, name, detail);
+}
+
+/// Runs `command --version` and returns the first line of its output.
+fn tool_version(command: &Path) -> Option<String> {
+    let output = Command::new(command).arg("--version").output_timed().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines().next().map(|line| line.trim().to_string())
+}
+
+pub fn run_doctor() -> Result<()> {
+    let mut failures = 0;
+
+    // Check the executables catalyst.toml points at, if any
+    let config = Config::load(Path::new("."))?;
+    let tools = [
+        (
+            "tuist",
+            config.tuist.unwrap_or_else(|| PathBuf::from("tuist")),
+        ),
+        (
+            "bazel",
+            config.bazel.unwrap_or_else(|| PathBuf::from("bazel")),
+        ),
+        ("xcrun", PathBuf::from("xcrun")),
+    ];
+    for (tool, executable) in tools {
+        match which::which(&executable) {
+            Ok(path) => {
+                let version = tool_version(&path).unwrap_or_else(|| "unknown version".into());
+                print_check(true, tool, &format!("{} ({})", path.display(), version));
+            }
+            Err(_) => {
+                failures += 1;
+                print_check(
+                    false,
+                    tool,
+                    &format!("{} not found on PATH", executable.display()),
+                );
+            }
+        }
+    }
+
+    // A simulator is only needed for `catalyst run`, but missing runtimes are a
+    // common setup problem worth surfacing here
+    let simulators = list_simulators().map(|d| d.len()).unwrap_or(0);
+    if simulators > 0 {
+        print_check(
+            true,
+            "simulator",
+            &format!("{} available devices", simulators),
+        );
+    } else {
+        failures += 1;
+        print_check(false, "simulator", "no available simulator devices");
+    }
+
+    match get_catalyst_cache_dir() {
+        Ok(dir) => print_check(true, "cache", &dir.display().to_string()),
+        Err(err) => {
+            failures += 1;
+            print_check(false, "cache", &err.to_string());
+        }
+    }
+
+    if failures > 0 {
+        anyhow::bail!("{} check(s) failed", failures);
+    }
+
+    println!("\nEverything looks good!");
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! The errors tools embedding catalyst may want to handle.
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::simulator::is_udid;
+
+/// Failures that tools embedding catalyst may want to handle. They're
+/// returned inside `anyhow::Error`, below any context added on the way up, so
+/// match them with `err.downcast_ref::<CatalystError>()`.
+#[derive(Debug, thiserror::Error)]
+pub enum CatalystError {
+    /// The Tuist executable couldn't be found
+    #[error("{} isn't installed or isn't on PATH; install Tuist or pass --tuist", .tuist.display())]
+    TuistNotFound { tuist: PathBuf },
+    /// The Tuist graph, or a project in it, isn't in the expected format
+    #[error("Failed to parse {what}")]
+    GraphParse {
+        /// e.g. `project /path/to/App from graph`
+        what: String,
+        #[source]
+        source: serde_json::Error,
+    },
+    /// Bazel ran, and the build failed
+    #[error("{}", with_output_tail(&match .target {
+        Some(target) => format!("Bazel build failed for target {}", target),
+        None => "Bazel build failed".to_string(),
+    }, .output))]
+    BazelBuildFailed {
+        /// Bazel's exit code, `None` when it was killed
+        code: Option<i32>,
+        /// The target built, or `None` for `//...`
+        target: Option<String>,
+        /// The last lines Bazel wrote to stderr
+        output: Vec<String>,
+    },
+    /// The graph has no iOS app to run, or none of the requested name
+    #[error("{}", match .requested {
+        Some(name) => format!("No app target named {}; the apps are: {}", name, .available.join(", ")),
+        None => "No iOS app target found in project".to_string(),
+    })]
+    NoAppTarget {
+        requested: Option<String>,
+        /// The app targets the graph has
+        available: Vec<String>,
+    },
+    /// No available simulator has the requested name or UDID
+    #[error("{}", if is_udid(.query) {
+        format!("No available simulator with UDID {}", .query)
+    } else {
+        format!("No available simulator named '{}'. Pass one of the devices above with --simulator", .query)
+    })]
+    SimulatorNotFound { query: String },
+    /// A command ran longer than the timeout set with `set_command_timeout`
+    /// and was killed
+    #[error("{}", with_output_tail(
+        &format!("`{}` timed out after {}s and was killed", .command, .timeout.as_secs()),
+        .output,
+    ))]
+    TimedOut {
+        command: String,
+        timeout: Duration,
+        /// The last lines of the command's stderr
+        output: Vec<String>,
+    },
+}
+
+/// `message`, followed by the last lines of a failed command's output.
+pub(crate) fn with_output_tail(message: &str, tail: &[String]) -> String {
+    if tail.is_empty() {
+        message.to_string()
+    } else {
+        format!(
+            "{}; last lines of its output:\n  {}",
+            message,
+            tail.join("\n  ")
+        )
+    }
+}
@@ -0,0 +1,426 @@
+//! `list-targets` and `explain-target`.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::build_file::{
+    bundle_directory, generate_build_file, glob_matches, resource_directory, swift_identifier,
+    OBJC_EXTENSIONS, RESOURCE_EXTENSIONS,
+};
+use crate::cache::{get_catalyst_cache_dir, load_graph};
+use crate::config::{apply_target_overrides, Config, GenerateOptions, Platform, SharedFrameworks};
+use crate::graph::{
+    synthesized_name, synthesized_target, PathResolver, TuistDependency, TuistProject,
+};
+use crate::index::TargetIndex;
+use crate::plist::read_info_plist_template;
+use crate::starlark::{render_families, starlark_string};
+use crate::tuist::{graph_format_hint, run_tuist_graph};
+
+/// A project as printed by `list-targets`.
+#[derive(Debug, Serialize)]
+struct ProjectSummary<'a> {
+    name: &'a str,
+    path: &'a str,
+    targets: Vec<TargetSummary<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct TargetSummary<'a> {
+    name: &'a str,
+    product: &'a str,
+    bundle_id: &'a str,
+    dependencies: Vec<&'a str>,
+}
+
+pub fn list_targets(project_dir: &Path, tuist: &Path, json: bool) -> Result<()> {
+    // Read-only: go straight to tuist so the cache is left untouched
+    let graph = run_tuist_graph(tuist, project_dir)?;
+    let projects = graph.projects()?;
+
+    let summaries: Vec<ProjectSummary> = projects
+        .iter()
+        .map(|project| {
+            let mut targets: Vec<TargetSummary> = project
+                .targets
+                .values()
+                .map(|target| TargetSummary {
+                    name: &target.name,
+                    product: &target.product,
+                    bundle_id: &target.bundle_id,
+                    dependencies: target
+                        .dependencies
+                        .iter()
+                        .filter_map(TuistDependency::target_name)
+                        .collect(),
+                })
+                .collect();
+            targets.sort_by(|a, b| a.name.cmp(b.name));
+
+            ProjectSummary {
+                name: &project.name,
+                path: &project.path,
+                targets,
+            }
+        })
+        .collect();
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&summaries)?);
+        return Ok(());
+    }
+
+    for project in &summaries {
+        println!("\n{} ({})", project.name, project.path);
+        for target in &project.targets {
+            println!(
+                "  {} [{}] {}",
+                target.name, target.product, target.bundle_id
+            );
+            if !target.dependencies.is_empty() {
+                println!("    depends on: {}", target.dependencies.join(", "));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints how catalyst converts one target: the files of its buildable
+/// folders it takes and those it leaves out, what each dependency becomes,
+/// the settings it reads, and the rules it generates. The rules are rendered
+/// into a temporary directory, so nothing in the project is written.
+pub fn explain_target(
+    project_dir: &Path,
+    target_name: &str,
+    options: &GenerateOptions,
+) -> Result<()> {
+    let cache_dir = get_catalyst_cache_dir()?;
+    fs::create_dir_all(&cache_dir)?;
+    let graph = load_graph(project_dir, &cache_dir, options, options.no_cache)?;
+    let mut projects = graph
+        .projects()
+        .with_context(|| graph_format_hint(graph.tuist_version.as_deref()))?;
+    let resolver = PathResolver::new(project_dir, &graph.path);
+    let variant = options.configuration.bazelrc_config();
+    // The xcconfig files the explained target's settings come from
+    let mut xcconfigs = Vec::new();
+    for project in &mut projects {
+        project.resolve_paths(&resolver);
+        let project_path = PathBuf::from(&project.path);
+        for target in project.targets.values_mut() {
+            let mut read = Vec::new();
+            target.apply_xcconfigs(&project.settings, variant, &project_path, &mut read)?;
+            if target.name == target_name {
+                xcconfigs.extend(read);
+            }
+        }
+    }
+    xcconfigs.sort();
+    xcconfigs.dedup();
+    apply_target_overrides(&mut projects, &options.targets)?;
+
+    let package_root = options.package_root(project_dir, &graph);
+    let mut index = TargetIndex::new(&package_root, &projects);
+    if options.shared_frameworks == SharedFrameworks::Dynamic {
+        index.share_frameworks(&projects);
+    }
+    index.exclude(&projects, options);
+
+    let Some((project, target)) = projects
+        .iter()
+        .find_map(|project| Some((project, project.targets.get(target_name)?)))
+    else {
+        anyhow::bail!(
+            "{} isn't a target of the Tuist graph of {}; `catalyst list-targets` prints its targets",
+            target_name,
+            graph.path
+        );
+    };
+    let platform = target.platform();
+    println!(
+        "{} [{}, {}] in project {} ({})",
+        target.name,
+        target.product,
+        platform.prefix(),
+        project.name,
+        project.path
+    );
+    println!(
+        "  package: //{}",
+        index.package(&project.path).unwrap_or_default()
+    );
+    if !index.generates(project, target) {
+        println!(
+            "\ncatalyst generates no rules for it, as exclude_projects or exclude_targets in {} matches it",
+            Config::FILE_NAME
+        );
+        return Ok(());
+    }
+
+    // Render the project's BUILD file where it can't touch the project, and
+    // keep the target's rules. Rendering logs what it writes and warns about
+    // the other targets too, which goes ahead of the trace.
+    let plist_template = options
+        .info_plist_template
+        .as_ref()
+        .map(|path| read_info_plist_template(&project_dir.join(path)))
+        .transpose()?;
+    let temp_dir = tempfile::Builder::new()
+        .prefix("catalyst-explain-")
+        .tempdir()
+        .context("Failed to create a temporary directory for the rules")?;
+    let output_dir = temp_dir.path();
+    generate_build_file(
+        project,
+        &index,
+        output_dir,
+        options,
+        plist_template.as_deref(),
+    )
+    .with_context(|| format!("Project {}", project.name))?;
+    let build_file = options.build_file(output_dir);
+    let content = fs::read_to_string(&build_file)
+        .with_context(|| format!("Failed to read {}", build_file.display()))?;
+    let lower = target.name.to_lowercase();
+    let names: Vec<String> = target
+        .rule_names()
+        .into_iter()
+        .chain([format!("{}_resources", lower)])
+        .map(|name| format!("    name = {},", starlark_string(&name)))
+        .collect();
+    let rules: Vec<&str> = content
+        .split("\n\n")
+        .filter(|rule| {
+            names
+                .iter()
+                .any(|name| rule.lines().any(|line| line == name))
+        })
+        .map(str::trim_end)
+        .collect();
+
+    // The files of the buildable folders, classified as generate_build_file does
+    let is_test = matches!(target.product.as_str(), "unit_tests" | "ui_tests");
+    let excluded = options
+        .exclude
+        .get(&target.name)
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let mut included: Vec<String> = Vec::new();
+    let mut left_out: Vec<String> = Vec::new();
+    let mut has_objc = false;
+    for folder in &target.buildable_folders {
+        for file in &folder.resolved_files {
+            let path = Path::new(&file.path);
+            let rel_path = path.strip_prefix(&project.path).unwrap_or(path);
+            let reason = if folder.excludes(&file.path) {
+                Some("excluded by the manifest".to_string())
+            } else if !path.exists() {
+                Some("missing on disk".to_string())
+            } else if !path.starts_with(&project.path) {
+                Some("outside the project directory".to_string())
+            } else {
+                excluded
+                    .iter()
+                    .find(|pattern| glob_matches(pattern, rel_path))
+                    .map(|pattern| format!("matches {} in {}", pattern, Config::FILE_NAME))
+            };
+            if let Some(reason) = reason {
+                left_out.push(format!("{} ({})", rel_path.display(), reason));
+                continue;
+            }
+            let ext = rel_path
+                .extension()
+                .map(|ext| ext.to_string_lossy())
+                .unwrap_or_default();
+            let kind = if let Some(bundle) = bundle_directory(rel_path) {
+                if bundle == rel_path {
+                    "prebuilt bundle".to_string()
+                } else {
+                    format!("prebuilt bundle, in {}", bundle.display())
+                }
+            } else if let Some(dir) = resource_directory(rel_path) {
+                if dir == rel_path {
+                    "resource directory".to_string()
+                } else {
+                    format!("resource, in {}", dir.display())
+                }
+            } else if ext == "swift" {
+                "Swift source".to_string()
+            } else if ext == "h" || OBJC_EXTENSIONS.contains(&ext.as_ref()) {
+                has_objc = true;
+                "Objective-C source".to_string()
+            } else if RESOURCE_EXTENSIONS.contains(&ext.as_ref()) {
+                "resource".to_string()
+            } else if is_test {
+                "test data".to_string()
+            } else {
+                left_out.push(format!(
+                    "{} (neither a source nor a resource)",
+                    rel_path.display()
+                ));
+                continue;
+            };
+            let kind = if is_test && kind.starts_with("resource") {
+                format!("{}, and test data", kind)
+            } else {
+                kind
+            };
+            included.push(format!("{} ({})", rel_path.display(), kind));
+        }
+    }
+    for path in project.synthesized_sources() {
+        if synthesized_target(&path) == Some(synthesized_name(&target.name)) {
+            let rel_path = path.strip_prefix(&project.path).unwrap_or(&path);
+            included.push(format!(
+                "{} (Swift source, synthesized by Tuist)",
+                rel_path.display()
+            ));
+        }
+    }
+    included.sort();
+    left_out.sort();
+    println!("\nFiles ({}):", included.len());
+    for file in &included {
+        println!("  {}", file);
+    }
+    if target
+        .buildable_folders
+        .iter()
+        .all(|folder| folder.resolved_files.is_empty())
+    {
+        for folder in &target.buildable_folders {
+            println!(
+                "  {}/**/*.swift (the graph lists no files of the folder, so it's globbed)",
+                folder.path
+            );
+        }
+    }
+    if !left_out.is_empty() {
+        println!("\nLeft out ({}):", left_out.len());
+        for file in &left_out {
+            println!("  {}", file);
+        }
+    }
+
+    println!("\nDependencies ({}):", target.dependencies.len());
+    for dep in &target.dependencies {
+        let (name, outcome) = explain_dependency(dep, &index, project, platform);
+        println!("  {} -> {}", name, outcome);
+    }
+
+    println!("\nSettings:");
+    let setting = |name: &str, value: String| {
+        if !value.is_empty() {
+            println!("  {}: {}", name, value);
+        }
+    };
+    setting(
+        "module name",
+        swift_identifier(target.declared_module_name()),
+    );
+    setting("product name", target.product_name().to_string());
+    setting("bundle ID", target.bundle_id.clone());
+    setting(
+        "minimum OS version",
+        target
+            .minimum_os_version(&options.minimum_os_version)
+            .to_string(),
+    );
+    if platform == Platform::Ios {
+        setting(
+            "families",
+            render_families(&target.families(&options.families)),
+        );
+    }
+    setting(
+        "Swift copts",
+        target.swift_copts(options.configuration).join(" "),
+    );
+    setting("SDK linkopts", target.sdk_linkopts().join(" "));
+    setting(
+        "OTHER_LDFLAGS linkopts",
+        target.linker_flags(options.configuration).0.join(" "),
+    );
+    setting(
+        "bridging header",
+        target
+            .bridging_header(Path::new(&project.path))
+            .map(|path| path.display().to_string())
+            .unwrap_or_default(),
+    );
+    setting(
+        "generated header",
+        index
+            .generated_header(target, project, options, has_objc)
+            .unwrap_or_default(),
+    );
+    setting(
+        "xcconfigs",
+        xcconfigs
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+    );
+    if options.targets.contains_key(&target.name) {
+        setting(
+            "overrides",
+            format!("[targets.{}] in {}", target.name, Config::FILE_NAME),
+        );
+    }
+
+    println!("\nRules:");
+    for rule in &rules {
+        println!("\n{}", rule);
+    }
+
+    Ok(())
+}
+
+/// A dependency of the target `explain_target` explains, and the label it
+/// becomes or why it has none.
+fn explain_dependency(
+    dep: &TuistDependency,
+    index: &TargetIndex,
+    from: &TuistProject,
+    platform: Platform,
+) -> (String, String) {
+    let name = if let Some(name) = dep.target_name() {
+        format!("target {}", name)
+    } else if let Some(package) = &dep.package {
+        format!("package product {}", package.product)
+    } else if let Some((_, prebuilt)) = dep.prebuilt() {
+        format!("prebuilt {}", prebuilt.path)
+    } else if let Some(sdk) = &dep.sdk {
+        format!("SDK {}", sdk.name)
+    } else {
+        "unknown dependency".to_string()
+    };
+    if let Some(label) = index.dependency_label(dep, from, platform) {
+        return (name, label);
+    }
+    let outcome = if dep.is_unlinked() {
+        "not linked (status: none)".to_string()
+    } else if let Some(sdk) = &dep.sdk {
+        format!("linked with {}", sdk.linkopts().join(" "))
+    } else if let Some((_, prebuilt)) = dep.prebuilt() {
+        match index.imports.get(&prebuilt.path) {
+            Some(Err(reason)) => format!("dropped; {}", reason),
+            _ => "dropped".to_string(),
+        }
+    } else {
+        match index.resolve(dep, from).map(|dep| index.product(&dep)) {
+            None => "dropped; it isn't a target of the graph".to_string(),
+            Some(Some("app_extension")) => "embedded in the app rather than linked".to_string(),
+            Some(Some("bundle")) => "bundled with the target's resources".to_string(),
+            Some(Some("app")) => {
+                "an app for another platform, embedded rather than linked".to_string()
+            }
+            Some(_) => "dropped".to_string(),
+        }
+    };
+    (name, outcome)
+}
@@ -0,0 +1,1074 @@
+//! Generating the Bazel files of a workspace, package by package.
+
+use anyhow::{Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
+
+use crate::build_file::{generate_build_file, read_build_extra, ProvisioningProfile};
+use crate::cache::{
+    get_catalyst_cache_dir, get_project_cache_dir, hash_file_contents, load_graph,
+    workspace_inputs_hash, GeneratedManifest, PackageStamp, PackageStamps,
+};
+use crate::command::CommandExt;
+use crate::config::{apply_target_overrides, BazelMode, Config, GenerateOptions, SharedFrameworks};
+use crate::graph::{BuildableFolder, PathResolver, TuistGraph, TuistProject, TuistTarget};
+use crate::index::TargetIndex;
+use crate::output::{dry_run, take_warnings, PhaseTimer, Progress};
+use crate::plist::read_info_plist_template;
+use crate::report::{BuildReport, GenerationSummary};
+use crate::starlark::starlark_string;
+use crate::tuist::graph_format_hint;
+use crate::workspace::{
+    generate_bazelrc, generate_bazelversion, generate_module_file, generate_workspace_file,
+    link_project_sources, remove_other_build_file, write_file, write_generated_file,
+};
+
+/// Heads the section catalyst adds to the project's .gitignore.
+const GITIGNORE_HEADER: &str = "### Generated by catalyst ###";
+
+/// Directories other tools fill with generated sources and checkouts. The
+/// ones that exist, and hold no Tuist project, are added to the workspace's
+/// .bazelignore so `//...` doesn't pick up packages inside them.
+const BAZELIGNORE_DEFAULTS: &[&str] = &["DerivedData", "Pods", ".build", "Tuist/.build"];
+
+/// Runs every step up to (but not including) the Bazel build and returns the
+/// paths of the files that were written.
+pub fn generate_project(
+    project_dir: &Path,
+    options: &GenerateOptions,
+    report: &mut BuildReport,
+) -> Result<Vec<PathBuf>> {
+    info!("Running catalyst on project: {}", project_dir.display());
+
+    // Step 1: Get XDG-compliant cache directory
+    let cache_dir = get_catalyst_cache_dir()?;
+    fs::create_dir_all(&cache_dir)?;
+
+    verbose!("Using catalyst cache directory: {}", cache_dir.display());
+
+    // Step 2: Run tuist graph, or reuse the cached graph if nothing changed
+    let graph = load_graph(project_dir, &cache_dir, options, options.no_cache)?;
+    report.add_projects(&graph)?;
+
+    // Step 3: Generate Bazel files
+    let mut summary = GenerationSummary::default();
+    let written = generate_bazel_files(&graph, project_dir, &cache_dir, options, &mut summary);
+    summary.warnings = take_warnings();
+    let warnings = summary.warnings.clone();
+    report.summary = Some(summary);
+    let written = written?;
+    // The warnings were printed as they came up; list them again next to
+    // the error, where CI logs end
+    if options.fail_on_warnings && !warnings.is_empty() {
+        anyhow::bail!(
+            "Generation printed {} warning(s), which --fail-on-warnings makes fatal:\n  {}",
+            warnings.len(),
+            warnings.join("\n  ")
+        );
+    }
+    Ok(written)
+}
+
+pub fn generate_bazel_files(
+    graph: &TuistGraph,
+    project_dir: &Path,
+    cache_dir: &Path,
+    options: &GenerateOptions,
+    summary: &mut GenerationSummary,
+) -> Result<Vec<PathBuf>> {
+    info!("Generating Bazel files...");
+    let _timer = PhaseTimer::start("generate Bazel files");
+
+    // With --keep-going, projects that fail to parse or generate are reported
+    // at the end, after the others' files are written
+    let mut failed = Vec::new();
+    let mut projects = if options.keep_going {
+        let (projects, failures) = graph.parse_projects()?;
+        failed.extend(failures.iter().map(|err| format!("{:#}", err)));
+        projects
+    } else {
+        graph
+            .projects()
+            .with_context(|| graph_format_hint(graph.tuist_version.as_deref()))?
+    };
+    let resolver = PathResolver::new(project_dir, &graph.path);
+    for project in &mut projects {
+        project.resolve_paths(&resolver);
+    }
+    // Bazel happily builds an empty workspace, which would pass for success
+    if projects.is_empty() {
+        anyhow::bail!(
+            "The Tuist graph of {} has no projects; check that its Project.swift or Workspace.swift defines one",
+            graph.path
+        );
+    }
+    if projects.iter().all(|project| project.targets.is_empty()) {
+        anyhow::bail!(
+            "The Tuist graph of {} has no targets; check the targets declared in its Project.swift",
+            graph.path
+        );
+    }
+
+    for name in options.exclude.keys() {
+        let known = projects
+            .iter()
+            .any(|project| project.targets.contains_key(name));
+        if !known {
+            warning!(
+                "{} excludes files from {}, which isn't a target of the graph",
+                Config::FILE_NAME,
+                name
+            );
+        }
+    }
+    let variant = options.configuration.bazelrc_config();
+    let mut xcconfigs: HashMap<String, Vec<PathBuf>> = HashMap::new();
+    for project in &mut projects {
+        let project_path = PathBuf::from(&project.path);
+        let read = xcconfigs.entry(project.path.clone()).or_default();
+        for target in project.targets.values_mut() {
+            target.apply_xcconfigs(&project.settings, variant, &project_path, read)?;
+        }
+        read.sort();
+        read.dedup();
+    }
+    apply_target_overrides(&mut projects, &options.targets)?;
+
+    // Projects outside the graph's root put the workspace at a directory
+    // that holds them all, which packages are then relative to
+    let source_root = options.source_root(project_dir, graph);
+    let package_root = options.package_root(project_dir, graph);
+    if package_root != resolver.root {
+        info!("Using workspace root: {}", package_root.display());
+    }
+    let mut index = TargetIndex::new(&package_root, &projects);
+    if options.shared_frameworks == SharedFrameworks::Dynamic {
+        index.share_frameworks(&projects);
+    }
+    index.exclude(&projects, options);
+    index.check_rule_names(&projects)?;
+    index.check_bundle_ids(&projects)?;
+    index.check_cycles(&projects)?;
+    check_missing_files(&projects, &index, options.strict)?;
+    let plist_template = options
+        .info_plist_template
+        .as_ref()
+        .map(|path| read_info_plist_template(&project_dir.join(path)))
+        .transpose()?;
+
+    let workspace_dir = options.workspace_dir(project_dir, graph);
+    if !dry_run() {
+        fs::create_dir_all(&workspace_dir).with_context(|| {
+            format!(
+                "Failed to create output directory {}",
+                workspace_dir.display()
+            )
+        })?;
+    }
+
+    let project_cache_dir = get_project_cache_dir(cache_dir, project_dir)?;
+    let mut written = Vec::new();
+    let buildifier = buildifier(options);
+
+    // Generate WORKSPACE or MODULE.bazel file. Writing both makes Bazel
+    // complain about a dual configuration, so only one is ever emitted.
+    // local_provisioning_profile rules need the repository of the profiles
+    // installed on the machine
+    let local_profiles = projects
+        .iter()
+        .flat_map(|project| {
+            project
+                .targets
+                .values()
+                .filter(|target| index.generates(project, target))
+        })
+        .filter(|target| matches!(target.product.as_str(), "app" | "app_extension"))
+        .any(|target| {
+            matches!(
+                target.provisioning_profile(options),
+                Some(ProvisioningProfile::Local { .. })
+            )
+        });
+    let manifest = GeneratedManifest::load(&project_cache_dir)?;
+    let workspace_file = match options.bazel_mode {
+        BazelMode::Workspace => {
+            let other = options.build_file_name.other().workspace();
+            remove_other_build_file(&workspace_dir.join(other), &manifest)?;
+            generate_workspace_file(&workspace_dir, options, local_profiles)?
+        }
+        BazelMode::Module => generate_module_file(&workspace_dir, options, local_profiles)?,
+    };
+    written.push(workspace_file.clone());
+
+    // Generate .bazelrc file
+    written.extend(generate_bazelrc(
+        &source_root,
+        &workspace_dir,
+        options,
+        &index.platforms,
+    )?);
+    written.extend(generate_bazelversion(
+        &source_root,
+        &workspace_dir,
+        &project_cache_dir,
+        options,
+    )?);
+
+    if workspace_dir == source_root {
+        update_bazelignore(&workspace_dir, &projects, &index)?;
+    }
+    let ignored = read_bazelignore(&workspace_dir)?;
+
+    // Every project is its own Bazel package, at its path below the root
+    let mut packages: Vec<(&TuistProject, PathBuf)> = Vec::new();
+    for project in &projects {
+        if !index.generates_project(project) {
+            continue;
+        }
+        let package_dir = match index.package(&project.path) {
+            Some(package) if is_bazelignored(&package, &ignored) => {
+                warning!(
+                    "skipping project {}; {} is listed in .bazelignore",
+                    project.name,
+                    package
+                );
+                continue;
+            }
+            Some(package) => workspace_dir.join(package),
+            None => {
+                warning!(
+                    "project {} is outside the workspace root; writing its BUILD file to the root package",
+                    project.path
+                );
+                workspace_dir.clone()
+            }
+        };
+        if let Some((other, _)) = packages.iter().find(|(_, dir)| *dir == package_dir) {
+            anyhow::bail!(
+                "Projects {} and {} would both write {}",
+                other.name,
+                project.name,
+                options.build_file(&package_dir).display()
+            );
+        }
+        packages.push((project, package_dir));
+    }
+    for (project, package_dir) in &packages {
+        let other = options.build_file_name.other().build();
+        remove_other_build_file(&package_dir.join(other), &manifest)?;
+        summary.projects += 1;
+        for target in project.targets.values() {
+            if index.generates(project, target) {
+                summary.add_target(target, project, options);
+            }
+        }
+    }
+
+    // Projects whose definition and surroundings are unchanged since the last
+    // generation keep their files. The root package is always regenerated
+    // when workspace-wide rules, the app aliases, `//:all_tests` and the
+    // xcodeproj target, are added to it below.
+    let aliases = app_aliases(&projects, &index, options);
+    let all_tests = all_tests_suite(&projects, &index, options);
+    let mut stamps = PackageStamps::load(&project_cache_dir);
+    let workspace_inputs = workspace_inputs_hash(&projects, &index, options);
+    let raw_projects: HashMap<String, String> = graph
+        .projects
+        .as_array()
+        .map(|entries| {
+            entries
+                .chunks(2)
+                .filter_map(|pair| {
+                    let path = resolver.resolve(&resolver.root, pair[0].as_str()?);
+                    Some((path, pair[1].to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let mut inputs: HashMap<&str, String> = HashMap::new();
+    let mut unchanged = 0;
+    let affected = options
+        .add_target
+        .as_ref()
+        .map(|name| index.dependent_projects(name, &projects))
+        .transpose()?;
+    let mut untouched = 0;
+    packages.retain(|(project, package_dir)| {
+        let mut hasher = DefaultHasher::new();
+        workspace_inputs.hash(&mut hasher);
+        package_dir.hash(&mut hasher);
+        raw_projects.get(&project.path).hash(&mut hasher);
+        read_build_extra(project).ok().flatten().hash(&mut hasher);
+        plist_template.hash(&mut hasher);
+        project.synthesized_sources().hash(&mut hasher);
+        xcconfigs
+            .get(&project.path)
+            .and_then(|files| hash_file_contents(files))
+            .hash(&mut hasher);
+        let key = format!("{:016x}", hasher.finish());
+
+        let has_root_rules = options.with_xcodeproj || aliases.is_some() || all_tests.is_some();
+        let is_root = has_root_rules && *package_dir == workspace_dir;
+        // An added target leaves the packages that don't mention it as they
+        // are, whether or not they'd come out differently. The root package
+        // gets the workspace-wide rules added again, so it's regenerated.
+        if let Some(affected) = &affected {
+            if !affected.contains(&project.path) && !is_root {
+                verbose!("Leaving {} as it is", project.name);
+                match stamps.packages.get(&project.path) {
+                    Some(stamp) => written.extend(stamp.files.iter().cloned()),
+                    None => written.push(options.build_file(package_dir)),
+                }
+                untouched += 1;
+                return false;
+            }
+        }
+        let regenerate = options.force || is_root;
+        let files = (!regenerate)
+            .then(|| stamps.unchanged_files(&project.path, &key))
+            .flatten();
+        if let Some(files) = files {
+            verbose!("Unchanged since the last generation: {}", project.name);
+            written.extend(files.iter().cloned());
+            unchanged += 1;
+            return false;
+        }
+        inputs.insert(project.path.as_str(), key);
+        true
+    });
+
+    // Projects write to distinct packages and only read the shared index, so
+    // their BUILD files are generated in parallel
+    let started = Instant::now();
+    let progress = Progress::bar(packages.len(), "Generating project");
+    let workers = match options.jobs {
+        Some(jobs) => jobs.max(1) as usize,
+        None => std::thread::available_parallelism().map_or(1, |n| n.get()),
+    };
+    let chunk_size = packages.len().div_ceil(workers).max(1);
+    let results: Vec<(&str, Result<Vec<PathBuf>>)> = std::thread::scope(|scope| {
+        let handles: Vec<_> = packages
+            .chunks(chunk_size)
+            .map(|chunk| {
+                let index = &index;
+                let progress = &progress;
+                let plist_template = plist_template.as_deref();
+                scope.spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|(project, package_dir)| {
+                            let result = generate_package(
+                                project,
+                                index,
+                                package_dir,
+                                options,
+                                plist_template,
+                                buildifier,
+                                progress,
+                            );
+                            progress.advance(&project.name);
+                            (project.path.as_str(), result)
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().expect("BUILD file generation panicked"))
+            .collect()
+    });
+    drop(progress);
+
+    let mut errors = Vec::new();
+    for (project, result) in results {
+        match result {
+            Ok(files) => {
+                if let Some(contents) = hash_file_contents(&files) {
+                    stamps.packages.insert(
+                        project.to_string(),
+                        PackageStamp {
+                            inputs: inputs[project].clone(),
+                            files: files.clone(),
+                            contents,
+                            xcconfigs: xcconfigs.remove(project).unwrap_or_default(),
+                        },
+                    );
+                }
+                written.extend(files);
+            }
+            Err(err) => {
+                stamps.packages.remove(project);
+                errors.push(format!("{:#}", err));
+            }
+        }
+    }
+    if !dry_run() {
+        stamps.save(&project_cache_dir)?;
+    }
+    if options.keep_going {
+        failed.append(&mut errors);
+    } else if !errors.is_empty() {
+        anyhow::bail!(
+            "Failed to generate {} BUILD file(s):\n  {}",
+            errors.len(),
+            errors.join("\n  ")
+        );
+    }
+    verbose!(
+        "Generated {} BUILD file(s) in {:.2?}",
+        packages.len(),
+        started.elapsed()
+    );
+    if unchanged > 0 {
+        info!(
+            "Skipped {} unchanged project(s); pass --force to regenerate them",
+            unchanged
+        );
+    }
+    if untouched > 0 {
+        info!(
+            "Left {} project(s) that don't depend on {} as they are",
+            untouched,
+            options.add_target.as_deref().unwrap_or_default()
+        );
+    }
+
+    let mut root_rules: Vec<RootRules> = Vec::new();
+    if let Some(rules) = aliases {
+        root_rules.push(RootRules {
+            what: "the aliases of the apps",
+            load: None,
+            rules,
+        });
+    }
+    if let Some(rules) = all_tests {
+        root_rules.push(RootRules {
+            what: "the suite of all tests",
+            load: None,
+            rules,
+        });
+    }
+    if options.with_xcodeproj {
+        root_rules.extend(xcodeproj_target(graph, &projects, &index));
+    }
+    if let Some(path) = add_root_rules(
+        &options.build_file(&workspace_dir),
+        &written,
+        &manifest,
+        &root_rules,
+    )? {
+        written.push(path);
+    }
+
+    // The packages' files were formatted as they were generated, before their
+    // stamps were taken; this covers the workspace file and the root rules
+    if let Some(buildifier) = buildifier {
+        let root_build = options.build_file(&workspace_dir);
+        let mut files = vec![workspace_file];
+        if written.contains(&root_build) {
+            files.push(root_build);
+        }
+        format_starlark_files(buildifier, &files);
+    }
+
+    // Out-of-tree output needs the sources visible inside the Bazel workspace
+    if workspace_dir != source_root {
+        if dry_run() {
+            info!(
+                "Would link the project's sources into {}",
+                workspace_dir.display()
+            );
+        } else {
+            written.extend(link_project_sources(&source_root, &workspace_dir)?);
+        }
+    } else if options.update_gitignore {
+        update_gitignore(&source_root, &written)?;
+    }
+
+    if !dry_run() {
+        GeneratedManifest::record(&project_cache_dir, &written)?;
+    }
+
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "{} project(s) failed; the files of the others were generated:\n  {}",
+            failed.len(),
+            failed.join("\n  ")
+        );
+    }
+
+    Ok(written)
+}
+
+/// Reports the files of the graph that don't exist on disk, which happens
+/// when the graph is older than the sources. They're left out of the BUILD
+/// files, so Bazel doesn't fail on them later; with `--strict` they fail the
+/// generation instead.
+fn check_missing_files(projects: &[TuistProject], index: &TargetIndex, strict: bool) -> Result<()> {
+    let mut missing: Vec<String> = projects
+        .iter()
+        .flat_map(|project| {
+            project
+                .targets
+                .values()
+                .filter(|target| index.generates(project, target))
+        })
+        .flat_map(|target| {
+            target
+                .buildable_folders
+                .iter()
+                .flat_map(BuildableFolder::included_files)
+                .filter(|file| !Path::new(&file.path).exists())
+                .map(move |file| format!("  {}: {}", target.name, file.path))
+        })
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    missing.sort();
+
+    let message = format!(
+        "{} file(s) in the Tuist graph don't exist; the graph may be stale, so run with --no-cache to refresh it:\n{}",
+        missing.len(),
+        missing.join("\n")
+    );
+    if strict {
+        anyhow::bail!(message);
+    }
+    warning!("{}", message);
+    Ok(())
+}
+
+/// An `xcodeproj` target covering every app, for the root BUILD file.
+fn xcodeproj_target(
+    graph: &TuistGraph,
+    projects: &[TuistProject],
+    index: &TargetIndex,
+) -> Option<RootRules> {
+    let mut apps: Vec<String> = projects
+        .iter()
+        .flat_map(|project| {
+            let package = index.package(&project.path).unwrap_or_default();
+            project
+                .targets
+                .values()
+                .filter(|target| target.product == "app" && index.generates(project, target))
+                .map(move |target| {
+                    starlark_string(format!("//{}:{}", package, target.name.to_lowercase()))
+                })
+        })
+        .collect();
+    apps.sort();
+
+    if apps.is_empty() {
+        warning!("no app targets found; skipping the xcodeproj target");
+        return None;
+    }
+
+    let rules = format!(
+        "xcodeproj(\n    name = \"xcodeproj\",\n    project_name = {},\n    tags = [\"manual\"],\n    top_level_targets = [{}],\n)\n",
+        starlark_string(&graph.name),
+        apps.join(", ")
+    );
+
+    Some(RootRules {
+        what: "the xcodeproj target",
+        load: Some("load(\"@rules_xcodeproj//xcodeproj:defs.bzl\", \"xcodeproj\")\n"),
+        rules,
+    })
+}
+
+/// `alias` rules in the root package for the apps of the other packages, so
+/// `bazel build //:app` works from the workspace root. An alias whose name
+/// two apps share, or that a target of the root package already uses, is
+/// left out with a warning.
+fn app_aliases(
+    projects: &[TuistProject],
+    index: &TargetIndex,
+    options: &GenerateOptions,
+) -> Option<String> {
+    let mut apps: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut taken: BTreeSet<String> = BTreeSet::new();
+    if options.with_xcodeproj {
+        taken.insert("xcodeproj".to_string());
+    }
+    for project in projects {
+        let package = index.package(&project.path).unwrap_or_default();
+        if package.is_empty() {
+            taken.extend(project.targets.values().flat_map(TuistTarget::rule_names));
+            taken.extend(
+                index
+                    .imports_of(&project.path)
+                    .map(|import| import.name.clone()),
+            );
+            continue;
+        }
+        for target in project.targets.values() {
+            if target.product == "app" && index.generates(project, target) {
+                let name = target.name.to_lowercase();
+                let label = format!("//{}:{}", package, name);
+                apps.entry(name).or_default().push(label);
+            }
+        }
+    }
+
+    let mut rules = String::new();
+    for (name, mut labels) in apps {
+        labels.sort();
+        if labels.len() > 1 {
+            warning!(
+                "not creating //:{}; the apps {} would share it",
+                name,
+                labels.join(" and ")
+            );
+        } else if taken.contains(&name) {
+            warning!(
+                "not creating //:{} for {}; the root package already has a target of that name",
+                name,
+                labels[0]
+            );
+        } else {
+            rules.push_str(&format!(
+                "alias(\n    name = {},\n    actual = {},\n)\n\n",
+                starlark_string(&name),
+                starlark_string(&labels[0])
+            ));
+        }
+    }
+    (!rules.is_empty()).then_some(rules)
+}
+
+/// The project's unit and UI test targets, sorted by name.
+fn test_targets<'a>(project: &'a TuistProject, index: &TargetIndex) -> Vec<&'a TuistTarget> {
+    let mut targets: Vec<&TuistTarget> = project
+        .targets
+        .values()
+        .filter(|target| matches!(target.product.as_str(), "unit_tests" | "ui_tests"))
+        .filter(|target| index.generates(project, target))
+        .collect();
+    targets.sort_by(|a, b| a.name.cmp(&b.name));
+    targets
+}
+
+/// A `test_suite` rule running `tests`, rendered labels.
+fn test_suite_rule(name: &str, tests: &[String]) -> String {
+    format!(
+        "test_suite(\n    name = {},\n    tests = [\n        {},\n    ],\n)\n\n",
+        starlark_string(name),
+        tests.join(",\n        ")
+    )
+}
+
+/// The suite of a project's tests, named after the project, e.g.
+/// `//Core:core_tests`, unless one of its rules already has that name.
+pub(crate) fn project_test_suite(project: &TuistProject, index: &TargetIndex) -> Option<String> {
+    let tests: Vec<String> = test_targets(project, index)
+        .iter()
+        .map(|target| starlark_string(format!(":{}", target.name.to_lowercase())))
+        .collect();
+    if tests.is_empty() {
+        return None;
+    }
+
+    let name = format!("{}_tests", project.name.to_lowercase());
+    let taken = project
+        .targets
+        .values()
+        .flat_map(TuistTarget::rule_names)
+        .chain(
+            index
+                .imports_of(&project.path)
+                .map(|import| import.name.clone()),
+        )
+        .any(|rule| rule == name);
+    if taken {
+        warning!(
+            "not creating the test suite :{} of project {}; one of its targets already has that name",
+            name,
+            project.name
+        );
+        return None;
+    }
+    Some(test_suite_rule(&name, &tests))
+}
+
+/// `//:all_tests`, running the tests of every project, apart from the ones
+/// `catalyst.toml` leaves out.
+fn all_tests_suite(
+    projects: &[TuistProject],
+    index: &TargetIndex,
+    options: &GenerateOptions,
+) -> Option<String> {
+    const NAME: &str = "all_tests";
+    let mut tests = Vec::new();
+    for project in projects {
+        let package = index.package(&project.path).unwrap_or_default();
+        let taken = package.is_empty()
+            && project
+                .targets
+                .values()
+                .flat_map(TuistTarget::rule_names)
+                .chain([format!("{}_tests", project.name.to_lowercase())])
+                .any(|rule| rule == NAME);
+        if taken {
+            warning!(
+                "not creating //:{}; the root package already has a target of that name",
+                NAME
+            );
+            return None;
+        }
+
+        for target in test_targets(project, index) {
+            let included = options
+                .targets
+                .get(&target.name)
+                .and_then(|overrides| overrides.all_tests)
+                .unwrap_or(true);
+            if included {
+                let label = format!("//{}:{}", package, target.name.to_lowercase());
+                tests.push(starlark_string(label));
+            } else {
+                verbose!("Leaving {} out of //:{}", target.name, NAME);
+            }
+        }
+    }
+    tests.sort();
+    (!tests.is_empty()).then(|| test_suite_rule(NAME, &tests))
+}
+
+/// Rules generated for the whole workspace, like the xcodeproj target, that
+/// go into the root BUILD file, with the `load` they need.
+struct RootRules {
+    what: &'static str,
+    load: Option<&'static str>,
+    rules: String,
+}
+
+/// Adds the rules generated for the whole workspace to the root BUILD file,
+/// all at once so their loads come before any rule. A root BUILD catalyst
+/// generated for a project in this run gets the loads added after its own
+/// and the rules appended. Otherwise a BUILD holding only the rules is
+/// written and its path returned, replacing the one an earlier run wrote.
+/// A hand-written root BUILD is left alone.
+fn add_root_rules(
+    build_path: &Path,
+    written: &[PathBuf],
+    manifest: &GeneratedManifest,
+    root_rules: &[RootRules],
+) -> Result<Option<PathBuf>> {
+    if root_rules.is_empty() {
+        return Ok(None);
+    }
+    let loads: String = root_rules.iter().filter_map(|rules| rules.load).collect();
+    let rules = root_rules
+        .iter()
+        .map(|rules| rules.rules.trim_end())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let build_path = build_path.to_path_buf();
+    if dry_run() && written.contains(&build_path) {
+        for root_rules in root_rules {
+            info!(
+                "Would add {} to {}:\n{}",
+                root_rules.what,
+                build_path.display(),
+                root_rules.rules
+            );
+        }
+        Ok(None)
+    } else if written.contains(&build_path) {
+        let content = fs::read_to_string(&build_path)
+            .with_context(|| format!("Failed to read {}", build_path.display()))?;
+        let loads_end = leading_loads_end(&content);
+        let separator = if loads_end == 0 && !loads.is_empty() {
+            "\n"
+        } else {
+            ""
+        };
+        let content = format!(
+            "{}{}{}{}\n\n{}\n",
+            &content[..loads_end],
+            loads,
+            separator,
+            content[loads_end..].trim_end(),
+            rules
+        );
+        write_generated_file(&build_path, content).context("Failed to write BUILD file")?;
+        for root_rules in root_rules {
+            info!("Added {} to {}", root_rules.what, build_path.display());
+        }
+        Ok(None)
+    } else if build_path.exists() && !manifest.contains(&build_path) {
+        for root_rules in root_rules {
+            warning!(
+                "{} wasn't generated by catalyst; add {} to it by hand:\n{}{}",
+                build_path.display(),
+                root_rules.what,
+                root_rules.load.unwrap_or_default(),
+                root_rules.rules
+            );
+        }
+        Ok(None)
+    } else {
+        let content = if loads.is_empty() {
+            format!("{}\n", rules)
+        } else {
+            format!("{}\n{}\n", loads, rules)
+        };
+        write_generated_file(&build_path, content).context("Failed to write BUILD file")?;
+        info!("Generated: {}", build_path.display());
+        Ok(Some(build_path))
+    }
+}
+
+/// Where the `load` statements a BUILD file starts with end, after any
+/// comments before them. Bazel rejects a `load` after another statement.
+fn leading_loads_end(content: &str) -> usize {
+    let mut end = 0;
+    let mut offset = 0;
+    let mut in_load = false;
+    for line in content.split_inclusive('\n') {
+        offset += line.len();
+        let trimmed = line.trim();
+        if in_load || line.starts_with("load(") {
+            // buildifier wraps long loads, one argument per line
+            in_load = !trimmed.ends_with(')');
+            if !in_load {
+                end = offset;
+            }
+        } else if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            break;
+        }
+    }
+    end
+}
+
+/// Generates the BUILD file of one project in its package directory.
+fn generate_package(
+    project: &TuistProject,
+    index: &TargetIndex,
+    package_dir: &Path,
+    options: &GenerateOptions,
+    plist_template: Option<&str>,
+    buildifier: Option<&Path>,
+    progress: &Progress,
+) -> Result<Vec<PathBuf>> {
+    // The progress bar names the projects as they're done
+    if !progress.is_drawn() {
+        info!("Generating BUILD file for project: {}", project.name);
+    }
+    if !dry_run() {
+        fs::create_dir_all(package_dir).with_context(|| {
+            format!(
+                "Failed to create package directory {}",
+                package_dir.display()
+            )
+        })?;
+    }
+    let files = generate_build_file(project, index, package_dir, options, plist_template)
+        .with_context(|| format!("Project {}", project.name))?;
+    if let Some(buildifier) = buildifier {
+        format_starlark_files(buildifier, &files);
+    }
+    Ok(files)
+}
+
+/// The buildifier to format the generated files with, when
+/// `format_build_files` is set and it runs. Without one the files are left
+/// as catalyst writes them, with a warning.
+fn buildifier(options: &GenerateOptions) -> Option<&Path> {
+    if !options.format_build_files || dry_run() {
+        return None;
+    }
+    let buildifier = options.buildifier.as_path();
+    match Command::new(buildifier)
+        .arg("--version")
+        .logged()
+        .output_timed()
+    {
+        Ok(output) if output.status.success() => Some(buildifier),
+        Err(err)
+            if err
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound) =>
+        {
+            warning!(
+                "{} isn't installed or isn't on PATH, so the generated files aren't formatted; install buildifier or set buildifier in {}",
+                buildifier.display(),
+                Config::FILE_NAME
+            );
+            None
+        }
+        Ok(_) | Err(_) => {
+            warning!(
+                "{} --version failed, so the generated files aren't formatted",
+                buildifier.display()
+            );
+            None
+        }
+    }
+}
+
+/// Whether buildifier can tell the file's dialect of Starlark from its name.
+fn is_starlark_file(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    matches!(
+        name,
+        Some("BUILD" | "BUILD.bazel" | "WORKSPACE" | "WORKSPACE.bazel" | "MODULE.bazel")
+    ) || path.extension().is_some_and(|ext| ext == "bzl")
+}
+
+/// Formats the Starlark files among `files` in place. A file buildifier
+/// can't parse, like one with a broken `BUILD.catalyst-extra` snippet, is
+/// left as it is with a warning, since Bazel reports it better.
+fn format_starlark_files(buildifier: &Path, files: &[PathBuf]) {
+    let files: Vec<&PathBuf> = files.iter().filter(|path| is_starlark_file(path)).collect();
+    if files.is_empty() {
+        return;
+    }
+    match Command::new(buildifier)
+        .args(&files)
+        .logged()
+        .output_timed()
+    {
+        Ok(output) if output.status.success() => {}
+        Ok(output) => warning!(
+            "{} couldn't format some generated files:\n  {}",
+            buildifier.display(),
+            String::from_utf8_lossy(&output.stderr)
+                .trim()
+                .replace('\n', "\n  ")
+        ),
+        Err(err) => warning!("couldn't run {}: {:#}", buildifier.display(), err),
+    }
+}
+
+/// Appends the generated files, and Bazel's output links, to the project's
+/// .gitignore so they don't get committed by accident. Entries that are
+/// already listed are left alone.
+fn update_gitignore(project_dir: &Path, written: &[PathBuf]) -> Result<()> {
+    let gitignore_path = project_dir.join(".gitignore");
+    let existing = match fs::read_to_string(&gitignore_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => {
+            return Err(err)
+                .with_context(|| format!("Failed to read {}", gitignore_path.display()));
+        }
+    };
+    let listed: BTreeSet<&str> = existing.lines().map(str::trim).collect();
+
+    let mut entries = vec!["/bazel-*".to_string()];
+    entries.extend(written.iter().filter_map(|path| {
+        path.strip_prefix(project_dir)
+            .ok()
+            .map(|rel| format!("/{}", rel.display()))
+    }));
+    let missing: Vec<&String> = entries
+        .iter()
+        .filter(|entry| !listed.contains(entry.as_str()))
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let mut content = existing.clone();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    if !listed.contains(GITIGNORE_HEADER) {
+        content.push_str(&format!("\n{}\n", GITIGNORE_HEADER));
+    }
+    for entry in missing {
+        content.push_str(entry);
+        content.push('\n');
+    }
+
+    write_file(&gitignore_path, content)
+        .with_context(|| format!("Failed to write {}", gitignore_path.display()))?;
+    info!(
+        "Added the generated files to {} (pass --no-gitignore to skip)",
+        gitignore_path.display()
+    );
+
+    Ok(())
+}
+
+/// The directories the workspace's .bazelignore lists, relative to the
+/// workspace root. Comments and blank lines are skipped.
+fn read_bazelignore(workspace_dir: &Path) -> Result<Vec<String>> {
+    let path = workspace_dir.join(".bazelignore");
+    let content = match fs::read_to_string(&path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to read {}", path.display()));
+        }
+    };
+    Ok(content
+        .lines()
+        .map(|line| line.trim().trim_start_matches("./").trim_end_matches('/'))
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect())
+}
+
+/// Whether a package is one of the ignored directories or inside one.
+fn is_bazelignored(package: &str, ignored: &[String]) -> bool {
+    ignored.iter().any(|dir| {
+        package == dir
+            || package
+                .strip_prefix(dir.as_str())
+                .is_some_and(|rest| rest.starts_with('/'))
+    })
+}
+
+/// Appends the `BAZELIGNORE_DEFAULTS` that exist in the workspace to its
+/// .bazelignore, creating the file if needed. Directories that hold a Tuist
+/// project, like Tuist's package checkouts, stay visible to Bazel.
+fn update_bazelignore(
+    workspace_dir: &Path,
+    projects: &[TuistProject],
+    index: &TargetIndex,
+) -> Result<()> {
+    let listed = read_bazelignore(workspace_dir)?;
+    let packages: Vec<String> = projects
+        .iter()
+        .filter_map(|project| index.package(&project.path))
+        .collect();
+    let missing: Vec<&str> = BAZELIGNORE_DEFAULTS
+        .iter()
+        .copied()
+        .filter(|dir| workspace_dir.join(dir).is_dir())
+        .filter(|dir| !is_bazelignored(dir, &listed))
+        .filter(|dir| {
+            !packages
+                .iter()
+                .any(|package| is_bazelignored(package, &[dir.to_string()]))
+        })
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let path = workspace_dir.join(".bazelignore");
+    let mut content = fs::read_to_string(&path).unwrap_or_default();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for dir in &missing {
+        content.push_str(dir);
+        content.push('\n');
+    }
+    write_file(&path, content).with_context(|| format!("Failed to write {}", path.display()))?;
+    info!("Added {} to {}", missing.join(", "), path.display());
+
+    Ok(())
+}
@@ -0,0 +1,1532 @@
+//! The Tuist graph `tuist graph` writes, and the settings of its targets.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use crate::build_file::{
+    bundle_directory, bundle_import_name, glob_matches, resource_directory, ProvisioningProfile,
+    OBJC_EXTENSIONS,
+};
+use crate::config::{
+    BuildConfiguration, Config, DeviceFamily, GenerateOptions, Platform, TargetOverrides,
+    DEFAULT_MACOS_MINIMUM_OS_VERSION, DEFAULT_WATCHOS_MINIMUM_OS_VERSION,
+};
+use crate::error::CatalystError;
+use crate::index::PrebuiltKind;
+use crate::plist::ConfigurationValue;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TuistGraph {
+    pub name: String,
+    pub path: String,
+    pub projects: serde_json::Value, // Array with path string followed by project object
+    /// Version of the tuist CLI that produced the graph. Not part of Tuist's
+    /// output; catalyst records it in the cached graph for diagnostics.
+    #[serde(
+        rename = "catalystTuistVersion",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub tuist_version: Option<String>,
+}
+
+impl TuistGraph {
+    /// Parses the projects array of the graph. Tuist encodes the path-keyed
+    /// projects dictionary as `[path_string, project_object, ...]`, so each
+    /// project is paired with the path before it, and any other shape is an
+    /// error rather than something to skip past.
+    pub fn projects(&self) -> Result<Vec<TuistProject>> {
+        let (projects, failures) = self.parse_projects()?;
+        match failures.into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(projects),
+        }
+    }
+
+    /// Parses the projects that can be parsed, and returns the errors of the
+    /// others alongside them. Only a graph without the path/project pairs
+    /// fails as a whole.
+    pub(crate) fn parse_projects(&self) -> Result<(Vec<TuistProject>, Vec<anyhow::Error>)> {
+        let entries = self
+            .projects
+            .as_array()
+            .context("Unexpected Tuist graph: `projects` is not an array")?;
+        if entries.len() % 2 != 0 {
+            anyhow::bail!(
+                "Unexpected Tuist graph: `projects` has {} entries, expected path/project pairs",
+                entries.len()
+            );
+        }
+
+        let mut projects = Vec::new();
+        let mut failures = Vec::new();
+        for (index, pair) in entries.chunks(2).enumerate() {
+            let path = pair[0].as_str().with_context(|| {
+                format!(
+                    "Unexpected Tuist graph: entry {} of `projects` is not a project path",
+                    index * 2
+                )
+            })?;
+            if pair[1].is_string() {
+                anyhow::bail!(
+                    "Unexpected Tuist graph: `projects` lists paths without project definitions (first: {})",
+                    path
+                );
+            }
+            let project: TuistProject = match serde_json::from_value(pair[1].clone()) {
+                Ok(project) => project,
+                Err(source) => {
+                    failures.push(
+                        CatalystError::GraphParse {
+                            what: format!("project {} from graph", path),
+                            source,
+                        }
+                        .into(),
+                    );
+                    continue;
+                }
+            };
+
+            // Tuist may report either path relative to the workspace
+            let root = Path::new(&self.path);
+            if normalize_lexically(&root.join(&project.path))
+                != normalize_lexically(&root.join(path))
+            {
+                failures.push(anyhow::anyhow!(
+                    "Unexpected Tuist graph: project {} is listed under {} but its path is {}",
+                    project.name,
+                    path,
+                    project.path
+                ));
+                continue;
+            }
+            projects.push(project);
+        }
+
+        Ok((projects, failures))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TuistProject {
+    pub name: String,
+    pub path: String,
+    /// Keyed by target name. Ordered, so the rules in generated files come
+    /// out in the same order on every run
+    #[serde(default)]
+    pub targets: BTreeMap<String, TuistTarget>,
+    /// Settings shared by the targets; only their xcconfig files are used
+    #[serde(default)]
+    pub settings: TargetSettings,
+    /// The resource synthesizers Tuist generates accessors with, or `None`
+    /// in graphs that don't list them, where Tuist's defaults apply
+    #[serde(rename = "resourceSynthesizers", default)]
+    pub resource_synthesizers: Option<Vec<ResourceSynthesizer>>,
+}
+
+/// A Tuist resource synthesizer, which generates typed Swift accessors, like
+/// `Asset.logo`, for the resources of a target with the given extensions.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResourceSynthesizer {
+    /// `assets`, `strings`, `plists`, `fonts`, `coreData`, ...
+    pub parser: String,
+    #[serde(default)]
+    pub extensions: Vec<String>,
+}
+
+impl TuistProject {
+    /// Makes the paths of the project and its targets absolute, so that file
+    /// paths can be taken relative to the project path however Tuist
+    /// reported them. The project path is resolved against the workspace
+    /// root, the paths of its targets against the project path.
+    pub(crate) fn resolve_paths(&mut self, resolver: &PathResolver) {
+        self.path = resolver.resolve(&resolver.root, &self.path);
+        let base = PathBuf::from(&self.path);
+        let resolve = |path: &mut String| *path = resolver.resolve(&base, path);
+
+        for target in self.targets.values_mut() {
+            for folder in &mut target.buildable_folders {
+                resolve(&mut folder.path);
+                for file in &mut folder.resolved_files {
+                    resolve(&mut file.path);
+                }
+                for exception in &mut folder.exceptions.exceptions {
+                    exception.excluded.iter_mut().for_each(resolve);
+                }
+            }
+            for dep in &mut target.dependencies {
+                let paths = [&mut dep.xcframework, &mut dep.framework, &mut dep.library];
+                for reference in paths.into_iter().flatten() {
+                    resolve(&mut reference.path);
+                    if let Some(headers) = &mut reference.public_headers {
+                        resolve(headers);
+                    }
+                }
+                if let Some(reference) = &mut dep.project {
+                    resolve(&mut reference.path);
+                }
+            }
+            for file in [&mut target.info_plist, &mut target.entitlements]
+                .into_iter()
+                .flatten()
+            {
+                if let Some(serde_json::Value::String(path)) = file.pointer_mut("/file/path") {
+                    resolve(path);
+                }
+            }
+        }
+    }
+
+    /// The parsers and resource extensions of the project's resource
+    /// synthesizers.
+    pub(crate) fn resource_synthesizers(&self) -> Vec<(&str, Vec<&str>)> {
+        match &self.resource_synthesizers {
+            Some(synthesizers) => synthesizers
+                .iter()
+                .map(|synthesizer| {
+                    let extensions = synthesizer.extensions.iter().map(String::as_str).collect();
+                    (synthesizer.parser.as_str(), extensions)
+                })
+                .collect(),
+            None => DEFAULT_RESOURCE_SYNTHESIZERS
+                .iter()
+                .map(|(parser, extensions)| (*parser, extensions.to_vec()))
+                .collect(),
+        }
+    }
+
+    /// The accessors Tuist's resource synthesizers generated for the
+    /// project's targets, which `tuist generate` writes to
+    /// `Derived/Sources` and the graph doesn't list, sorted.
+    pub(crate) fn synthesized_sources(&self) -> Vec<PathBuf> {
+        let dir = Path::new(&self.path).join(TUIST_DERIVED_SOURCES);
+        let Ok(entries) = fs::read_dir(&dir) else {
+            return Vec::new();
+        };
+        let mut sources: Vec<PathBuf> = entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| synthesized_target(path).is_some())
+            .collect();
+        sources.sort();
+        sources
+    }
+}
+
+/// Where `tuist generate` writes a project's derived sources, relative to
+/// the project.
+pub(crate) const TUIST_DERIVED_SOURCES: &str = "Derived/Sources";
+
+/// Tuist's resource synthesizers for projects that don't declare their own.
+const DEFAULT_RESOURCE_SYNTHESIZERS: &[(&str, &[&str])] = &[
+    ("strings", &["strings", "stringsdict"]),
+    ("assets", &["xcassets"]),
+    ("plists", &["plist"]),
+    ("fonts", &["otf", "ttc", "ttf", "woff"]),
+];
+
+/// The target a synthesized accessor, `Tuist<Kind>+<Target>.swift`, was
+/// generated for, with the characters Tuist drops from target names
+/// stripped.
+pub(crate) fn synthesized_target(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    let (kind, target) = name.strip_suffix(".swift")?.split_once('+')?;
+    kind.starts_with("Tuist").then(|| synthesized_name(target))
+}
+
+/// `name` as it appears in the file names of synthesized accessors, for
+/// matching them to targets.
+pub(crate) fn synthesized_name(name: &str) -> String {
+    name.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// The kind of accessor the `parser` synthesizer generates, as in
+/// `TuistAssets+App.swift`.
+pub(crate) fn synthesized_kind(parser: &str) -> String {
+    match parser {
+        "json" | "yaml" => parser.to_uppercase(),
+        _ => {
+            let mut chars = parser.chars();
+            chars
+                .next()
+                .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                .unwrap_or_default()
+        }
+    }
+}
+
+/// The type the `parser` synthesizer's accessors for `module` are
+/// reached through in Tuist's templates, like `AppAsset.logo`, for the
+/// synthesizers that name one after the target.
+pub(crate) fn synthesized_symbol(parser: &str, module: &str) -> Option<String> {
+    let suffix = match parser {
+        "assets" => "Asset",
+        "strings" => "Strings",
+        "fonts" => "FontFamily",
+        _ => return None,
+    };
+    Some(format!("{}{}", module, suffix))
+}
+
+/// Resolves the paths of a Tuist graph to absolute ones under the workspace
+/// root. Paths that only lead into the root through a symlink, like a CI
+/// checkout reached through a different path than the one Tuist recorded,
+/// are rebased onto the root as reported, so they still share its prefix.
+/// So are paths into a directory that a symlink inside the root points at,
+/// like a submodule linked into the repository.
+pub(crate) struct PathResolver {
+    pub(crate) root: PathBuf,
+    canonical_root: Option<PathBuf>,
+    /// Symlinked directories under the root, with their canonical targets,
+    /// only looked for once a path falls outside the root
+    links: OnceLock<Vec<(PathBuf, PathBuf)>>,
+}
+
+/// How deep below the workspace root symlinked directories are looked for.
+const LINKED_DIRECTORY_MAX_DEPTH: usize = 4;
+
+impl PathResolver {
+    pub(crate) fn new(project_dir: &Path, graph_path: &str) -> Self {
+        let root = normalize_lexically(&project_dir.join(graph_path));
+        let canonical_root = root.canonicalize().ok();
+        Self {
+            root,
+            canonical_root,
+            links: OnceLock::new(),
+        }
+    }
+
+    pub(crate) fn resolve(&self, base: &Path, path: &str) -> String {
+        let path = normalize_lexically(&base.join(path));
+        if path.starts_with(&self.root) {
+            return path.display().to_string();
+        }
+        let canonical = canonicalize_existing(&path);
+        let rebased = self.canonical_root.as_ref().and_then(|canonical_root| {
+            let rest = canonical
+                .as_ref()?
+                .strip_prefix(canonical_root)
+                .ok()?
+                .to_path_buf();
+            Some(self.root.join(rest))
+        });
+        let linked = || {
+            let canonical = canonical.as_ref()?;
+            self.links()
+                .iter()
+                .find_map(|(link, target)| Some(link.join(canonical.strip_prefix(target).ok()?)))
+        };
+        rebased
+            .or_else(linked)
+            .unwrap_or(path)
+            .display()
+            .to_string()
+    }
+
+    fn links(&self) -> &[(PathBuf, PathBuf)] {
+        self.links.get_or_init(|| {
+            let mut links = Vec::new();
+            find_linked_directories(&self.root, 0, &mut links);
+            links
+        })
+    }
+}
+
+/// Collects the symlinks to directories below `dir`, with their canonical
+/// targets, without descending into them. Hidden directories and Bazel's
+/// output links are skipped.
+fn find_linked_directories(dir: &Path, depth: usize, links: &mut Vec<(PathBuf, PathBuf)>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name.starts_with("bazel-") {
+            continue;
+        }
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+        let path = entry.path();
+        if file_type.is_symlink() {
+            if let Some(target) = path.canonicalize().ok().filter(|target| target.is_dir()) {
+                links.push((path, target));
+            }
+        } else if file_type.is_dir() && depth < LINKED_DIRECTORY_MAX_DEPTH {
+            find_linked_directories(&path, depth + 1, links);
+        }
+    }
+}
+
+/// The closest directory that holds the project directory and every project
+/// of the graph, when some project lives outside the graph's root. `None`
+/// when they all live inside it, or when only the file system root would
+/// hold them all.
+pub(crate) fn detect_workspace_root(project_dir: &Path, graph: &TuistGraph) -> Option<PathBuf> {
+    let resolver = PathResolver::new(project_dir, &graph.path);
+    let outside: Vec<PathBuf> = graph
+        .projects
+        .as_array()?
+        .chunks(2)
+        .filter_map(|pair| pair[0].as_str())
+        .map(|path| PathBuf::from(resolver.resolve(&resolver.root, path)))
+        .filter(|path| !path.starts_with(&resolver.root))
+        .collect();
+    if outside.is_empty() {
+        return None;
+    }
+
+    let mut root = normalize_lexically(project_dir);
+    for path in outside.iter().chain([&resolver.root]) {
+        while !path.starts_with(&root) {
+            if !root.pop() {
+                return None;
+            }
+        }
+    }
+    root.parent().is_some().then_some(root)
+}
+
+/// Makes `path` absolute and drops its `.` and `..` components, without
+/// resolving symlinks.
+pub(crate) fn normalize_lexically(path: &Path) -> PathBuf {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            std::path::Component::CurDir => {}
+            std::path::Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// Canonicalizes the longest existing ancestor of `path` and appends the
+/// rest, so paths of files that don't exist resolve too.
+fn canonicalize_existing(path: &Path) -> Option<PathBuf> {
+    let existing = path.ancestors().find(|ancestor| ancestor.exists())?;
+    let rest = path.strip_prefix(existing).ok()?;
+    Some(existing.canonicalize().ok()?.join(rest))
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TuistTarget {
+    pub name: String,
+    pub product: String,
+    #[serde(rename = "bundleId")]
+    pub bundle_id: String,
+    #[serde(rename = "buildableFolders", default)]
+    pub buildable_folders: Vec<BuildableFolder>,
+    #[serde(default)]
+    pub dependencies: Vec<TuistDependency>,
+    #[serde(rename = "deploymentTargets", default)]
+    pub deployment_targets: DeploymentTargets,
+    /// Devices the target runs on, e.g. `iPhone`, `appleWatch`, or `mac`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub destinations: Vec<String>,
+    #[serde(rename = "infoPlist", default, skip_serializing_if = "Option::is_none")]
+    pub info_plist: Option<serde_json::Value>,
+    #[serde(default)]
+    pub settings: TargetSettings,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entitlements: Option<serde_json::Value>,
+    /// Name of the built product, when the manifest sets one apart from the
+    /// target name
+    #[serde(
+        rename = "productName",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub product_name: Option<String>,
+}
+
+impl TuistTarget {
+    /// The platform the target is built for: watchOS or macOS when its
+    /// destinations (or, lacking those, its deployment targets) only name
+    /// that platform, iOS otherwise. Mac Catalyst and "Designed for iPad"
+    /// destinations are iOS builds.
+    pub fn platform(&self) -> Platform {
+        let has = |destination: &str| self.destinations.iter().any(|d| d == destination);
+        let targets = &self.deployment_targets;
+        if has("appleWatch") {
+            Platform::Watchos
+        } else if has("mac") && !has("iPhone") && !has("iPad") {
+            Platform::Macos
+        } else if !self.destinations.is_empty() || targets.ios.is_some() {
+            Platform::Ios
+        } else if targets.watchos.is_some() {
+            Platform::Watchos
+        } else if targets.macos.is_some() {
+            Platform::Macos
+        } else {
+            Platform::Ios
+        }
+    }
+
+    /// The deployment target for the target's platform declared in the graph.
+    /// iOS targets fall back to `ios_fallback`, other platforms to catalyst's
+    /// defaults for them.
+    pub(crate) fn minimum_os_version<'a>(&'a self, ios_fallback: &'a str) -> &'a str {
+        let targets = &self.deployment_targets;
+        match self.platform() {
+            Platform::Ios => targets.ios.as_deref().unwrap_or(ios_fallback),
+            Platform::Watchos => targets
+                .watchos
+                .as_deref()
+                .unwrap_or(DEFAULT_WATCHOS_MINIMUM_OS_VERSION),
+            Platform::Macos => targets
+                .macos
+                .as_deref()
+                .unwrap_or(DEFAULT_MACOS_MINIMUM_OS_VERSION),
+        }
+    }
+
+    /// Adds the settings of the configuration's xcconfig files, the project's
+    /// under the target's, for the keys the graph doesn't set. The deployment
+    /// target and a `$(PRODUCT_BUNDLE_IDENTIFIER)` bundle ID are filled in
+    /// from them too. The files read, includes too, are added to `read`.
+    pub(crate) fn apply_xcconfigs(
+        &mut self,
+        project_settings: &TargetSettings,
+        variant: &str,
+        project_path: &Path,
+        read: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        let mut xcconfig_settings = HashMap::new();
+        for xcconfig in [project_settings, &self.settings]
+            .into_iter()
+            .filter_map(|settings| settings.xcconfig(variant, project_path))
+        {
+            if !xcconfig.is_file() {
+                warning!(
+                    "xcconfig {} of {} doesn't exist; ignoring it",
+                    xcconfig.display(),
+                    self.name
+                );
+                continue;
+            }
+            read_xcconfig(&xcconfig, &mut xcconfig_settings, read, 0)?;
+        }
+        if xcconfig_settings.is_empty() {
+            return Ok(());
+        }
+
+        let platform = self.platform();
+        for (key, value) in xcconfig_settings {
+            self.settings.base.entry(key).or_insert(value);
+        }
+        let (key, slot) = match platform {
+            Platform::Ios => (
+                "IPHONEOS_DEPLOYMENT_TARGET",
+                &mut self.deployment_targets.ios,
+            ),
+            Platform::Watchos => (
+                "WATCHOS_DEPLOYMENT_TARGET",
+                &mut self.deployment_targets.watchos,
+            ),
+            Platform::Macos => (
+                "MACOSX_DEPLOYMENT_TARGET",
+                &mut self.deployment_targets.macos,
+            ),
+        };
+        if slot.is_none() {
+            *slot = self
+                .settings
+                .base
+                .get(key)
+                .and_then(SettingValue::as_string);
+        }
+        if self.bundle_id.is_empty() || self.bundle_id.contains("$(PRODUCT_BUNDLE_IDENTIFIER)") {
+            if let Some(bundle_id) = self
+                .settings
+                .base
+                .get("PRODUCT_BUNDLE_IDENTIFIER")
+                .and_then(SettingValue::as_string)
+            {
+                self.bundle_id = if self.bundle_id.is_empty() {
+                    bundle_id
+                } else {
+                    self.bundle_id
+                        .replace("$(PRODUCT_BUNDLE_IDENTIFIER)", &bundle_id)
+                };
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces what the graph says about the target with the values of its
+    /// `[targets.<name>]` table in `catalyst.toml`. The deployment target
+    /// applies to the target's own platform, and `infoplist` is resolved
+    /// against `project_path`.
+    pub(crate) fn apply_overrides(
+        &mut self,
+        overrides: &TargetOverrides,
+        project_path: &Path,
+    ) -> Result<()> {
+        if let Some(bundle_id) = &overrides.bundle_id {
+            // The override holds for every configuration
+            self.bundle_id = bundle_id.clone();
+            self.settings
+                .remove_configuration_setting("PRODUCT_BUNDLE_IDENTIFIER");
+        }
+        if let Some(product_name) = &overrides.product_name {
+            self.product_name = Some(product_name.clone());
+            self.settings.base.remove("PRODUCT_NAME");
+        }
+        if let Some(version) = &overrides.minimum_os_version {
+            let platform = self.platform();
+            let targets = &mut self.deployment_targets;
+            let slot = match platform {
+                Platform::Ios => &mut targets.ios,
+                Platform::Watchos => &mut targets.watchos,
+                Platform::Macos => &mut targets.macos,
+            };
+            *slot = Some(version.clone());
+        }
+        if let Some(families) = &overrides.families {
+            let value: Vec<&str> = families.iter().map(|family| family.setting()).collect();
+            self.settings.base.insert(
+                "TARGETED_DEVICE_FAMILY".to_string(),
+                SettingValue::String(value.join(",")),
+            );
+        }
+        if let Some(infoplist) = &overrides.infoplist {
+            let path = project_path.join(infoplist);
+            if !path.is_file() {
+                anyhow::bail!(
+                    "{} sets the Info.plist of {} to {}, which doesn't exist",
+                    Config::FILE_NAME,
+                    self.name,
+                    path.display()
+                );
+            }
+            self.info_plist = Some(serde_json::json!({ "file": { "path": path } }));
+        }
+        if let Some(profile) = &overrides.provisioning_profile {
+            let path = project_path.join(profile);
+            if !path.is_file() {
+                anyhow::bail!(
+                    "{} sets the provisioning profile of {} to {}, which doesn't exist",
+                    Config::FILE_NAME,
+                    self.name,
+                    path.display()
+                );
+            }
+            // Bazel labels can't leave their package, so neither may the
+            // profile, not even through `..` halfway along
+            let depth = profile
+                .components()
+                .try_fold(0usize, |depth, component| match component {
+                    std::path::Component::Normal(_) => Some(depth + 1),
+                    std::path::Component::CurDir => Some(depth),
+                    std::path::Component::ParentDir => depth.checked_sub(1),
+                    _ => None,
+                });
+            if depth.is_none() {
+                anyhow::bail!(
+                    "{} sets the provisioning profile of {} to {}, which is outside the project directory {}",
+                    Config::FILE_NAME,
+                    self.name,
+                    profile.display(),
+                    project_path.display()
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The module name from PRODUCT_MODULE_NAME, or the target name like
+    /// Xcode's default, before it's made a valid Swift identifier.
+    pub(crate) fn declared_module_name(&self) -> &str {
+        match self.setting("PRODUCT_MODULE_NAME") {
+            Some(SettingValue::String(name)) if !name.is_empty() => name,
+            _ => &self.name,
+        }
+    }
+
+    /// The name of the bundle and its executable, for `bundle_name` and the
+    /// CFBundleName of synthesized Info.plists: PRODUCT_NAME, the graph's
+    /// product name, or the target name. The module name stays separate.
+    pub(crate) fn product_name(&self) -> &str {
+        match self.setting("PRODUCT_NAME") {
+            Some(SettingValue::String(name)) if !name.is_empty() && !name.contains("$(") => name,
+            _ => self
+                .product_name
+                .as_deref()
+                .filter(|name| !name.is_empty())
+                .unwrap_or(&self.name),
+        }
+    }
+
+    /// Looks up a build setting from the target's base settings.
+    fn setting(&self, key: &str) -> Option<&SettingValue> {
+        self.settings.base.get(key)
+    }
+
+    /// The Info.plist file the Tuist manifest points at, if any. Tuist encodes
+    /// `infoPlist: .file(path:)` as `{"file": {"path": ...}}`; an explicit
+    /// `INFOPLIST_FILE` build setting is honored as well.
+    pub(crate) fn info_plist_file(&self, project_path: &Path) -> Option<PathBuf> {
+        let from_manifest = self
+            .info_plist
+            .as_ref()
+            .and_then(|plist| plist.get("file"))
+            .and_then(|file| file.get("path"))
+            .and_then(|path| path.as_str())
+            .map(PathBuf::from);
+
+        let from_settings = self
+            .setting("INFOPLIST_FILE")
+            .and_then(SettingValue::as_string)
+            .map(|value| {
+                let value = value.trim_start_matches("$(SRCROOT)/");
+                project_path.join(value)
+            });
+
+        from_manifest.or(from_settings)
+    }
+
+    /// Linker flags for the SDK frameworks and libraries the target depends
+    /// on. Swift autolinks the frameworks it imports, but Objective-C code
+    /// and the libraries it uses need them spelled out. The ones the
+    /// manifest marks `optional` are linked weakly, so the binary still loads
+    /// on OS versions without them.
+    pub(crate) fn sdk_linkopts(&self) -> Vec<String> {
+        self.sdk_dependencies()
+            .flat_map(SdkReference::linkopts)
+            .collect()
+    }
+
+    /// The SDK frameworks and libraries among the target's dependencies.
+    pub(crate) fn sdk_dependencies(&self) -> impl Iterator<Item = &SdkReference> {
+        self.dependencies.iter().filter_map(|dep| dep.sdk.as_ref())
+    }
+
+    /// SWIFT_VERSION, the active compilation conditions as `-D` flags, and
+    /// OTHER_SWIFT_FLAGS. Settings of the configuration override the base
+    /// settings, and Debug builds define DEBUG the way Tuist's default
+    /// settings do.
+    pub(crate) fn swift_copts(&self, configuration: BuildConfiguration) -> Vec<String> {
+        let lookup = |key: &str| self.setting_flags(configuration, key);
+
+        let mut conditions = lookup("SWIFT_ACTIVE_COMPILATION_CONDITIONS");
+        if configuration == BuildConfiguration::Debug && !conditions.iter().any(|c| c == "DEBUG") {
+            conditions.insert(0, "DEBUG".to_string());
+        }
+
+        // Without SWIFT_VERSION the toolchain's default language mode applies
+        let mut copts: Vec<String> = lookup("SWIFT_VERSION")
+            .first()
+            .map(|version| {
+                // swiftc takes `5` and `6` but not Xcode's `5.0` and `6.0`
+                let version = version.strip_suffix(".0").unwrap_or(version);
+                vec!["-swift-version".to_string(), version.to_string()]
+            })
+            .unwrap_or_default();
+        copts.extend(conditions.iter().map(|c| format!("-D{}", c)));
+        copts.extend(lookup("OTHER_SWIFT_FLAGS"));
+        copts
+    }
+
+    /// The flags of a list setting like OTHER_SWIFT_FLAGS, from the
+    /// configuration's settings or else the base settings, without
+    /// `$(inherited)`.
+    fn setting_flags(&self, configuration: BuildConfiguration, key: &str) -> Vec<String> {
+        let configured = self
+            .settings
+            .configuration(configuration.bazelrc_config())
+            .unwrap_or_default();
+        configured
+            .get(key)
+            .or_else(|| self.setting(key))
+            .and_then(SettingValue::as_string)
+            .map(|value| {
+                value
+                    .split_whitespace()
+                    .filter(|flag| *flag != "$(inherited)")
+                    .map(str::to_string)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// OTHER_LDFLAGS as linkopts, like `-ObjC` and `-all_load`, which load
+    /// the Objective-C categories of static libraries, or `-framework UIKit`.
+    /// Flags referencing other build settings, which Bazel can't expand, are
+    /// returned separately, along with the value of a flag like `-framework`
+    /// they'd leave dangling.
+    pub(crate) fn linker_flags(
+        &self,
+        configuration: BuildConfiguration,
+    ) -> (Vec<String>, Vec<String>) {
+        let mut linkopts = Vec::new();
+        let mut unsupported = Vec::new();
+        let mut flags = self
+            .setting_flags(configuration, "OTHER_LDFLAGS")
+            .into_iter();
+        while let Some(flag) = flags.next() {
+            // `-framework X` and the like take their value as the next flag
+            let value = matches!(
+                flag.as_str(),
+                "-framework" | "-weak_framework" | "-force_load" | "-Xlinker"
+            )
+            .then(|| flags.next())
+            .flatten();
+            let flag_and_value: Vec<String> = std::iter::once(flag).chain(value).collect();
+            if flag_and_value.iter().any(|flag| flag.contains("$(")) {
+                unsupported.push(flag_and_value.join(" "));
+            } else {
+                linkopts.extend(flag_and_value);
+            }
+        }
+        (linkopts, unsupported)
+    }
+
+    /// The entitlements file the Tuist manifest points at, either through
+    /// `entitlements: .file(path:)` or the `CODE_SIGN_ENTITLEMENTS` setting.
+    pub(crate) fn entitlements_file(&self, project_path: &Path) -> Option<PathBuf> {
+        let from_manifest = self
+            .entitlements
+            .as_ref()
+            .and_then(|entitlements| entitlements.get("file"))
+            .and_then(|file| file.get("path"))
+            .and_then(|path| path.as_str())
+            .map(PathBuf::from);
+
+        let from_settings = self
+            .setting("CODE_SIGN_ENTITLEMENTS")
+            .and_then(SettingValue::as_string)
+            .map(|value| {
+                let value = value.trim_start_matches("$(SRCROOT)/");
+                project_path.join(value)
+            });
+
+        from_manifest.or(from_settings)
+    }
+
+    /// The device families from `TARGETED_DEVICE_FAMILY` (`1` is iPhone, `2`
+    /// is iPad, `1,2` both), or `default` when the setting is absent.
+    pub(crate) fn families(&self, default: &[DeviceFamily]) -> Vec<DeviceFamily> {
+        let families: Vec<DeviceFamily> = self
+            .setting("TARGETED_DEVICE_FAMILY")
+            .and_then(SettingValue::as_string)
+            .map(|value| {
+                value
+                    .split(',')
+                    .filter_map(DeviceFamily::from_setting)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        if families.is_empty() {
+            default.to_vec()
+        } else {
+            families
+        }
+    }
+
+    /// The Objective-C bridging header named by `SWIFT_OBJC_BRIDGING_HEADER`.
+    pub(crate) fn bridging_header(&self, project_path: &Path) -> Option<PathBuf> {
+        self.setting("SWIFT_OBJC_BRIDGING_HEADER")
+            .and_then(SettingValue::as_string)
+            .map(|value| project_path.join(value.trim_start_matches("$(SRCROOT)/")))
+    }
+
+    /// Whether the buildable folders contain Objective-C sources or headers,
+    /// which get an `objc_library` next to the target's swift_library.
+    pub(crate) fn has_objc_sources(&self) -> bool {
+        self.buildable_folders
+            .iter()
+            .flat_map(BuildableFolder::included_files)
+            .any(|file| {
+                Path::new(&file.path)
+                    .extension()
+                    .is_some_and(|ext| OBJC_EXTENSIONS.contains(&ext.to_string_lossy().as_ref()))
+            })
+    }
+
+    /// Capabilities declared inline with `entitlements: .dictionary(...)`.
+    pub(crate) fn entitlements_dictionary(
+        &self,
+    ) -> Option<&serde_json::Map<String, serde_json::Value>> {
+        self.entitlements
+            .as_ref()?
+            .get("dictionary")?
+            .as_object()
+            .filter(|entries| !entries.is_empty())
+    }
+
+    /// Names of the Bazel rules generated for this target. Keep in sync with
+    /// the product handling in `generate_build_file`.
+    pub(crate) fn rule_names(&self) -> Vec<String> {
+        let name = self.name.to_lowercase();
+        let mut names = match self.product.as_str() {
+            // Bundles signed with an installed profile get a
+            // local_provisioning_profile rule
+            "app" | "app_extension" => vec![
+                format!("{}_lib", name),
+                format!("{}_profile", name),
+                name.clone(),
+            ],
+            "unit_tests" | "ui_tests" => vec![format!("{}_lib", name), name.clone()],
+            "framework" | "static_library" | "static_framework" => {
+                vec![format!("{}_framework", name), name.clone()]
+            }
+            _ => vec![name.clone()],
+        };
+        if self.has_objc_sources() {
+            names.push(format!("{}_objc", name));
+        }
+        let bundles: BTreeSet<PathBuf> = self
+            .buildable_folders
+            .iter()
+            .flat_map(|folder| &folder.resolved_files)
+            .filter_map(|file| bundle_directory(Path::new(&file.path)))
+            .collect();
+        names.extend(
+            bundles
+                .iter()
+                .map(|bundle| bundle_import_name(&name, bundle)),
+        );
+        names
+    }
+
+    /// An entry of the `extendingDefault` dictionary of the Tuist Info.plist.
+    pub(crate) fn info_plist_entry(&self, key: &str) -> Option<&serde_json::Value> {
+        self.info_plist
+            .as_ref()?
+            .get("extendingDefault")?
+            .get("with")?
+            .get(key)
+    }
+
+    /// A string value from the `extendingDefault` dictionary of the Tuist Info.plist.
+    fn info_plist_value(&self, key: &str) -> Option<String> {
+        let value = self.info_plist_entry(key)?;
+        // Plist values are encoded either bare or tagged like {"string": "1.0"}
+        value
+            .as_str()
+            .or_else(|| value.get("string").and_then(|v| v.as_str()))
+            .map(str::to_string)
+    }
+
+    /// CFBundleShortVersionString, from MARKETING_VERSION or the Info.plist.
+    pub(crate) fn marketing_version(&self) -> Option<String> {
+        self.setting("MARKETING_VERSION")
+            .and_then(SettingValue::as_string)
+            .or_else(|| self.info_plist_value("CFBundleShortVersionString"))
+    }
+
+    /// CFBundleVersion, from CURRENT_PROJECT_VERSION or the Info.plist.
+    pub(crate) fn build_version(&self) -> Option<String> {
+        self.setting("CURRENT_PROJECT_VERSION")
+            .and_then(SettingValue::as_string)
+            .or_else(|| self.info_plist_value("CFBundleVersion"))
+    }
+
+    /// The bundle ID in the configuration with the given variant: the
+    /// PRODUCT_BUNDLE_IDENTIFIER it sets, or the graph's bundle ID.
+    pub(crate) fn configuration_bundle_id(&self, variant: &str) -> String {
+        self.settings
+            .configuration(variant)
+            .and_then(|settings| settings.get("PRODUCT_BUNDLE_IDENTIFIER")?.as_string())
+            .filter(|bundle_id| !bundle_id.is_empty() && !bundle_id.contains("$("))
+            .unwrap_or_else(|| self.bundle_id.clone())
+    }
+
+    /// The bundle ID of each configuration, for the `bundle_id` attribute.
+    pub(crate) fn bundle_id_value(&self) -> ConfigurationValue {
+        ConfigurationValue::new(
+            self.configuration_bundle_id("debug"),
+            self.configuration_bundle_id("release"),
+        )
+    }
+
+    /// CFBundleDisplayName in the configuration with the given variant, from
+    /// INFOPLIST_KEY_CFBundleDisplayName or the Info.plist.
+    pub(crate) fn display_name(&self, variant: &str) -> Option<String> {
+        let key = "INFOPLIST_KEY_CFBundleDisplayName";
+        self.settings
+            .configuration(variant)
+            .and_then(|settings| settings.get(key)?.as_string())
+            .or_else(|| self.setting(key).and_then(SettingValue::as_string))
+            .or_else(|| self.info_plist_value("CFBundleDisplayName"))
+    }
+
+    /// Whether the synthesized Info.plist differs between Debug and Release,
+    /// so one has to be generated per configuration.
+    pub(crate) fn has_configuration_plists(&self) -> bool {
+        self.bundle_id_value().is_split()
+            || self.display_name("debug") != self.display_name("release")
+    }
+
+    /// The Swift and Objective-C files of the target that end up in its
+    /// rules, leaving out the ones the manifest or `catalyst.toml` exclude.
+    pub(crate) fn source_file_count(
+        &self,
+        project: &TuistProject,
+        options: &GenerateOptions,
+    ) -> usize {
+        let excluded = options
+            .exclude
+            .get(&self.name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        self.buildable_folders
+            .iter()
+            .flat_map(BuildableFolder::included_files)
+            .filter_map(|file| Path::new(&file.path).strip_prefix(&project.path).ok())
+            .filter(|path| {
+                path.extension().is_some_and(|ext| {
+                    ext == "swift" || OBJC_EXTENSIONS.iter().any(|objc| ext == *objc)
+                })
+            })
+            .filter(|path| !excluded.iter().any(|pattern| glob_matches(pattern, path)))
+            .count()
+    }
+
+    /// The launch storyboard named by the Info.plist or the
+    /// `INFOPLIST_KEY_UILaunchStoryboardName` setting, `LaunchScreen` like
+    /// Tuist's default Info.plist otherwise, if the target has a storyboard
+    /// or xib of that name, loose or in an `.lproj` directory.
+    pub(crate) fn launch_storyboard(&self) -> Option<String> {
+        let name = self
+            .info_plist_value("UILaunchStoryboardName")
+            .or_else(|| {
+                self.setting("INFOPLIST_KEY_UILaunchStoryboardName")
+                    .and_then(SettingValue::as_string)
+            })
+            .unwrap_or_else(|| "LaunchScreen".to_string());
+        let file_names = [format!("{}.storyboard", name), format!("{}.xib", name)];
+
+        let found = self
+            .buildable_folders
+            .iter()
+            .flat_map(BuildableFolder::included_files)
+            .any(|file| {
+                let path = Path::new(&file.path);
+                let is_named = path
+                    .file_name()
+                    .is_some_and(|file_name| file_names.iter().any(|n| file_name == n.as_str()));
+                let in_lproj = path.extension().is_some_and(|ext| ext == "lproj")
+                    && file_names.iter().any(|n| path.join(n).is_file());
+                is_named || in_lproj
+            });
+        found.then_some(name)
+    }
+
+    /// The provisioning profile the bundle is signed with on devices: the file
+    /// `provisioning_profile` in `catalyst.toml` names, or else the installed
+    /// profile PROVISIONING_PROFILE_SPECIFIER names, of DEVELOPMENT_TEAM.
+    pub(crate) fn provisioning_profile(
+        &self,
+        options: &GenerateOptions,
+    ) -> Option<ProvisioningProfile> {
+        if let Some(path) = options
+            .targets
+            .get(&self.name)
+            .and_then(|overrides| overrides.provisioning_profile.as_ref())
+        {
+            return Some(ProvisioningProfile::File(path.clone()));
+        }
+
+        // Xcode projects often only set it for device builds
+        let device_setting = |key: &str| {
+            [key.to_string(), format!("{}[sdk=iphoneos*]", key)]
+                .iter()
+                .find_map(|key| self.setting(key).and_then(SettingValue::as_string))
+                .filter(|value| !value.is_empty())
+        };
+        let name = device_setting("PROVISIONING_PROFILE_SPECIFIER")?;
+        Some(ProvisioningProfile::Local {
+            name,
+            team_id: device_setting("DEVELOPMENT_TEAM"),
+        })
+    }
+
+    /// The app icon set of ASSETCATALOG_COMPILER_APPICON_NAME (`AppIcon` by
+    /// default) in one of the target's asset catalogs, relative to
+    /// `project_path`.
+    pub(crate) fn app_icon_set(&self, project_path: &Path) -> Option<PathBuf> {
+        let name = self
+            .setting("ASSETCATALOG_COMPILER_APPICON_NAME")
+            .and_then(SettingValue::as_string)
+            .unwrap_or_else(|| "AppIcon".to_string());
+
+        self.buildable_folders
+            .iter()
+            .flat_map(BuildableFolder::included_files)
+            .filter_map(|file| {
+                let rel_path = Path::new(&file.path).strip_prefix(project_path).ok()?;
+                resource_directory(rel_path)
+            })
+            .filter(|dir| dir.extension().is_some_and(|ext| ext == "xcassets"))
+            .map(|catalog| catalog.join(format!("{}.appiconset", name)))
+            .find(|icon_set| project_path.join(icon_set).is_dir())
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct TargetSettings {
+    #[serde(default)]
+    pub base: HashMap<String, SettingValue>,
+    /// Per-configuration settings. Tuist keys them by build configuration, so
+    /// like `projects` they come as `[configuration, {"settings": ...}, ...]`.
+    #[serde(default, skip_serializing_if = "serde_json::Value::is_null")]
+    pub configurations: serde_json::Value,
+}
+
+impl TargetSettings {
+    /// The entry of the configuration with the given variant (`debug` or
+    /// `release`), e.g. `{"settings": {...}, "xcconfig": "..."}`.
+    fn configuration_entry(&self, variant: &str) -> Option<&serde_json::Value> {
+        let entries = self.configurations.as_array()?;
+        entries.chunks(2).find_map(|pair| {
+            let [configuration, entry] = pair else {
+                return None;
+            };
+            (configuration.get("variant")?.as_str()? == variant).then_some(entry)
+        })
+    }
+
+    /// The settings of the configuration with the given variant, if the
+    /// target declares any.
+    fn configuration(&self, variant: &str) -> Option<HashMap<String, SettingValue>> {
+        let settings = self.configuration_entry(variant)?.get("settings")?;
+        serde_json::from_value(settings.clone()).ok()
+    }
+
+    /// Removes `key` from the settings of every configuration.
+    fn remove_configuration_setting(&mut self, key: &str) {
+        let Some(entries) = self.configurations.as_array_mut() else {
+            return;
+        };
+        for entry in entries.iter_mut().skip(1).step_by(2) {
+            if let Some(settings) = entry
+                .get_mut("settings")
+                .and_then(serde_json::Value::as_object_mut)
+            {
+                settings.remove(key);
+            }
+        }
+    }
+
+    /// The xcconfig file of the configuration with the given variant,
+    /// resolved against `project_path`.
+    fn xcconfig(&self, variant: &str, project_path: &Path) -> Option<PathBuf> {
+        let path = self
+            .configuration_entry(variant)?
+            .get("xcconfig")?
+            .as_str()?;
+        Some(project_path.join(path))
+    }
+}
+
+/// How deep `#include`s in xcconfig files may nest, which stops include
+/// cycles.
+const XCCONFIG_MAX_INCLUDE_DEPTH: usize = 16;
+
+/// Reads the settings of an xcconfig file into `settings`, in file order, so
+/// later assignments win. `#include "other.xcconfig"` is read in place,
+/// relative to the including file; `#include?` skips a missing file.
+/// Conditional settings keep their condition in the key, like
+/// `PROVISIONING_PROFILE_SPECIFIER[sdk=iphoneos*]`.
+fn read_xcconfig(
+    path: &Path,
+    settings: &mut HashMap<String, SettingValue>,
+    read: &mut Vec<PathBuf>,
+    depth: usize,
+) -> Result<()> {
+    if depth > XCCONFIG_MAX_INCLUDE_DEPTH {
+        anyhow::bail!(
+            "{} is included more than {} levels deep; do the xcconfig files include each other?",
+            path.display(),
+            XCCONFIG_MAX_INCLUDE_DEPTH
+        );
+    }
+    let content =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    read.push(path.to_path_buf());
+
+    for line in content.lines() {
+        let line = line.split("//").next().unwrap_or_default().trim();
+        if let Some(include) = line.strip_prefix("#include") {
+            let (optional, include) = match include.strip_prefix('?') {
+                Some(include) => (true, include),
+                None => (false, include),
+            };
+            let include = include.trim().trim_matches('"');
+            let include = path.parent().unwrap_or(Path::new("")).join(include);
+            if optional && !include.is_file() {
+                continue;
+            }
+            read_xcconfig(&include, settings, read, depth + 1)
+                .with_context(|| format!("Failed to read the includes of {}", path.display()))?;
+        } else if let Some((key, value)) = line.split_once('=') {
+            let key: String = key.split_whitespace().collect();
+            if !key.is_empty() {
+                settings.insert(key, SettingValue::String(value.trim().to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A build setting value. Tuist emits either a string or a list of strings;
+/// anything else is preserved but ignored.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum SettingValue {
+    String(String),
+    Array(Vec<String>),
+    Other(serde_json::Value),
+}
+
+impl SettingValue {
+    /// The value as a single string, joining list values with spaces.
+    fn as_string(&self) -> Option<String> {
+        match self {
+            SettingValue::String(value) => Some(value.clone()),
+            SettingValue::Array(values) => Some(values.join(" ")),
+            SettingValue::Other(_) => None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DeploymentTargets {
+    #[serde(rename = "iOS", default, skip_serializing_if = "Option::is_none")]
+    pub ios: Option<String>,
+    #[serde(rename = "watchOS", default, skip_serializing_if = "Option::is_none")]
+    pub watchos: Option<String>,
+    #[serde(rename = "macOS", default, skip_serializing_if = "Option::is_none")]
+    pub macos: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BuildableFolder {
+    pub path: String,
+    #[serde(default)]
+    pub exceptions: BuildableFolderExceptions,
+    #[serde(rename = "resolvedFiles", default)]
+    pub resolved_files: Vec<ResolvedFile>,
+}
+
+impl BuildableFolder {
+    /// Whether the manifest excludes `path` from the folder's target, directly
+    /// or through an excluded directory.
+    pub(crate) fn excludes(&self, path: &str) -> bool {
+        self.exceptions
+            .exceptions
+            .iter()
+            .flat_map(|exception| &exception.excluded)
+            .any(|excluded| Path::new(path).starts_with(excluded))
+    }
+
+    /// The resolved files the manifest doesn't exclude.
+    pub(crate) fn included_files(&self) -> impl Iterator<Item = &ResolvedFile> {
+        self.resolved_files
+            .iter()
+            .filter(|file| !self.excludes(&file.path))
+    }
+}
+
+/// The `exceptions:` of a buildable folder in the manifest.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct BuildableFolderExceptions {
+    #[serde(default)]
+    pub exceptions: Vec<BuildableFolderException>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BuildableFolderException {
+    /// Absolute paths of the files and directories left out of the target
+    #[serde(default)]
+    pub excluded: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ResolvedFile {
+    pub path: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TuistDependency {
+    pub target: Option<TargetReference>,
+    /// A target that lives in another project of the workspace
+    pub project: Option<ProjectTargetReference>,
+    /// A product of a Swift package resolved by Tuist
+    pub package: Option<PackageReference>,
+    pub xcframework: Option<PathReference>,
+    pub framework: Option<PathReference>,
+    pub library: Option<PathReference>,
+    /// A framework or library of the SDK, e.g. `UIKit.framework`
+    pub sdk: Option<SdkReference>,
+}
+
+impl TuistDependency {
+    /// Label of a Swift package product, following the repository naming of
+    /// rules_swift_package_manager (`@swiftpkg_<identity>//:<product>`). The
+    /// graph doesn't record the package identity, so the product name stands
+    /// in for it, which holds for most single-product packages.
+    pub(crate) fn package_label(&self) -> Option<String> {
+        self.package.as_ref().map(|package| {
+            let identity: String = package
+                .product
+                .chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() {
+                        c.to_ascii_lowercase()
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            format!("@swiftpkg_{}//:{}", identity, package.product)
+        })
+    }
+
+    /// The kind and reference of a prebuilt `xcframework`, `framework`, or
+    /// `library` dependency, which catalyst imports with an import rule.
+    pub(crate) fn prebuilt(&self) -> Option<(PrebuiltKind, &PathReference)> {
+        [
+            (PrebuiltKind::Xcframework, &self.xcframework),
+            (PrebuiltKind::Framework, &self.framework),
+            (PrebuiltKind::Library, &self.library),
+        ]
+        .into_iter()
+        .find_map(|(kind, dep)| Some((kind, dep.as_ref()?)))
+    }
+
+    /// Whether the manifest says not to link the target, with Tuist's
+    /// `status: .none`. `.optional` only links it weakly, so the code still
+    /// needs it.
+    pub(crate) fn is_unlinked(&self) -> bool {
+        let status = self
+            .target
+            .as_ref()
+            .map(|t| t.status.as_str())
+            .or_else(|| self.project.as_ref().map(|p| p.status.as_str()));
+        status == Some("none")
+    }
+
+    /// The name of the depended-on target, for target and project dependencies.
+    pub(crate) fn target_name(&self) -> Option<&str> {
+        self.target
+            .as_ref()
+            .map(|t| t.name.as_str())
+            .or_else(|| self.project.as_ref().map(|p| p.target.as_str()))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct TargetReference {
+    pub name: String,
+    /// `required`, `optional` for a weakly linked target, or `none` for one
+    /// that isn't linked
+    #[serde(default)]
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ProjectTargetReference {
+    pub target: String,
+    pub path: String,
+    #[serde(default)]
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PackageReference {
+    pub product: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PathReference {
+    pub path: String,
+    /// Directory of a `library`'s public headers
+    #[serde(rename = "publicHeaders", default)]
+    pub public_headers: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SdkReference {
+    /// File name in the SDK, like `StoreKit.framework` or `libz.tbd`
+    pub name: String,
+    /// `required`, or `optional` for a weakly linked one
+    #[serde(default)]
+    pub status: String,
+}
+
+impl SdkReference {
+    /// The framework's name, like `UIKit`, for a framework of the SDK.
+    pub(crate) fn framework(&self) -> Option<&str> {
+        self.name.strip_suffix(".framework")
+    }
+
+    /// The library's name, like `libz`, for a library of the SDK.
+    pub(crate) fn dylib(&self) -> Option<&str> {
+        if self.framework().is_some() {
+            return None;
+        }
+        let library = self
+            .name
+            .strip_suffix(".tbd")
+            .or_else(|| self.name.strip_suffix(".dylib"))
+            .unwrap_or(&self.name);
+        Some(library)
+    }
+
+    /// Whether the manifest marks it `optional`, so it's linked weakly.
+    pub(crate) fn is_weak(&self) -> bool {
+        self.status == "optional"
+    }
+
+    /// The linker flags that link the framework or library, e.g.
+    /// `-framework UIKit` or `-lz`, or `-weak_framework StoreKit` and
+    /// `-weak-lz` for an optional one.
+    pub(crate) fn linkopts(&self) -> Vec<String> {
+        let weak = self.is_weak();
+        if let Some(framework) = self.framework() {
+            let flag = if weak {
+                "-weak_framework"
+            } else {
+                "-framework"
+            };
+            return vec![flag.to_string(), framework.to_string()];
+        }
+        let library = self.dylib().unwrap_or(&self.name);
+        let library = library.strip_prefix("lib").unwrap_or(library);
+        vec![format!(
+            "{}{}",
+            if weak { "-weak-l" } else { "-l" },
+            library
+        )]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An app target with the given base build settings.
+    fn target_with_settings(settings: serde_json::Value) -> TuistTarget {
+        serde_json::from_value(serde_json::json!({
+            "name": "App",
+            "product": "app",
+            "bundleId": "dev.tuist.App",
+            "settings": { "base": settings, "configurations": [] },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn provisioning_profiles_must_stay_in_the_project() {
+        let workspace = tempfile::tempdir().unwrap();
+        let project = workspace.path().join("App");
+        fs::create_dir_all(project.join("Signing")).unwrap();
+        fs::write(project.join("Signing/App.mobileprovision"), "").unwrap();
+        fs::write(workspace.path().join("App.mobileprovision"), "").unwrap();
+
+        let apply = |profile: &str| {
+            let overrides = TargetOverrides {
+                provisioning_profile: Some(PathBuf::from(profile)),
+                ..TargetOverrides::default()
+            };
+            target_with_settings(serde_json::json!({})).apply_overrides(&overrides, &project)
+        };
+        assert!(apply("Signing/App.mobileprovision").is_ok());
+        assert!(apply("./Signing/../Signing/App.mobileprovision").is_ok());
+        for profile in [
+            "../App.mobileprovision",
+            "Signing/../../App.mobileprovision",
+        ] {
+            let error = apply(profile).unwrap_err().to_string();
+            assert!(error.contains("outside the project directory"), "{error}");
+        }
+    }
+
+    #[test]
+    fn path_resolver_resolves_relative_project_paths_and_absolute_files() {
+        let resolver = PathResolver::new(Path::new("/workspace"), ".");
+        let project = resolver.resolve(&resolver.root, "App");
+        assert_eq!(project, "/workspace/App");
+        assert_eq!(
+            resolver.resolve(Path::new(&project), "/workspace/App/Sources/App.swift"),
+            "/workspace/App/Sources/App.swift"
+        );
+    }
+
+    #[test]
+    fn path_resolver_resolves_absolute_project_paths_and_relative_files() {
+        let resolver = PathResolver::new(Path::new("/workspace"), ".");
+        let project = resolver.resolve(&resolver.root, "/workspace/App");
+        assert_eq!(project, "/workspace/App");
+        assert_eq!(
+            resolver.resolve(Path::new(&project), "Sources/../Sources/App.swift"),
+            "/workspace/App/Sources/App.swift"
+        );
+    }
+
+    #[test]
+    fn a_target_needs_only_its_name_product_and_bundle_id() {
+        let target: TuistTarget = serde_json::from_value(serde_json::json!({
+            "name": "Core",
+            "product": "framework",
+            "bundleId": "dev.tuist.Core",
+        }))
+        .unwrap();
+
+        assert_eq!(target.name, "Core");
+        assert!(target.buildable_folders.is_empty());
+        assert!(target.dependencies.is_empty());
+        assert!(target.settings.base.is_empty());
+    }
+
+    #[test]
+    fn families_follow_targeted_device_family() {
+        let default = [DeviceFamily::Iphone];
+        let families = |value: &str| {
+            target_with_settings(serde_json::json!({ "TARGETED_DEVICE_FAMILY": value }))
+                .families(&default)
+        };
+
+        assert_eq!(families("1"), [DeviceFamily::Iphone]);
+        assert_eq!(families("2"), [DeviceFamily::Ipad]);
+        assert_eq!(families("1,2"), [DeviceFamily::Iphone, DeviceFamily::Ipad]);
+        assert_eq!(
+            target_with_settings(serde_json::json!({})).families(&default),
+            default
+        );
+    }
+
+    #[test]
+    fn bundle_and_module_names_are_separate() {
+        let target: TuistTarget = serde_json::from_value(serde_json::json!({
+            "name": "App",
+            "product": "app",
+            "productName": "Acme",
+            "bundleId": "dev.tuist.App",
+        }))
+        .unwrap();
+        assert_eq!(target.product_name(), "Acme");
+        assert_eq!(target.declared_module_name(), "App");
+
+        let target = target_with_settings(serde_json::json!({
+            "PRODUCT_NAME": "Acme Beta",
+            "PRODUCT_MODULE_NAME": "AcmeKit",
+        }));
+        assert_eq!(target.product_name(), "Acme Beta");
+        assert_eq!(target.declared_module_name(), "AcmeKit");
+    }
+}
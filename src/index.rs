@@ -0,0 +1,1016 @@
+//! The index of the graph's targets that dependencies are resolved through.
+
+use anyhow::Result;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs;
+use std::hash::Hash;
+use std::path::{Path, PathBuf};
+
+use crate::build_file::{
+    glob_matches, imported_modules, is_valid_bundle_id, name_matches, swift_identifier,
+};
+use crate::config::{GenerateOptions, Platform};
+use crate::graph::{PathReference, TuistDependency, TuistProject, TuistTarget};
+use crate::starlark::starlark_string;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum PrebuiltKind {
+    Xcframework,
+    Framework,
+    /// A `.a` or `.dylib` with its headers
+    Library,
+}
+
+/// An import rule for a vendored binary framework or library. It's generated
+/// in the package of the innermost project that contains the binary, since
+/// Bazel packages can only reference files below them.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct PrebuiltImport {
+    /// Path of the project whose BUILD file declares the rule
+    owner: String,
+    pub(crate) name: String,
+    /// The framework's path relative to the owner
+    rel_path: String,
+    pub(crate) kind: PrebuiltKind,
+    /// Whether the binary is a static library rather than a dynamic one
+    is_static: bool,
+    /// A library's public headers directory relative to the owner
+    headers: Option<String>,
+}
+
+impl PrebuiltImport {
+    /// Fails with the reason the framework or library can't be imported.
+    fn new(
+        kind: PrebuiltKind,
+        dep: &PathReference,
+        projects: &[TuistProject],
+    ) -> Result<Self, &'static str> {
+        let path = Path::new(&dep.path);
+        if !path.exists() {
+            return Err("it doesn't exist");
+        }
+        let (owner, rel_path) = projects
+            .iter()
+            .filter_map(|project| Some((project, path.strip_prefix(&project.path).ok()?)))
+            .max_by_key(|(project, _)| project.path.len())
+            .ok_or("it's outside every project of the workspace")?;
+        let headers = match &dep.public_headers {
+            Some(headers) => Some(
+                Path::new(headers)
+                    .strip_prefix(&owner.path)
+                    .map_err(|_| "its headers are outside its project")?
+                    .display()
+                    .to_string(),
+            ),
+            None => None,
+        };
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        Ok(Self {
+            owner: owner.path.clone(),
+            name: format!("{}_import", stem.to_lowercase()),
+            rel_path: rel_path.display().to_string(),
+            kind,
+            is_static: is_static_framework(path, kind),
+            headers,
+        })
+    }
+
+    /// The import rule, e.g. `apple_dynamic_xcframework_import`. Libraries
+    /// use Bazel's own `cc_import`, which needs no load.
+    pub(crate) fn rule(&self) -> &'static str {
+        match (self.kind, self.is_static) {
+            (PrebuiltKind::Xcframework, false) => "apple_dynamic_xcframework_import",
+            (PrebuiltKind::Xcframework, true) => "apple_static_xcframework_import",
+            (PrebuiltKind::Framework, false) => "apple_dynamic_framework_import",
+            (PrebuiltKind::Framework, true) => "apple_static_framework_import",
+            (PrebuiltKind::Library, _) => "cc_import",
+        }
+    }
+
+    pub(crate) fn render(&self) -> String {
+        let files_attr = match self.kind {
+            PrebuiltKind::Xcframework => "xcframework_imports",
+            PrebuiltKind::Framework => "framework_imports",
+            PrebuiltKind::Library => return self.render_library(),
+        };
+        format!(
+            "{}(\n    name = {},\n    {} = glob([{}]),\n    visibility = [\"//visibility:public\"],\n)\n\n",
+            self.rule(),
+            starlark_string(&self.name),
+            files_attr,
+            starlark_string(format!("{}/**", self.rel_path))
+        )
+    }
+
+    /// A `cc_import` of the library, with its public headers on the include
+    /// path so `#import "Header.h"` finds them.
+    fn render_library(&self) -> String {
+        let mut rule = format!(
+            "cc_import(
+    name = {},
+",
+            starlark_string(&self.name)
+        );
+        if let Some(headers) = &self.headers {
+            rule.push_str(&format!(
+                "    hdrs = glob([{}]),
+    includes = [{}],
+",
+                starlark_string(format!("{}/**/*.h", headers)),
+                starlark_string(headers)
+            ));
+        }
+        let library_attr = if self.is_static {
+            "static_library"
+        } else {
+            "shared_library"
+        };
+        rule.push_str(&format!(
+            "    {} = {},
+    visibility = [\"//visibility:public\"],
+)
+
+",
+            library_attr,
+            starlark_string(&self.rel_path)
+        ));
+        rule
+    }
+}
+
+/// Whether the framework or library at `path` is a static library. An
+/// xcframework is judged by its first slice, which either holds a `.a`
+/// library or a framework. Anything that can't be read is assumed to be
+/// dynamic.
+fn is_static_framework(path: &Path, kind: PrebuiltKind) -> bool {
+    let framework_binary = |framework: &Path| {
+        framework
+            .file_stem()
+            .map(|name| framework.join(name))
+            .is_some_and(|binary| is_static_archive(&binary))
+    };
+
+    match kind {
+        PrebuiltKind::Library => path.extension().is_some_and(|ext| ext == "a"),
+        PrebuiltKind::Framework => framework_binary(path),
+        PrebuiltKind::Xcframework => {
+            let Some(slice) = fs::read_dir(path).ok().and_then(|entries| {
+                let mut slices: Vec<PathBuf> = entries
+                    .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                    .filter(|path| path.is_dir())
+                    .collect();
+                slices.sort();
+                slices.into_iter().next()
+            }) else {
+                return false;
+            };
+            let Ok(entries) = fs::read_dir(&slice) else {
+                return false;
+            };
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .find_map(|entry| match entry.extension()?.to_str()? {
+                    "a" => Some(true),
+                    "framework" => Some(framework_binary(&entry)),
+                    _ => None,
+                })
+                .unwrap_or(false)
+        }
+    }
+}
+
+/// Whether `binary` is an `ar` archive, i.e. a static library, looking
+/// through a universal binary's first architecture.
+fn is_static_archive(binary: &Path) -> bool {
+    const AR_MAGIC: &[u8] = b"!<arch>\n";
+    let Ok(bytes) = fs::read(binary) else {
+        return false;
+    };
+    let offset = match bytes.get(..4) {
+        // fat_header, then the first fat_arch's offset after its CPU type
+        // and subtype
+        Some([0xca, 0xfe, 0xba, 0xbe]) => bytes
+            .get(16..20)
+            .map(|offset| u32::from_be_bytes(offset.try_into().unwrap()) as usize),
+        Some([0xca, 0xfe, 0xba, 0xbf]) => bytes
+            .get(16..24)
+            .map(|offset| u64::from_be_bytes(offset.try_into().unwrap()) as usize),
+        _ => Some(0),
+    };
+    offset
+        .and_then(|offset| bytes.get(offset..offset + AR_MAGIC.len()))
+        .is_some_and(|magic| magic == AR_MAGIC)
+}
+
+/// Maps target names to the project that owns them so dependencies can be
+/// rendered as Bazel labels relative to the workspace root.
+pub(crate) struct TargetIndex {
+    workspace_root: PathBuf,
+    owners: HashMap<String, String>,
+    /// Product of every target, keyed by (project path, target name)
+    products: HashMap<(String, String), String>,
+    /// Platform of every target, keyed like `products`
+    target_platforms: HashMap<(String, String), Platform>,
+    /// Platforms the targets are built for
+    pub(crate) platforms: BTreeSet<Platform>,
+    /// Import rules of the vendored frameworks, keyed by the framework's
+    /// path, or why a framework can't be imported
+    pub(crate) imports: BTreeMap<String, Result<PrebuiltImport, &'static str>>,
+    /// The frameworks each app embeds because other apps depend on them
+    /// too, as (project path, target name), keyed like `products`
+    pub(crate) shared_frameworks: BTreeMap<(String, String), Vec<(String, String)>>,
+    /// The targets each target links, keyed like `products`
+    dependencies: HashMap<(String, String), Vec<(String, String)>>,
+    /// Targets that a dependent with Objective-C sources links, whose Swift
+    /// it may import through `<Module>-Swift.h`, keyed like `products`
+    objc_dependencies: BTreeSet<(String, String)>,
+    /// The targets defining each Swift module, keyed by module name
+    modules: HashMap<String, Vec<(String, String)>>,
+    /// Paths of the projects catalyst writes no BUILD file for
+    excluded_projects: BTreeSet<String>,
+    /// Targets catalyst generates no rules for, keyed like `products`
+    excluded_targets: BTreeSet<(String, String)>,
+}
+
+/// A dependency resolved to the target it points at.
+pub(crate) struct ResolvedDependency<'a> {
+    name: &'a str,
+    /// Path of the project that owns the target
+    owner: &'a str,
+}
+
+impl TargetIndex {
+    pub(crate) fn new(workspace_root: &Path, projects: &[TuistProject]) -> Self {
+        let mut owners = HashMap::new();
+        let mut products = HashMap::new();
+        let mut target_platforms = HashMap::new();
+        let mut imports = BTreeMap::new();
+        let mut modules: HashMap<String, Vec<(String, String)>> = HashMap::new();
+        for project in projects {
+            for (name, target) in &project.targets {
+                target_platforms.insert((project.path.clone(), name.clone()), target.platform());
+                modules
+                    .entry(swift_identifier(target.declared_module_name()))
+                    .or_default()
+                    .push((project.path.clone(), name.clone()));
+                owners
+                    .entry(name.clone())
+                    .or_insert_with(|| project.path.clone());
+                products.insert((project.path.clone(), name.clone()), target.product.clone());
+                for (kind, dep) in target
+                    .dependencies
+                    .iter()
+                    .filter_map(TuistDependency::prebuilt)
+                {
+                    imports
+                        .entry(dep.path.clone())
+                        .or_insert_with(|| PrebuiltImport::new(kind, dep, projects));
+                }
+            }
+        }
+
+        let mut index = Self {
+            workspace_root: workspace_root.to_path_buf(),
+            owners,
+            products,
+            platforms: target_platforms.values().copied().collect(),
+            target_platforms,
+            imports,
+            shared_frameworks: BTreeMap::new(),
+            dependencies: HashMap::new(),
+            objc_dependencies: BTreeSet::new(),
+            modules,
+            excluded_projects: BTreeSet::new(),
+            excluded_targets: BTreeSet::new(),
+        };
+        // Resolving needs the owners of every target, so the edges come last
+        let mut dependencies = HashMap::new();
+        let mut objc_dependencies = BTreeSet::new();
+        for project in projects {
+            for target in project.targets.values() {
+                let deps: Vec<(String, String)> = target
+                    .dependencies
+                    .iter()
+                    .filter(|dep| !dep.is_unlinked())
+                    .filter_map(|dep| index.resolve(dep, project))
+                    .map(|dep| (dep.owner.to_string(), dep.name.to_string()))
+                    .collect();
+                if target.has_objc_sources() {
+                    objc_dependencies.extend(deps.iter().cloned());
+                }
+                dependencies.insert((project.path.clone(), target.name.clone()), deps);
+            }
+        }
+        index.dependencies = dependencies;
+        index.objc_dependencies = objc_dependencies;
+        index
+    }
+
+    /// Paths of the projects whose BUILD files a newly added target shows up
+    /// in: its own, and those of the targets that link or embed it.
+    pub(crate) fn dependent_projects(
+        &self,
+        name: &str,
+        projects: &[TuistProject],
+    ) -> Result<BTreeSet<String>> {
+        let added: Vec<(String, String)> = projects
+            .iter()
+            .filter(|project| project.targets.contains_key(name))
+            .map(|project| (project.path.clone(), name.to_string()))
+            .collect();
+        if added.is_empty() {
+            anyhow::bail!(
+                "{} isn't a target of the Tuist graph; add it to a Project.swift first, or check `catalyst list-targets`",
+                name
+            );
+        }
+        let mut affected: BTreeSet<String> = added.iter().map(|(path, _)| path.clone()).collect();
+        let dependents = self
+            .dependencies
+            .iter()
+            .chain(self.shared_frameworks.iter());
+        for ((path, _), deps) in dependents {
+            if deps.iter().any(|dep| added.contains(dep)) {
+                affected.insert(path.clone());
+            }
+        }
+        Ok(affected)
+    }
+
+    /// Leaves the projects and targets matching `exclude_projects` and
+    /// `exclude_targets` out of generation, for ones that are built another
+    /// way. They stay in the index, so their dependents still get labels for
+    /// them, which point at rules from the project's own BUILD file or, for
+    /// targets of generated projects, its `BUILD_EXTRA_FILE`.
+    pub(crate) fn exclude(&mut self, projects: &[TuistProject], options: &GenerateOptions) {
+        let mut matched = BTreeSet::new();
+        for project in projects {
+            let package = self.package(&project.path).unwrap_or_default();
+            let pattern = options.exclude_projects.iter().find(|pattern| {
+                name_matches(pattern, &project.name) || glob_matches(pattern, Path::new(&package))
+            });
+            if let Some(pattern) = pattern {
+                verbose!(
+                    "Excluding project {} from generation, as {} matches it",
+                    project.name,
+                    pattern
+                );
+                matched.insert(pattern);
+                self.excluded_projects.insert(project.path.clone());
+            }
+
+            for target in project.targets.values() {
+                let pattern = options
+                    .exclude_targets
+                    .iter()
+                    .find(|pattern| name_matches(pattern, &target.name));
+                if let Some(pattern) = pattern {
+                    verbose!(
+                        "Excluding target {} of {} from generation, as {} matches it",
+                        target.name,
+                        project.name,
+                        pattern
+                    );
+                    matched.insert(pattern);
+                }
+                if pattern.is_some() || self.excluded_projects.contains(&project.path) {
+                    self.excluded_targets
+                        .insert((project.path.clone(), target.name.clone()));
+                }
+            }
+        }
+
+        for pattern in options
+            .exclude_projects
+            .iter()
+            .chain(&options.exclude_targets)
+        {
+            if !matched.contains(pattern) {
+                warning!(
+                    "{} excludes nothing; no project or target matches it",
+                    pattern
+                );
+            }
+        }
+    }
+
+    /// Whether catalyst writes the BUILD file of `project`.
+    pub(crate) fn generates_project(&self, project: &TuistProject) -> bool {
+        !self.excluded_projects.contains(&project.path)
+    }
+
+    /// Whether catalyst generates the rules of `target` in `project`.
+    pub(crate) fn generates(&self, project: &TuistProject, target: &TuistTarget) -> bool {
+        !self
+            .excluded_targets
+            .contains(&(project.path.clone(), target.name.clone()))
+    }
+
+    /// Finds the frameworks that more than one app depends on, directly or
+    /// through libraries and other frameworks, so each app embeds the one
+    /// `ios_framework` instead of linking a copy of its code. Frameworks
+    /// for another platform than the app's don't count.
+    pub(crate) fn share_frameworks(&mut self, projects: &[TuistProject]) {
+        let targets: HashMap<(&str, &str), (&TuistProject, &TuistTarget)> = projects
+            .iter()
+            .flat_map(|project| {
+                project.targets.values().map(move |target| {
+                    (
+                        (project.path.as_str(), target.name.as_str()),
+                        (project, target),
+                    )
+                })
+            })
+            .collect();
+
+        let mut users: BTreeMap<(String, String), Vec<(String, String)>> = BTreeMap::new();
+        let mut apps: Vec<_> = targets
+            .iter()
+            .filter(|(_, (_, target))| target.product == "app")
+            .collect();
+        apps.sort_by_key(|(key, _)| **key);
+        for (&(app_owner, app_name), (_, app)) in apps {
+            let platform = app.platform();
+            let mut seen = BTreeSet::new();
+            let mut stack = vec![(app_owner, app_name)];
+            while let Some(key) = stack.pop() {
+                let Some((project, target)) = targets.get(&key) else {
+                    continue;
+                };
+                for dep in &target.dependencies {
+                    let Some(dep) = self.resolve(dep, project) else {
+                        continue;
+                    };
+                    let product = self.product(&dep);
+                    // Apps and extensions link their own code
+                    if !matches!(
+                        product,
+                        Some("framework" | "static_library" | "static_framework")
+                    ) || !seen.insert((dep.owner, dep.name))
+                    {
+                        continue;
+                    }
+                    if product == Some("framework") && self.platform(&dep) == Some(platform) {
+                        users
+                            .entry((dep.owner.to_string(), dep.name.to_string()))
+                            .or_default()
+                            .push((app_owner.to_string(), app_name.to_string()));
+                    }
+                    stack.push((dep.owner, dep.name));
+                }
+            }
+        }
+
+        for (framework, apps) in users {
+            if apps.len() < 2 {
+                continue;
+            }
+            verbose!(
+                "{} is shared by {} apps; embedding it as a dynamic framework",
+                framework.1,
+                apps.len()
+            );
+            for app in apps {
+                self.shared_frameworks
+                    .entry(app)
+                    .or_default()
+                    .push(framework.clone());
+            }
+        }
+    }
+
+    /// Labels of the `_framework` rules of the shared frameworks the app
+    /// `target` embeds.
+    pub(crate) fn shared_framework_labels(
+        &self,
+        target: &TuistTarget,
+        project: &TuistProject,
+    ) -> Vec<String> {
+        self.shared_frameworks
+            .get(&(project.path.clone(), target.name.clone()))
+            .into_iter()
+            .flatten()
+            .map(|(owner, name)| format!("{}_framework", self.label(name, owner, &project.path)))
+            .collect()
+    }
+
+    /// Fails when two targets would generate rules with the same name in the
+    /// same Bazel package, e.g. `App` and `app`, or two projects that both end
+    /// up in the root package. Bazel would otherwise reject the BUILD file, or
+    /// the second target would silently replace the first.
+    pub(crate) fn check_rule_names(&self, projects: &[TuistProject]) -> Result<()> {
+        let mut rules: BTreeMap<(String, String), Vec<String>> = BTreeMap::new();
+        for project in projects {
+            let package = self.package(&project.path).unwrap_or_default();
+            for target in project.targets.values() {
+                for rule in target.rule_names() {
+                    rules
+                        .entry((package.clone(), rule))
+                        .or_default()
+                        .push(format!("{}/{}", project.name, target.name));
+                }
+            }
+            for import in self.imports_of(&project.path) {
+                rules
+                    .entry((package.clone(), import.name.clone()))
+                    .or_default()
+                    .push(format!("{}/{}", project.name, import.rel_path));
+            }
+        }
+
+        let conflicts: Vec<String> = rules
+            .into_iter()
+            .filter(|(_, targets)| targets.len() > 1)
+            .map(|((package, rule), mut targets)| {
+                targets.sort();
+                format!("  //{}:{} from {}", package, rule, targets.join(", "))
+            })
+            .collect();
+
+        if !conflicts.is_empty() {
+            anyhow::bail!(
+                "Conflicting Bazel target names; rename one of the Tuist targets:\n{}",
+                conflicts.join("\n")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Fails when a bundled target has a missing or malformed bundle ID, or
+    /// when two targets share one. simctl would otherwise reject the app at
+    /// install time with an error that doesn't name the target.
+    pub(crate) fn check_bundle_ids(&self, projects: &[TuistProject]) -> Result<()> {
+        let mut problems = Vec::new();
+        let mut owners: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+        for project in projects {
+            for target in project.targets.values() {
+                // Targets built another way get their bundle IDs checked there
+                if !self.generates(project, target) {
+                    continue;
+                }
+                let required = matches!(
+                    target.product.as_str(),
+                    "app" | "app_extension" | "unit_tests" | "ui_tests" | "framework"
+                );
+                let id = target.bundle_id.as_str();
+                // Static products have no bundle, so their ID is never used,
+                // and resource bundles may leave it out
+                if !required && (target.product != "bundle" || id.is_empty()) {
+                    continue;
+                }
+
+                let name = format!("{}/{}", project.name, target.name);
+                if id.is_empty() {
+                    problems.push(format!("  {} has no bundle ID", name));
+                } else if !is_valid_bundle_id(id) {
+                    problems.push(format!(
+                        "  {} has an invalid bundle ID \"{}\"; use reverse-DNS like com.example.App, with letters, digits, and hyphens",
+                        name, id
+                    ));
+                } else {
+                    owners.entry(id).or_default().push(name);
+                }
+            }
+        }
+
+        for (id, mut targets) in owners {
+            if targets.len() > 1 {
+                targets.sort();
+                problems.push(format!(
+                    "  {} share the bundle ID {}",
+                    targets.join(", "),
+                    id
+                ));
+            }
+        }
+
+        if !problems.is_empty() {
+            anyhow::bail!(
+                "Invalid bundle IDs; fix them in the Tuist manifests:\n{}",
+                problems.join("\n")
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Warns when the `sources` of `target` import the module of another
+    /// target in the workspace that it doesn't depend on, directly or through
+    /// other targets. Xcode may still build such a target when the module
+    /// happens to be built first, but Bazel only sees the declared deps, and
+    /// fails on the import or with missing symbols.
+    pub(crate) fn check_imports(
+        &self,
+        target: &TuistTarget,
+        project: &TuistProject,
+        sources: &[&str],
+    ) {
+        let key = (project.path.clone(), target.name.clone());
+        let mut reachable = BTreeSet::new();
+        let mut stack = vec![&key];
+        while let Some(node) = stack.pop() {
+            for dep in self.dependencies.get(node).into_iter().flatten() {
+                if reachable.insert(dep) {
+                    stack.push(dep);
+                }
+            }
+        }
+
+        let mut missing: BTreeMap<&str, &[(String, String)]> = BTreeMap::new();
+        for source in sources {
+            let Ok(content) = fs::read_to_string(source) else {
+                continue;
+            };
+            for module in imported_modules(&content) {
+                let Some((module, owners)) = self.modules.get_key_value(module) else {
+                    continue;
+                };
+                if !owners
+                    .iter()
+                    .any(|owner| *owner == key || reachable.contains(owner))
+                {
+                    missing.insert(module, owners);
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            let missing: Vec<String> = missing
+                .into_iter()
+                .map(|(module, owners)| {
+                    let names: Vec<&str> = owners.iter().map(|(_, name)| name.as_str()).collect();
+                    if names == [module] {
+                        module.to_string()
+                    } else {
+                        format!("{} (from {})", module, names.join(", "))
+                    }
+                })
+                .collect();
+            warning!(
+                "{} imports modules it doesn't depend on in the Tuist graph, directly or transitively, so its Bazel build may fail; add their targets to its dependencies in {}:\n  {}",
+                target.name,
+                Path::new(&project.path).join("Project.swift").display(),
+                missing.join("\n  ")
+            );
+        }
+    }
+
+    /// Fails when target dependencies form a cycle, naming the targets in it.
+    /// Bazel reports cycles too, but in terms of generated labels and only
+    /// after loading every package.
+    pub(crate) fn check_cycles(&self, projects: &[TuistProject]) -> Result<()> {
+        let names: HashMap<&str, &str> = projects
+            .iter()
+            .map(|project| (project.path.as_str(), project.name.as_str()))
+            .collect();
+
+        // Edges keyed by (project path, target name), sorted so the reported
+        // cycle doesn't depend on hash order
+        let mut edges: BTreeMap<(&str, &str), Vec<(&str, &str)>> = BTreeMap::new();
+        for project in projects {
+            for target in project.targets.values() {
+                let mut deps: Vec<(&str, &str)> = target
+                    .dependencies
+                    .iter()
+                    .filter(|dep| !dep.is_unlinked())
+                    .filter_map(|dep| self.resolve(dep, project))
+                    .map(|dep| (dep.owner, dep.name))
+                    .collect();
+                deps.sort_unstable();
+                edges.insert((project.path.as_str(), target.name.as_str()), deps);
+            }
+        }
+
+        let mut done: BTreeSet<(&str, &str)> = BTreeSet::new();
+        for &start in edges.keys() {
+            if done.contains(&start) {
+                continue;
+            }
+
+            // Iterative depth-first search; `path` holds the targets on the
+            // current branch together with the index of the next edge to visit
+            let mut path: Vec<((&str, &str), usize)> = vec![(start, 0)];
+            while let Some((node, next)) = path.last_mut() {
+                let node = *node;
+                let Some(&dep) = edges.get(&node).and_then(|deps| deps.get(*next)) else {
+                    done.insert(node);
+                    path.pop();
+                    continue;
+                };
+                *next += 1;
+
+                if let Some(position) = path.iter().position(|(on_path, _)| *on_path == dep) {
+                    let cycle: Vec<String> = path[position..]
+                        .iter()
+                        .map(|(node, _)| *node)
+                        .chain([dep])
+                        .map(|(owner, name)| {
+                            format!("{}/{}", names.get(owner).copied().unwrap_or(owner), name)
+                        })
+                        .collect();
+                    anyhow::bail!("Dependency cycle between targets: {}", cycle.join(" → "));
+                }
+                if !done.contains(&dep) {
+                    path.push((dep, 0));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The Bazel package for a project path, e.g. `Core/Networking`.
+    pub(crate) fn package(&self, project_path: &str) -> Option<String> {
+        Path::new(project_path)
+            .strip_prefix(&self.workspace_root)
+            .ok()
+            .map(|rel| rel.display().to_string())
+    }
+
+    /// Resolves a target or project dependency of a target in `from`, or
+    /// `None` for dependency kinds that don't map to a generated target.
+    pub(crate) fn resolve<'a>(
+        &'a self,
+        dep: &'a TuistDependency,
+        from: &'a TuistProject,
+    ) -> Option<ResolvedDependency<'a>> {
+        if let Some(target) = &dep.target {
+            let owner = if from.targets.contains_key(&target.name) {
+                from.path.as_str()
+            } else {
+                self.owners.get(&target.name)?.as_str()
+            };
+            Some(ResolvedDependency {
+                name: &target.name,
+                owner,
+            })
+        } else {
+            dep.project.as_ref().map(|project| ResolvedDependency {
+                name: &project.target,
+                owner: &project.path,
+            })
+        }
+    }
+
+    /// The import rules the BUILD file of the project at `project_path`
+    /// declares.
+    pub(crate) fn imports_of<'a>(
+        &'a self,
+        project_path: &'a str,
+    ) -> impl Iterator<Item = &'a PrebuiltImport> {
+        self.imports
+            .values()
+            .filter_map(|import| import.as_ref().ok())
+            .filter(move |import| import.owner == project_path)
+    }
+
+    /// The platform a resolved dependency is built for.
+    fn platform(&self, dep: &ResolvedDependency) -> Option<Platform> {
+        self.target_platforms
+            .get(&(dep.owner.to_string(), dep.name.to_string()))
+            .copied()
+    }
+
+    /// The product of a resolved dependency, e.g. `app` or `framework`.
+    pub(crate) fn product(&self, dep: &ResolvedDependency) -> Option<&str> {
+        self.products
+            .get(&(dep.owner.to_string(), dep.name.to_string()))
+            .map(String::as_str)
+    }
+
+    /// Renders the Bazel label a swift_library uses to depend on `dep`, or
+    /// `None` for dependency kinds that don't map to a generated target.
+    /// Targets in the same project get a relative `:name` label, targets in
+    /// other projects a fully-qualified `//package:name` one. Apps are
+    /// referenced through their `_lib` swift_library, since code can't link
+    /// against an ios_application. App extensions, apps for another
+    /// `platform` (a watchOS companion app), and resource bundles are
+    /// embedded or bundled rather than linked, so they have no label here
+    /// (see `dependency_labels_with_product` and `watch_application`), and
+    /// neither do targets the manifest doesn't link.
+    pub(crate) fn dependency_label(
+        &self,
+        dep: &TuistDependency,
+        from: &TuistProject,
+        platform: Platform,
+    ) -> Option<String> {
+        if dep.is_unlinked() {
+            return None;
+        }
+        if let Some(label) = dep.package_label() {
+            return Some(label);
+        }
+        if let Some((_, prebuilt)) = dep.prebuilt() {
+            let import = self.imports.get(&prebuilt.path)?.as_ref().ok()?;
+            return Some(self.label(&import.name, &import.owner, &from.path));
+        }
+        let resolved = self.resolve(dep, from)?;
+        let label = self.label(resolved.name, resolved.owner, &from.path);
+        match self.product(&resolved) {
+            Some("app") if self.platform(&resolved) != Some(platform) => None,
+            Some("app") => Some(format!("{}_lib", label)),
+            Some("app_extension" | "bundle") => None,
+            _ => Some(label),
+        }
+    }
+
+    /// The `<Module>-Swift.h` header the swift_library of `target` publishes:
+    /// its `generated_header_name` override, or else Xcode's name when
+    /// Objective-C code imports its Swift, whether it's in the target
+    /// (`has_objc`) or in a dependent.
+    pub(crate) fn generated_header(
+        &self,
+        target: &TuistTarget,
+        from: &TuistProject,
+        options: &GenerateOptions,
+        has_objc: bool,
+    ) -> Option<String> {
+        let objc_consumers = has_objc
+            || self
+                .objc_dependencies
+                .contains(&(from.path.clone(), target.name.clone()));
+        options
+            .targets
+            .get(&target.name)
+            .and_then(|overrides| overrides.generated_header_name.clone())
+            .or_else(|| {
+                objc_consumers.then(|| {
+                    format!(
+                        "{}-Swift.h",
+                        swift_identifier(target.declared_module_name())
+                    )
+                })
+            })
+    }
+
+    /// Label of the app that hosts the tests of `target`: the `test_host` of
+    /// its `[targets.<name>]` table, or else the first dependency whose product
+    /// is `app`. Falls back to stripping `suffixes` from the test target's
+    /// name, with a warning, when the project has an app by that name, and
+    /// `None` when it doesn't, as in projects with only test targets.
+    pub(crate) fn test_host(
+        &self,
+        target: &TuistTarget,
+        from: &TuistProject,
+        suffixes: &[&str],
+        options: &GenerateOptions,
+    ) -> Option<String> {
+        if let Some(host) = options
+            .targets
+            .get(&target.name)
+            .and_then(|overrides| overrides.test_host.clone())
+        {
+            return Some(host);
+        }
+        let host = target
+            .dependencies
+            .iter()
+            .filter_map(|dep| self.resolve(dep, from))
+            .find(|dep| self.product(dep) == Some("app"))
+            .map(|dep| self.label(dep.name, dep.owner, &from.path));
+        if host.is_some() {
+            return host;
+        }
+
+        let name = suffixes
+            .iter()
+            .find_map(|suffix| target.name.strip_suffix(suffix))
+            .unwrap_or(&target.name);
+        let is_app = self
+            .products
+            .get(&(from.path.clone(), name.to_string()))
+            .is_some_and(|product| product == "app");
+        if !is_app {
+            return None;
+        }
+        let guess = format!(":{}", name.to_lowercase());
+        warning!(
+            "{} has no app dependency; guessing test host {}",
+            target.name,
+            guess
+        );
+        Some(guess)
+    }
+
+    /// Whether `dep` points at a target with the given product.
+    pub(crate) fn has_product(
+        &self,
+        dep: &TuistDependency,
+        from: &TuistProject,
+        product: &str,
+    ) -> bool {
+        self.resolve(dep, from)
+            .is_some_and(|dep| self.product(&dep) == Some(product))
+    }
+
+    /// Labels of the dependencies of `target` with the given product, e.g. the
+    /// app extensions it embeds or the resource bundles it ships.
+    pub(crate) fn dependency_labels_with_product(
+        &self,
+        target: &TuistTarget,
+        from: &TuistProject,
+        product: &str,
+    ) -> Vec<String> {
+        let mut labels: Vec<String> = target
+            .dependencies
+            .iter()
+            .filter_map(|dep| self.resolve(dep, from))
+            .filter(|dep| self.product(dep) == Some(product))
+            .map(|dep| self.label(dep.name, dep.owner, &from.path))
+            .collect();
+        labels.sort();
+        labels.dedup();
+        labels
+    }
+
+    /// Label of the watchOS app that `target`, an iOS app, embeds as its
+    /// companion, if any.
+    pub(crate) fn watch_application(
+        &self,
+        target: &TuistTarget,
+        from: &TuistProject,
+    ) -> Option<String> {
+        target
+            .dependencies
+            .iter()
+            .filter_map(|dep| self.resolve(dep, from))
+            .find(|dep| {
+                self.product(dep) == Some("app") && self.platform(dep) == Some(Platform::Watchos)
+            })
+            .map(|dep| self.label(dep.name, dep.owner, &from.path))
+    }
+
+    fn label(&self, target_name: &str, owner: &str, from_project: &str) -> String {
+        let name = target_name.to_lowercase();
+        if owner == from_project {
+            return format!(":{}", name);
+        }
+
+        match self.package(owner) {
+            Some(package) => format!("//{}:{}", package, name),
+            None => {
+                warning!(
+                    "project {} is outside the workspace root {}; using a relative label for {}",
+                    owner,
+                    self.workspace_root.display(),
+                    target_name
+                );
+                format!(":{}", name)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A project at `/workspace/<name>` with one framework target of the same
+    /// name, depending on the framework of the project `dependency` with the
+    /// given link `status`.
+    fn project(name: &str, dependency: &str, status: &str) -> TuistProject {
+        serde_json::from_value(serde_json::json!({
+            "name": name,
+            "path": format!("/workspace/{}", name),
+            "targets": {
+                name: {
+                    "name": name,
+                    "product": "framework",
+                    "bundleId": format!("dev.tuist.{}", name),
+                    "dependencies": [{
+                        "project": {
+                            "target": dependency,
+                            "path": format!("/workspace/{}", dependency),
+                            "status": status,
+                        },
+                    }],
+                },
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn check_cycles_names_the_targets_of_a_cycle() {
+        let projects = [
+            project("App", "Core", "required"),
+            project("Core", "App", "required"),
+        ];
+        let index = TargetIndex::new(Path::new("/workspace"), &projects);
+
+        let err = index.check_cycles(&projects).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Dependency cycle between targets: App/App → Core/Core → App/App"
+        );
+
+        // A back-edge that isn't linked doesn't end up in the BUILD files
+        let projects = [
+            project("App", "Core", "required"),
+            project("Core", "App", "none"),
+        ];
+        let index = TargetIndex::new(Path::new("/workspace"), &projects);
+        assert!(index.check_cycles(&projects).is_ok());
+    }
+}
@@ -0,0 +1,112 @@
+//! Converts Tuist projects to Bazel builds.
+//!
+//! The `catalyst` binary is a thin command-line wrapper around this crate, so
+//! the same conversion can be driven from other tools:
+//!
+//! ```no_run
+//! use catalyst::Catalyst;
+//!
+//! let written = Catalyst::new("path/to/project").generate()?;
+//! for path in &written {
+//!     println!("{}", path.display());
+//! }
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! Progress goes to stderr like it does for the CLI; call [`set_verbosity`]
+//! with [`Verbosity::Quiet`] to only keep the errors.
+//!
+//! Errors are [`anyhow::Error`]s; the ones callers may want to handle, like a
+//! failed Bazel build, carry a [`CatalystError`] to downcast to.
+
+#[macro_use]
+mod output;
+mod build;
+mod build_file;
+mod cache;
+mod command;
+mod config;
+mod doctor;
+mod error;
+mod explain;
+mod generate;
+mod graph;
+mod index;
+mod plist;
+mod report;
+mod simulator;
+mod starlark;
+mod tuist;
+mod workspace;
+
+pub use build::*;
+pub use cache::*;
+pub use command::*;
+pub use config::*;
+pub use doctor::*;
+pub use error::*;
+pub use explain::*;
+pub use generate::*;
+pub use graph::*;
+pub use output::*;
+pub use report::*;
+pub use simulator::*;
+pub use tuist::*;
+
+use anyhow::Result;
+use std::path::PathBuf;
+
+/// A Tuist project to convert, with the options to convert it with.
+#[derive(Debug, Clone)]
+pub struct Catalyst {
+    project_dir: PathBuf,
+    options: GenerateOptions,
+}
+
+impl Catalyst {
+    /// Uses catalyst's built-in defaults. Pass
+    /// `GenerateOptions::from_config(&Config::load(dir)?)` to
+    /// [`Catalyst::with_options`] to honor the project's `catalyst.toml` the
+    /// way the CLI does.
+    pub fn new(project_dir: impl Into<PathBuf>) -> Self {
+        Self {
+            project_dir: project_dir.into(),
+            options: GenerateOptions::default(),
+        }
+    }
+
+    pub fn with_options(self, options: GenerateOptions) -> Self {
+        Self { options, ..self }
+    }
+
+    pub fn options(&self) -> &GenerateOptions {
+        &self.options
+    }
+
+    /// The project's Tuist graph, from catalyst's cache when it's current.
+    pub fn graph(&self) -> Result<TuistGraph> {
+        load_graph(
+            &self.project_dir,
+            &get_catalyst_cache_dir()?,
+            &self.options,
+            self.options.no_cache,
+        )
+    }
+
+    /// Writes the Bazel files, like `catalyst generate`, and returns their
+    /// paths.
+    pub fn generate(&self) -> Result<Vec<PathBuf>> {
+        generate_project(
+            &self.project_dir,
+            &self.options,
+            &mut BuildReport::default(),
+        )
+    }
+
+    /// Generates the Bazel files and builds them, like `catalyst build`.
+    pub fn build(&self) -> Result<BuildReport> {
+        let mut report = BuildReport::default();
+        build_project(&self.project_dir, &self.options, &mut report)?;
+        Ok(report)
+    }
+}
@@ -1,10 +1,18 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs;
-use std::path::{Path, PathBuf};
-use std::process::Command;
+use catalyst::{
+    build_project, clean_project, discover_project_roots, explain_target, find_app_target,
+    find_test_label, for_each_project_root, generate_project, get_catalyst_cache_dir, init_project,
+    list_targets, load_graph, run_bazel_test, run_doctor, run_in_simulator, set_cache_dir,
+    set_command_timeout, set_dry_run, set_timings, set_verbosity, show_cache, stream_app_logs,
+    verbosity, wait_for_interrupt, watch_project, BazelMode, BuildConfiguration, BuildFileName,
+    BuildPlatform, BuildReport, Config, GenerateOptions, LaunchOptions, OutputFormat, RunSteps,
+    SimulatorQuery, Verbosity, DEFAULT_BOOT_TIMEOUT_SECS, DEFAULT_SIMULATOR,
+};
+use clap::{Args, CommandFactory, Parser};
+use clap_complete::Shell;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
 
 #[derive(Parser, Debug)]
 #[command(name = "catalyst")]
@@ -12,635 +20,880 @@ use std::process::Command;
 struct Cli {
     #[command(subcommand)]
     command: Option<Commands>,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        help = "Also print the commands catalyst runs"
+    )]
+    verbose: bool,
+
+    #[arg(
+        short,
+        long,
+        global = true,
+        conflicts_with = "verbose",
+        help = "Only print errors and command results"
+    )]
+    quiet: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        default_value_t = OutputFormat::Human,
+        help = "Format of the result printed by build, run, test, and generate"
+    )]
+    format: OutputFormat,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Print the files catalyst would write, or remove, without touching them or running Bazel"
+    )]
+    dry_run: bool,
+
+    #[arg(
+        long,
+        global = true,
+        help = "Print how long each phase took, like tuist graph, generation, and bazel build, at the end; --format json includes them"
+    )]
+    timings: bool,
+
+    #[arg(
+        long,
+        global = true,
+        value_name = "DIR",
+        help = "Directory for catalyst's cache [default: $CATALYST_CACHE_DIR, or catalyst in the user cache directory]"
+    )]
+    cache_dir: Option<PathBuf>,
 }
 
 #[derive(Parser, Debug)]
 enum Commands {
     /// Build the project with Bazel
     Build {
+        #[command(flatten)]
+        project: ProjectArgs,
+
+        #[arg(long, help = "Build even if nothing changed since the last build")]
+        force: bool,
+
+        #[arg(long, help = SINGLE_HELP)]
+        single: bool,
+
         #[arg(
-            short,
             long,
-            help = "Project directory (defaults to current directory)"
+            help = "Rebuild whenever a source or Tuist manifest changes, until Ctrl-C"
         )]
-        path: Option<PathBuf>,
+        watch: bool,
+
+        #[arg(last = true, help = "Extra arguments passed to bazel build")]
+        bazel_args: Vec<String>,
     },
     /// Build and run the app in iOS Simulator
     Run {
+        #[command(flatten)]
+        project: ProjectArgs,
+
         #[arg(
             short,
             long,
-            help = "Project directory (defaults to current directory)"
+            help = "Simulator device name or UDID to use, or the device type with --ephemeral-simulator [default: iPhone 16]"
         )]
-        path: Option<PathBuf>,
+        simulator: Option<String>,
 
         #[arg(
-            short,
             long,
-            default_value = "iPhone 16",
-            help = "Simulator device to use"
+            value_name = "VERSION",
+            help = "iOS runtime the simulator has to run, e.g. 17.5; creates the device on it if needed"
         )]
-        simulator: String,
+        simulator_os: Option<String>,
 
-        #[arg(short, long, help = "Target to run (defaults to first app target)")]
-        target: Option<String>,
-    },
-}
+        #[arg(
+            long,
+            help = "Create a fresh simulator of the --simulator device type for this run, and delete it when catalyst exits"
+        )]
+        ephemeral_simulator: bool,
 
-#[derive(Debug, Deserialize, Serialize)]
-struct TuistGraph {
-    name: String,
-    path: String,
-    projects: serde_json::Value, // Array with path string followed by project object
-}
+        #[arg(
+            short,
+            long,
+            help = "App target to run (required when the project has several iOS apps)"
+        )]
+        target: Option<String>,
 
-#[derive(Debug, Deserialize, Serialize)]
-struct TuistProject {
-    name: String,
-    path: String,
-    targets: HashMap<String, TuistTarget>,
-}
+        #[arg(
+            long,
+            conflicts_with = "install_only",
+            help = "Stream the app's logs after launching until Ctrl-C"
+        )]
+        logs: bool,
 
-#[derive(Debug, Deserialize, Serialize)]
-struct TuistTarget {
-    name: String,
-    product: String,
-    #[serde(rename = "bundleId")]
-    bundle_id: String,
-    #[serde(rename = "buildableFolders")]
-    buildable_folders: Vec<BuildableFolder>,
-    dependencies: Vec<TuistDependency>,
-}
+        #[arg(long, help = "Install the app built last time instead of building it")]
+        skip_build: bool,
 
-#[derive(Debug, Deserialize, Serialize)]
-struct BuildableFolder {
-    path: String,
-    #[serde(rename = "resolvedFiles")]
-    resolved_files: Vec<ResolvedFile>,
-}
+        #[arg(
+            long,
+            conflicts_with = "ephemeral_simulator",
+            help = "Launch the app installed on the simulator instead of installing the build"
+        )]
+        skip_install: bool,
 
-#[derive(Debug, Deserialize, Serialize)]
-struct ResolvedFile {
-    path: String,
-}
+        #[arg(
+            long,
+            conflicts_with_all = ["skip_install", "launch_only", "ephemeral_simulator"],
+            help = "Build and install the app without launching it"
+        )]
+        install_only: bool,
 
-#[derive(Debug, Deserialize, Serialize)]
-struct TuistDependency {
-    target: Option<TargetReference>,
-}
+        #[arg(
+            long,
+            conflicts_with_all = ["skip_build", "skip_install", "ephemeral_simulator"],
+            help = "Relaunch the app installed on the simulator, without building or installing it"
+        )]
+        launch_only: bool,
 
-#[derive(Debug, Deserialize, Serialize)]
-struct TargetReference {
-    name: String,
-    #[serde(default)]
-    status: String,
-}
+        #[arg(long, help = "Build even if nothing changed since the last build")]
+        force: bool,
 
-fn main() -> Result<()> {
-    let cli = Cli::parse();
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            default_value_t = DEFAULT_BOOT_TIMEOUT_SECS,
+            help = "How long to wait for the simulator to boot"
+        )]
+        boot_timeout: u64,
 
-    match cli.command {
-        Some(Commands::Build { path }) => {
-            let project_dir = path.unwrap_or_else(|| PathBuf::from("."));
-            build_project(&project_dir)?;
-        }
-        Some(Commands::Run {
-            path,
-            simulator,
-            target,
-        }) => {
-            let project_dir = path.unwrap_or_else(|| PathBuf::from("."));
-            build_project(&project_dir)?;
+        #[arg(
+            long = "env",
+            value_name = "KEY=VALUE",
+            value_parser = parse_key_value,
+            help = "Environment variable for the launched app (repeatable)"
+        )]
+        env: Vec<(String, String)>,
 
-            // Get target info from the graph
-            let graph = run_tuist_graph(&project_dir)?;
-            let (target_name, bundle_id) = find_app_target(&graph, target.as_deref())?;
+        #[arg(
+            long = "arg",
+            value_name = "VALUE",
+            allow_hyphen_values = true,
+            help = "Launch argument for the app, e.g. --arg=-UITestMode (repeatable)"
+        )]
+        args: Vec<String>,
 
-            run_in_simulator(&project_dir, &target_name, &bundle_id, &simulator)?;
-        }
-        None => {
-            // Default behavior: build
-            let project_dir = PathBuf::from(".");
-            build_project(&project_dir)?;
-        }
-    }
+        #[arg(last = true, help = "Extra arguments passed to bazel build")]
+        bazel_args: Vec<String>,
+    },
+    /// Run the unit and UI tests with bazel test
+    Test {
+        #[command(flatten)]
+        project: ProjectArgs,
 
-    Ok(())
-}
+        #[arg(short, long, help = "Test target to run (defaults to all tests)")]
+        target: Option<String>,
 
-fn build_project(project_dir: &Path) -> Result<()> {
-    println!("Running catalyst on project: {}", project_dir.display());
+        #[arg(last = true, help = "Extra arguments passed to bazel test")]
+        bazel_args: Vec<String>,
+    },
+    /// Generate the Bazel files without building
+    Generate {
+        #[command(flatten)]
+        project: ProjectArgs,
 
-    // Step 1: Run tuist graph
-    let graph = run_tuist_graph(project_dir)?;
+        #[arg(long, help = "Regenerate the BUILD files of unchanged projects too")]
+        force: bool,
 
-    // Step 2: Get XDG-compliant cache directory
-    let cache_dir = get_catalyst_cache_dir()?;
-    fs::create_dir_all(&cache_dir)?;
+        #[arg(long, help = SINGLE_HELP)]
+        single: bool,
+    },
+    /// Generate the BUILD files of a newly added target and of its
+    /// dependents only, leaving the others as they are
+    AddTarget {
+        #[command(flatten)]
+        project: ProjectArgs,
+
+        #[arg(short, long, help = "The added target")]
+        target: String,
+    },
+    /// Print the targets parsed from the Tuist graph
+    ListTargets {
+        #[arg(
+            short,
+            long,
+            help = "Project directory (defaults to current directory)"
+        )]
+        path: Option<PathBuf>,
 
-    println!("Using catalyst cache directory: {}", cache_dir.display());
+        #[arg(
+            long,
+            value_name = "PATH",
+            help = "Tuist executable to run [default: tuist]"
+        )]
+        tuist: Option<PathBuf>,
 
-    // Step 3: Generate Bazel files
-    generate_bazel_files(&graph, project_dir, &cache_dir)?;
+        #[arg(long, help = "Print the parsed projects as JSON")]
+        json: bool,
+    },
+    /// Print how catalyst converts one target: its files, dependencies,
+    /// settings, and the rules generated for it
+    ExplainTarget {
+        #[command(flatten)]
+        project: ProjectArgs,
+
+        #[arg(short, long, help = "Target to explain")]
+        target: String,
+    },
+    /// Print where catalyst's cache for the project is and what it holds
+    ShowCache {
+        #[arg(
+            short,
+            long,
+            help = "Project directory (defaults to current directory)"
+        )]
+        path: Option<PathBuf>,
 
-    // Step 4: Run Bazel build
-    run_bazel_build(project_dir)?;
+        #[arg(long, help = "Also print the cached Tuist graph")]
+        contents: bool,
+    },
+    /// Check that the tools catalyst depends on are installed
+    Doctor,
+    /// Write a starter catalyst.toml
+    Init {
+        #[arg(
+            short,
+            long,
+            help = "Project directory (defaults to current directory)"
+        )]
+        path: Option<PathBuf>,
 
-    println!("Build completed successfully!");
+        #[arg(long, help = "Overwrite an existing catalyst.toml")]
+        force: bool,
+    },
+    /// Remove the files catalyst generated
+    Clean {
+        #[arg(
+            short,
+            long,
+            help = "Project directory (defaults to current directory)"
+        )]
+        path: Option<PathBuf>,
 
-    Ok(())
+        #[arg(long, help = "Also remove the catalyst cache directory")]
+        cache: bool,
+    },
+    /// Print a shell completion script
+    Completions {
+        #[arg(value_enum, help = "Shell to complete in")]
+        shell: Shell,
+    },
 }
 
-fn run_tuist_graph(project_dir: &Path) -> Result<TuistGraph> {
-    // Create a temporary directory for the graph output
-    let temp_dir = std::env::temp_dir();
-    let output_dir = temp_dir.join(format!("tuist-graph-{}", std::process::id()));
-    fs::create_dir_all(&output_dir)?;
-
-    let graph_file = output_dir.join("graph.json");
+/// Parses a `KEY=VALUE` argument of `--env` or `--define`.
+fn parse_key_value(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", value)),
+    }
+}
 
-    // Ensure cleanup on exit
-    struct TempDirGuard(PathBuf);
-    impl Drop for TempDirGuard {
-        fn drop(&mut self) {
-            let _ = fs::remove_dir_all(&self.0);
+impl Commands {
+    /// The subcommand as typed on the command line.
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Build { .. } => "build",
+            Self::Run { .. } => "run",
+            Self::Test { .. } => "test",
+            Self::Generate { .. } => "generate",
+            Self::AddTarget { .. } => "add-target",
+            Self::ListTargets { .. } => "list-targets",
+            Self::ExplainTarget { .. } => "explain-target",
+            Self::ShowCache { .. } => "show-cache",
+            Self::Doctor => "doctor",
+            Self::Init { .. } => "init",
+            Self::Clean { .. } => "clean",
+            Self::Completions { .. } => "completions",
         }
     }
-    let _guard = TempDirGuard(output_dir.clone());
-
-    println!(
-        "Running: tuist graph --format json --no-open --output-path {}",
-        output_dir.display()
-    );
-
-    let status = Command::new("tuist")
-        .args([
-            "graph",
-            "--format",
-            "json",
-            "--no-open",
-            "--output-path",
-            output_dir.to_str().unwrap(),
-        ])
-        .current_dir(project_dir)
-        .status()
-        .context("Failed to execute tuist graph")?;
-
-    if !status.success() {
-        anyhow::bail!("tuist graph command failed");
-    }
-
-    // Read the graph file
-    let graph_content = fs::read_to_string(&graph_file).context("Failed to read graph file")?;
-
-    let graph: TuistGraph =
-        serde_json::from_str(&graph_content).context("Failed to parse tuist graph JSON output")?;
-
-    println!(
-        "Successfully parsed Tuist graph for project: {}",
-        graph.name
-    );
-
-    Ok(graph)
 }
 
-fn get_catalyst_cache_dir() -> Result<PathBuf> {
-    let cache_base = dirs::cache_dir().context("Failed to determine cache directory")?;
-
-    Ok(cache_base.join("catalyst"))
+const SINGLE_HELP: &str = "Only convert the Tuist project at the path, instead of every Workspace.swift or Project.swift found below a path without one";
+
+/// Options shared by every command that generates Bazel files.
+#[derive(Args, Debug, Default)]
+struct ProjectArgs {
+    #[arg(
+        short,
+        long,
+        help = "Project directory (defaults to current directory)"
+    )]
+    path: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "How Bazel dependencies are declared [default: workspace]"
+    )]
+    bazel_mode: Option<BazelMode>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Name of the generated BUILD files; BUILD.bazel also renames WORKSPACE [default: BUILD]"
+    )]
+    build_file_name: Option<BuildFileName>,
+
+    #[arg(
+        long,
+        help = "Minimum OS version for targets that don't declare a deployment target [default: 15.0]"
+    )]
+    minimum_os_version: Option<String>,
+
+    #[arg(
+        long = "config",
+        value_enum,
+        help = "Build configuration [default: debug]"
+    )]
+    configuration: Option<BuildConfiguration>,
+
+    #[arg(
+        long,
+        value_enum,
+        help = "Build iOS bundles for the simulator or devices [default: Bazel's]"
+    )]
+    platform: Option<BuildPlatform>,
+
+    #[arg(
+        long,
+        help = "Write generated files to this directory instead of the project directory"
+    )]
+    output_dir: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Root the Bazel workspace at this directory, for projects outside the project directory [default: detected from the graph]"
+    )]
+    workspace_root: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Always run tuist graph instead of reusing the cached graph"
+    )]
+    no_cache: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Read the Tuist graph from this JSON file, or stdin for -, instead of running tuist graph"
+    )]
+    graph: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Don't add the generated files to the project's .gitignore"
+    )]
+    no_gitignore: bool,
+
+    #[arg(
+        long,
+        help = "Declare rules_xcodeproj and add an xcodeproj target for the apps to the root BUILD"
+    )]
+    with_xcodeproj: bool,
+
+    #[arg(
+        long,
+        help = "Fail instead of warning when files listed in the Tuist graph don't exist"
+    )]
+    strict: bool,
+
+    #[arg(
+        long,
+        help = "Fail after generating when any warning was printed, e.g. for CI"
+    )]
+    fail_on_warnings: bool,
+
+    #[arg(
+        long,
+        help = "Generate the other projects when some fail, and list the failures at the end"
+    )]
+    keep_going: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Bazel executable to run, e.g. bazelisk [default: bazel]"
+    )]
+    bazel: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Tuist executable to run [default: tuist]"
+    )]
+    tuist: Option<PathBuf>,
+
+    #[arg(
+        long,
+        help = "Format the generated BUILD and WORKSPACE files with buildifier, when it's installed"
+    )]
+    format_build_files: bool,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "buildifier executable to format with [default: buildifier]"
+    )]
+    buildifier: Option<PathBuf>,
+
+    #[arg(
+        long,
+        value_name = "SECONDS",
+        help = "Kill a tuist, bazel, or simctl command that runs longer than this; 0 waits as long as it takes [default: 0]"
+    )]
+    command_timeout: Option<u64>,
+
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Run at most N Bazel jobs, and generate BUILD files on at most N threads [default: Bazel's]"
+    )]
+    jobs: Option<u32>,
+
+    #[arg(
+        long = "define",
+        value_name = "KEY=VALUE",
+        value_parser = parse_key_value,
+        help = "Pass --define=KEY=VALUE to bazel build and test, for select()s on it (repeatable)"
+    )]
+    defines: Vec<(String, String)>,
+
+    #[arg(
+        long = "exclude-project",
+        value_name = "PATTERN",
+        help = "Write no BUILD file for the projects whose name or package path matches this glob (repeatable)"
+    )]
+    exclude_projects: Vec<String>,
+
+    #[arg(
+        long = "exclude-target",
+        value_name = "PATTERN",
+        help = "Generate no rules for the targets whose name matches this glob (repeatable)"
+    )]
+    exclude_targets: Vec<String>,
+
+    #[arg(
+        long,
+        value_name = "PATH",
+        help = "Pass --output_base=PATH to Bazel, so concurrent builds on one machine don't share an output base [default: Bazel's]"
+    )]
+    bazel_output_base: Option<PathBuf>,
 }
 
-fn generate_bazel_files(graph: &TuistGraph, project_dir: &Path, cache_dir: &Path) -> Result<()> {
-    println!("Generating Bazel files...");
-
-    // Generate WORKSPACE file
-    generate_workspace_file(project_dir)?;
-
-    // Generate .bazelrc file
-    generate_bazelrc(project_dir)?;
-
-    // Parse projects array - it's [path_string, project_object]
-    if let Some(projects_array) = graph.projects.as_array() {
-        for item in projects_array {
-            // Skip string entries (paths), only process objects (projects)
-            if item.as_object().is_some() {
-                let project: TuistProject = serde_json::from_value(item.clone())
-                    .context("Failed to parse project from graph")?;
+impl ProjectArgs {
+    fn project_dir(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(|| PathBuf::from("."))
+    }
 
-                println!("Generating BUILD file for project: {}", project.name);
-                generate_build_file(&project, project_dir)?;
-            }
+    /// The Tuist projects to convert: the one at the path, or with a path
+    /// that has no manifest, every one found below it. A graph read from a
+    /// file belongs to the path.
+    fn roots(&self, single: bool) -> Result<Vec<PathBuf>> {
+        let project_dir = self.project_dir();
+        if single || self.graph.is_some() {
+            return Ok(vec![project_dir]);
         }
+        discover_project_roots(&project_dir)
     }
 
-    // Save graph metadata to cache
-    let graph_cache_path = cache_dir.join("graph.json");
-    let graph_json = serde_json::to_string_pretty(graph)?;
-    fs::write(&graph_cache_path, graph_json).context("Failed to write graph cache")?;
-
-    println!("Saved graph metadata to: {}", graph_cache_path.display());
+    /// Resolves the generation options: flags win over `catalyst.toml`, which
+    /// wins over the built-in defaults.
+    fn options(&self, config: &Config) -> GenerateOptions {
+        let configured = GenerateOptions::from_config(config);
+
+        GenerateOptions {
+            bazel_mode: self.bazel_mode.unwrap_or(configured.bazel_mode),
+            build_file_name: self.build_file_name.unwrap_or(configured.build_file_name),
+            minimum_os_version: self
+                .minimum_os_version
+                .clone()
+                .unwrap_or(configured.minimum_os_version),
+            configuration: self.configuration.unwrap_or(configured.configuration),
+            platform: self.platform.or(configured.platform),
+            jobs: self.jobs,
+            // Flags replace the value catalyst.toml gives the same key
+            defines: configured
+                .defines
+                .clone()
+                .into_iter()
+                .chain(self.defines.iter().cloned())
+                .collect(),
+            output_dir: self.output_dir.clone(),
+            // Relative to the current directory, unlike the one in catalyst.toml
+            workspace_root: self
+                .workspace_root
+                .as_ref()
+                .map(|root| std::path::absolute(root).unwrap_or_else(|_| root.clone()))
+                .or(configured.workspace_root),
+            no_cache: self.no_cache,
+            graph: self.graph.clone(),
+            update_gitignore: !self.no_gitignore,
+            with_xcodeproj: self.with_xcodeproj,
+            strict: self.strict,
+            keep_going: self.keep_going,
+            fail_on_warnings: self.fail_on_warnings,
+            bazel: self.bazel.clone().unwrap_or(configured.bazel),
+            tuist: self.tuist.clone().unwrap_or(configured.tuist),
+            buildifier: self.buildifier.clone().unwrap_or(configured.buildifier),
+            format_build_files: self.format_build_files || configured.format_build_files,
+            // Flags add to the ones catalyst.toml excludes
+            exclude_projects: [
+                configured.exclude_projects.as_slice(),
+                &self.exclude_projects,
+            ]
+            .concat(),
+            exclude_targets: [configured.exclude_targets.as_slice(), &self.exclude_targets]
+                .concat(),
+            // Relative to the current directory, as Bazel runs elsewhere
+            bazel_output_base: self
+                .bazel_output_base
+                .as_ref()
+                .map(|base| std::path::absolute(base).unwrap_or_else(|_| base.clone())),
+            ..configured
+        }
+    }
 
-    Ok(())
+    /// Applies the command timeout from the flag or `catalyst.toml`.
+    fn set_command_timeout(&self, config: &Config) {
+        set_command_timeout(command_timeout(
+            self.command_timeout.or(config.command_timeout),
+        ));
+    }
 }
 
-fn generate_workspace_file(project_dir: &Path) -> Result<()> {
-    let workspace_path = project_dir.join("WORKSPACE");
-
-    let workspace_content = r#"workspace(name = "catalyst_workspace")
-
-# Load Apple rules for building iOS/macOS apps
-load("@bazel_tools//tools/build_defs/repo:http.bzl", "http_archive")
-
-http_archive(
-    name = "build_bazel_rules_apple",
-    sha256 = "b4df908ec14868369021182ab191dbd1f40830c9b300650d5dc389e0b9266c8d",
-    url = "https://github.com/bazelbuild/rules_apple/releases/download/3.5.1/rules_apple.3.5.1.tar.gz",
-)
-
-load(
-    "@build_bazel_rules_apple//apple:repositories.bzl",
-    "apple_rules_dependencies",
-)
-
-apple_rules_dependencies()
-
-load(
-    "@build_bazel_rules_swift//swift:repositories.bzl",
-    "swift_rules_dependencies",
-)
-
-swift_rules_dependencies()
-
-load(
-    "@build_bazel_rules_swift//swift:extras.bzl",
-    "swift_rules_extra_dependencies",
-)
-
-swift_rules_extra_dependencies()
-
-load(
-    "@build_bazel_apple_support//lib:repositories.bzl",
-    "apple_support_dependencies",
-)
-
-apple_support_dependencies()
-
-# Optional: rules_xcodeproj for generating Xcode projects from Bazel targets
-# Uncomment the following to enable Xcode project generation:
-#
-# http_archive(
-#     name = "com_github_buildbuddy_io_rules_xcodeproj",
-#     sha256 = "CHECK LATEST RELEASE",
-#     url = "https://github.com/MobileNativeFoundation/rules_xcodeproj/releases/download/VERSION/release.tar.gz",
-# )
-#
-# load(
-#     "@com_github_buildbuddy_io_rules_xcodeproj//xcodeproj:repositories.bzl",
-#     "xcodeproj_rules_dependencies",
-# )
-#
-# xcodeproj_rules_dependencies()
-#
-# Then add to your BUILD file:
-# load("@com_github_buildbuddy_io_rules_xcodeproj//xcodeproj:defs.bzl", "xcodeproj")
-#
-# xcodeproj(
-#     name = "xcodeproj",
-#     project_name = "Fixture",
-#     targets = [":fixture"],
-# )
-"#;
-
-    std::fs::write(&workspace_path, workspace_content).context("Failed to write WORKSPACE file")?;
-
-    println!("Generated: {}", workspace_path.display());
-
-    Ok(())
+/// Ctrl-C as messages, for the commands that stop what they're doing on it
+/// rather than exit. A process can only install one handler, so it's done
+/// here rather than in the library.
+fn interrupts() -> Result<mpsc::Receiver<()>> {
+    let (sender, interrupts) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = sender.send(());
+    })
+    .context("Failed to install Ctrl-C handler")?;
+    Ok(interrupts)
 }
 
-fn generate_bazelrc(project_dir: &Path) -> Result<()> {
-    let bazelrc_path = project_dir.join(".bazelrc");
-
-    let bazelrc_content = r#"# Build settings
-build --apple_platform_type=ios
-build --ios_minimum_os=15.0
-
-# Use Xcode toolchain
-build --apple_crosstool_top=@local_config_apple_cc//:toolchain
-build --crosstool_top=@local_config_apple_cc//:toolchain
-build --host_crosstool_top=@local_config_apple_cc//:toolchain
-
-# Output settings
-build --verbose_failures
-build --announce_rc
-"#;
+/// The command timeout for a number of seconds, where 0 means none.
+fn command_timeout(seconds: Option<u64>) -> Option<Duration> {
+    seconds
+        .filter(|&seconds| seconds > 0)
+        .map(Duration::from_secs)
+}
 
-    std::fs::write(&bazelrc_path, bazelrc_content).context("Failed to write .bazelrc file")?;
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    set_verbosity(if cli.quiet {
+        Verbosity::Quiet
+    } else if cli.verbose {
+        Verbosity::Verbose
+    } else {
+        Verbosity::Normal
+    });
+    if cli.dry_run && cli.format == OutputFormat::Json {
+        anyhow::bail!("--dry-run can't be combined with --format json");
+    }
+    // explain-target renders its rules into a temporary directory, which
+    // --dry-run would leave empty; it writes nothing to the project anyway
+    set_dry_run(cli.dry_run && !matches!(cli.command, Some(Commands::ExplainTarget { .. })));
+    set_timings(cli.timings);
+    set_cache_dir(cli.cache_dir);
 
-    println!("Generated: {}", bazelrc_path.display());
+    let format = cli.format;
+    let started = Instant::now();
+    let mut report = BuildReport::default();
 
-    Ok(())
-}
+    match cli.command {
+        Some(Commands::Build {
+            project,
+            force,
+            single,
+            watch,
+            bazel_args,
+        }) => {
+            if watch && format == OutputFormat::Json {
+                anyhow::bail!("--watch can't be combined with --format json");
+            }
+            if watch && project.graph.is_some() {
+                anyhow::bail!("--watch can't be combined with --graph");
+            }
 
-fn generate_build_file(project: &TuistProject, project_dir: &Path) -> Result<()> {
-    let mut build_content = String::new();
-
-    build_content.push_str("load(\"@build_bazel_rules_apple//apple:ios.bzl\", \"ios_application\", \"ios_unit_test\")\n");
-    build_content
-        .push_str("load(\"@build_bazel_rules_swift//swift:swift.bzl\", \"swift_library\")\n\n");
-
-    for target in project.targets.values() {
-        let target_name_lower = target.name.to_lowercase();
-
-        // Extract Swift source files from buildableFolders
-        let mut source_files: Vec<String> = Vec::new();
-        let mut resource_files: Vec<String> = Vec::new();
-
-        for folder in &target.buildable_folders {
-            for file in &folder.resolved_files {
-                let file_path = Path::new(&file.path);
-                if let Some(ext) = file_path.extension() {
-                    let ext_str = ext.to_string_lossy();
-                    if ext_str == "swift" {
-                        // Make path relative to project directory
-                        if let Ok(rel_path) = Path::new(&file.path).strip_prefix(&project.path) {
-                            source_files.push(format!("\"{}\"", rel_path.display()));
-                        }
-                    } else if ext_str == "xcassets" || ext_str == "storyboard" || ext_str == "xib" {
-                        if let Ok(rel_path) = Path::new(&file.path).strip_prefix(&project.path) {
-                            resource_files.push(format!("\"{}\"", rel_path.display()));
-                        }
-                    }
-                }
+            if watch {
+                let project_dir = project.project_dir();
+                let config = Config::load(&project_dir)?;
+                project.set_command_timeout(&config);
+                let options = GenerateOptions {
+                    bazel_args,
+                    force,
+                    ..project.options(&config)
+                };
+                return watch_project(&project_dir, &options, interrupts()?);
             }
+            let roots = project.roots(single)?;
+            let result = for_each_project_root(&roots, &mut report, |root, report| {
+                let config = Config::load(root)?;
+                project.set_command_timeout(&config);
+                let options = GenerateOptions {
+                    bazel_args: bazel_args.clone(),
+                    force,
+                    ..project.options(&config)
+                };
+                build_project(root, &options, report)
+            });
+            report.finish(format, started, result)?;
         }
-
-        // Get dependencies
-        let deps: Vec<String> = target
-            .dependencies
-            .iter()
-            .filter_map(|dep| {
-                dep.target
-                    .as_ref()
-                    .map(|t| format!("\":{}\"", t.name.to_lowercase()))
-            })
-            .collect();
-
-        match target.product.as_str() {
-            "app" => {
-                // Generate swift_library for the app sources
-                build_content.push_str(&format!(
-                    "swift_library(\n    name = \"{}_lib\",\n",
-                    target_name_lower
-                ));
-
-                if !source_files.is_empty() {
-                    build_content.push_str(&format!(
-                        "    srcs = [\n        {},\n    ],\n",
-                        source_files.join(",\n        ")
-                    ));
-                } else {
-                    build_content.push_str("    srcs = glob([\"Fixture/Sources/**/*.swift\"]),\n");
-                }
-
-                build_content.push_str(&format!("    module_name = \"{}\",\n", target.name));
-
-                if !deps.is_empty() {
-                    build_content.push_str(&format!("    deps = [{}],\n", deps.join(", ")));
-                }
-
-                build_content.push_str("    visibility = [\"//visibility:public\"],\n)\n\n");
-
-                // Generate minimal Info.plist for the app
-                let infoplist_path = project_dir.join(format!("{}-Info.plist", target.name));
-                let infoplist_content = format!(
-                    r#"<?xml version="1.0" encoding="UTF-8"?>
-<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
-<plist version="1.0">
-<dict>
-    <key>CFBundleDevelopmentRegion</key>
-    <string>en</string>
-    <key>CFBundleExecutable</key>
-    <string>{}</string>
-    <key>CFBundleIdentifier</key>
-    <string>{}</string>
-    <key>CFBundleInfoDictionaryVersion</key>
-    <string>6.0</string>
-    <key>CFBundleName</key>
-    <string>{}</string>
-    <key>CFBundlePackageType</key>
-    <string>APPL</string>
-    <key>CFBundleShortVersionString</key>
-    <string>1.0</string>
-    <key>CFBundleVersion</key>
-    <string>1</string>
-    <key>LSRequiresIPhoneOS</key>
-    <true/>
-    <key>UILaunchScreen</key>
-    <dict/>
-</dict>
-</plist>
-"#,
-                    target.name, target.bundle_id, target.name
+        Some(Commands::Run {
+            project,
+            simulator,
+            simulator_os,
+            ephemeral_simulator,
+            target,
+            logs,
+            skip_build,
+            skip_install,
+            install_only,
+            launch_only,
+            force,
+            boot_timeout,
+            env,
+            args,
+            bazel_args,
+        }) => {
+            if logs && format == OutputFormat::Json {
+                anyhow::bail!("--logs can't be combined with --format json");
+            }
+            if project.platform == Some(BuildPlatform::IosDevice) {
+                anyhow::bail!(
+                    "catalyst run builds for the simulator, so it can't take --platform ios-device"
                 );
-
-                fs::write(&infoplist_path, infoplist_content)
-                    .context("Failed to write Info.plist")?;
-
-                // Generate ios_application
-                build_content.push_str(&format!(
-                    "ios_application(\n    name = \"{}\",\n    bundle_id = \"{}\",\n",
-                    target_name_lower, target.bundle_id
-                ));
-                build_content.push_str("    families = [\"iphone\", \"ipad\"],\n");
-                build_content.push_str(&format!(
-                    "    infoplists = [\"{}-Info.plist\"],\n",
-                    target.name
-                ));
-                build_content.push_str("    minimum_os_version = \"15.0\",\n");
-                build_content.push_str(&format!("    deps = [\":{}_lib\"],\n", target_name_lower));
-                build_content.push_str(")\n\n");
             }
-            "unit_tests" => {
-                // Generate test target
-                build_content.push_str(&format!(
-                    "swift_library(\n    name = \"{}_lib\",\n",
-                    target_name_lower
-                ));
-
-                if !source_files.is_empty() {
-                    build_content.push_str(&format!(
-                        "    srcs = [\n        {},\n    ],\n",
-                        source_files.join(",\n        ")
-                    ));
-                } else {
-                    build_content.push_str("    srcs = glob([\"Fixture/Tests/**/*.swift\"]),\n");
-                }
-
-                build_content.push_str(&format!("    module_name = \"{}\",\n", target.name));
-                build_content.push_str("    testonly = True,\n");
 
-                if !deps.is_empty() {
-                    build_content.push_str(&format!("    deps = [{}],\n", deps.join(", ")));
+            let project_dir = project.project_dir();
+            let config = Config::load(&project_dir)?;
+            project.set_command_timeout(&config);
+            // The app runs in the simulator, whatever catalyst.toml builds for
+            let options = GenerateOptions {
+                bazel_args,
+                force,
+                platform: Some(BuildPlatform::IosSimulator),
+                ..project.options(&config)
+            };
+            let steps = RunSteps {
+                build: !(skip_build || launch_only),
+                install: !(skip_install || launch_only),
+                launch: !install_only,
+            };
+            let built = if steps.build {
+                build_project(&project_dir, &options, &mut report)
+            } else {
+                Ok(())
+            };
+            let result = built.and_then(|()| {
+                if cli.dry_run {
+                    return Ok(());
+                }
+                let simulator = SimulatorQuery {
+                    device: simulator
+                        .or(config.simulator)
+                        .unwrap_or_else(|| DEFAULT_SIMULATOR.to_string()),
+                    os: simulator_os.or(config.simulator_os),
+                    ephemeral: ephemeral_simulator,
+                };
+
+                // Get target info from the graph (fresh in the cache after a build)
+                let graph = load_graph(&project_dir, &get_catalyst_cache_dir()?, &options, false)?;
+                let (label, bundle_id) =
+                    find_app_target(&graph, &project_dir, target.as_deref(), &options)?;
+
+                let (device, pid) = run_in_simulator(
+                    &options.bazel,
+                    &options.workspace_dir(&project_dir, &graph),
+                    &label,
+                    &bundle_id,
+                    &simulator,
+                    &LaunchOptions {
+                        boot_timeout: Duration::from_secs(boot_timeout),
+                        env,
+                        args,
+                        steps,
+                        bazel_startup_args: options.bazel_startup_args(),
+                    },
+                    &options.bazel_build_args(),
+                )?;
+                report.launched_pid = pid;
+
+                let Some(pid) = pid else {
+                    if format == OutputFormat::Human {
+                        println!("\n✓ App installed successfully!");
+                    }
+                    return Ok(());
+                };
+                if format == OutputFormat::Human {
+                    println!("\n✓ App launched successfully!");
+                    println!("Process ID: {}", pid);
                 }
 
-                build_content.push_str("    visibility = [\"//visibility:public\"],\n)\n\n");
-
-                // Generate ios_unit_test
-                let test_host = target.name.replace("Tests", "").to_lowercase();
-                build_content.push_str(&format!(
-                    "ios_unit_test(\n    name = \"{}\",\n    bundle_id = \"{}\",\n",
-                    target_name_lower, target.bundle_id
-                ));
-                build_content.push_str("    minimum_os_version = \"15.0\",\n");
-                build_content.push_str(&format!("    test_host = \":{}\",\n", test_host));
-                build_content.push_str(&format!("    deps = [\":{}_lib\"],\n", target_name_lower));
-                build_content.push_str(")\n\n");
-            }
-            _ => {
-                // Default to library
-                build_content.push_str(&format!(
-                    "swift_library(\n    name = \"{}\",\n",
-                    target_name_lower
-                ));
-
-                if !source_files.is_empty() {
-                    build_content.push_str(&format!(
-                        "    srcs = [\n        {},\n    ],\n",
-                        source_files.join(",\n        ")
-                    ));
+                if logs {
+                    stream_app_logs(&device, pid, &interrupts()?)
+                } else if ephemeral_simulator {
+                    // Deleting the simulator stops the app
+                    eprintln!("\nPress Ctrl-C to stop the app and delete the simulator");
+                    wait_for_interrupt(&interrupts()?)
                 } else {
-                    build_content.push_str("    srcs = glob([\"Sources/**/*.swift\"]),\n");
+                    if verbosity() >= Verbosity::Normal {
+                        eprintln!(
+                            "\nTip: Open Simulator.app to see the running app, or pass --logs"
+                        );
+                    }
+                    Ok(())
                 }
-
-                build_content.push_str(&format!("    module_name = \"{}\",\n", target.name));
-
-                if !deps.is_empty() {
-                    build_content.push_str(&format!("    deps = [{}],\n", deps.join(", ")));
+            });
+            report.finish(format, started, result)?;
+        }
+        Some(Commands::Test {
+            project,
+            target,
+            bazel_args,
+        }) => {
+            let project_dir = project.project_dir();
+            let config = Config::load(&project_dir)?;
+            project.set_command_timeout(&config);
+            let options = GenerateOptions {
+                bazel_args,
+                ..project.options(&config)
+            };
+            let result = (|| {
+                report.files_written = generate_project(&project_dir, &options, &mut report)?;
+                if cli.dry_run {
+                    return Ok(());
                 }
 
-                build_content.push_str("    visibility = [\"//visibility:public\"],\n)\n\n");
-            }
+                let graph = load_graph(&project_dir, &get_catalyst_cache_dir()?, &options, false)?;
+                let label = match target {
+                    Some(target) => find_test_label(&graph, &target, &project_dir, &options)?,
+                    None => "//...".to_string(),
+                };
+                run_bazel_test(
+                    &options.bazel,
+                    &options.bazel_startup_args(),
+                    &options.workspace_dir(&project_dir, &graph),
+                    &label,
+                    &options.bazel_build_args(),
+                    &mut report,
+                )
+            })();
+            report.finish(format, started, result)?;
         }
-    }
-
-    let build_path = project_dir.join("BUILD");
-    fs::write(&build_path, build_content).context("Failed to write BUILD file")?;
-
-    println!("Generated: {}", build_path.display());
-
-    Ok(())
-}
-
-fn run_bazel_build(project_dir: &Path) -> Result<()> {
-    println!("\nRunning Bazel build...");
-
-    let status = Command::new("bazel")
-        .args(["build", "//..."])
-        .current_dir(project_dir)
-        .status()
-        .context("Failed to execute bazel build")?;
-
-    if !status.success() {
-        anyhow::bail!("Bazel build failed");
-    }
-
-    Ok(())
-}
-
-fn find_app_target(graph: &TuistGraph, target_hint: Option<&str>) -> Result<(String, String)> {
-    // Parse projects array to find app targets
-    if let Some(projects_array) = graph.projects.as_array() {
-        for item in projects_array {
-            if item.as_object().is_some() {
-                let project: TuistProject = serde_json::from_value(item.clone())
-                    .context("Failed to parse project from graph")?;
-
-                for (key, target) in &project.targets {
-                    if target.product == "app" {
-                        // If target hint provided, match it
-                        if let Some(hint) = target_hint {
-                            if key.to_lowercase() == hint.to_lowercase() {
-                                return Ok((key.to_lowercase(), target.bundle_id.clone()));
-                            }
-                        } else {
-                            // Return first app target found
-                            return Ok((key.to_lowercase(), target.bundle_id.clone()));
-                        }
+        Some(Commands::Generate {
+            project,
+            force,
+            single,
+        }) => {
+            let roots = project.roots(single)?;
+            let result = for_each_project_root(&roots, &mut report, |root, report| {
+                let config = Config::load(root)?;
+                project.set_command_timeout(&config);
+                let options = GenerateOptions {
+                    force,
+                    ..project.options(&config)
+                };
+                report.files_written = generate_project(root, &options, report)?;
+                Ok(())
+            });
+
+            if format == OutputFormat::Human && result.is_ok() {
+                println!(
+                    "\n{}",
+                    if cli.dry_run {
+                        "Files that would be written:"
+                    } else {
+                        "Generated files:"
                     }
+                );
+                for path in &report.files_written {
+                    println!("  {}", path.display());
                 }
             }
+            report.finish(format, started, result)?;
+        }
+        Some(Commands::AddTarget { project, target }) => {
+            let project_dir = project.project_dir();
+            let config = Config::load(&project_dir)?;
+            project.set_command_timeout(&config);
+            let options = GenerateOptions {
+                add_target: Some(target),
+                ..project.options(&config)
+            };
+            let result = generate_project(&project_dir, &options, &mut report)
+                .map(|files| report.files_written = files);
+            report.finish(format, started, result)?;
+        }
+        Some(Commands::ListTargets { path, json, tuist }) => {
+            let project_dir = path.unwrap_or_else(|| PathBuf::from("."));
+            let config = Config::load(&project_dir)?;
+            set_command_timeout(command_timeout(config.command_timeout));
+            let tuist = tuist
+                .or(config.tuist)
+                .unwrap_or_else(|| PathBuf::from("tuist"));
+            list_targets(&project_dir, &tuist, json || format == OutputFormat::Json)?;
+        }
+        Some(command) if format == OutputFormat::Json => {
+            anyhow::bail!(
+                "--format json isn't supported by `catalyst {}`",
+                command.name()
+            );
+        }
+        Some(Commands::ExplainTarget { project, target }) => {
+            let project_dir = project.project_dir();
+            let config = Config::load(&project_dir)?;
+            project.set_command_timeout(&config);
+            explain_target(&project_dir, &target, &project.options(&config))?;
+        }
+        Some(Commands::ShowCache { path, contents }) => {
+            let project_dir = path.unwrap_or_else(|| PathBuf::from("."));
+            show_cache(&project_dir, contents)?;
+        }
+        Some(Commands::Completions { shell }) => {
+            clap_complete::generate(
+                shell,
+                &mut Cli::command(),
+                "catalyst",
+                &mut std::io::stdout(),
+            );
+        }
+        Some(Commands::Doctor) => {
+            run_doctor()?;
+        }
+        Some(Commands::Init { path, force }) => {
+            let project_dir = path.unwrap_or_else(|| PathBuf::from("."));
+            init_project(&project_dir, force)?;
+        }
+        Some(Commands::Clean { path, cache }) => {
+            let project_dir = path.unwrap_or_else(|| PathBuf::from("."));
+            clean_project(&project_dir, cli.dry_run, cache)?;
+        }
+        None => {
+            // Default behavior: build
+            let project = ProjectArgs::default();
+            let roots = project.roots(false)?;
+            let result = for_each_project_root(&roots, &mut report, |root, report| {
+                let config = Config::load(root)?;
+                project.set_command_timeout(&config);
+                build_project(root, &project.options(&config), report)
+            });
+            report.finish(format, started, result)?;
         }
     }
 
-    anyhow::bail!("No app target found in project")
-}
-
-fn run_in_simulator(
-    project_dir: &Path,
-    target_name: &str,
-    bundle_id: &str,
-    simulator: &str,
-) -> Result<()> {
-    println!("\n=== Launching App in Simulator ===");
-
-    // Build the specific target with Bazel
-    println!("Building target: {}", target_name);
-    let build_status = Command::new("bazel")
-        .args(["build", &format!(":{}", target_name)])
-        .current_dir(project_dir)
-        .status()
-        .context("Failed to build target with Bazel")?;
-
-    if !build_status.success() {
-        anyhow::bail!("Bazel build failed for target {}", target_name);
-    }
-
-    // Boot simulator (ignore errors if already booted)
-    println!("Booting simulator: {}", simulator);
-    let _ = Command::new("xcrun")
-        .args(["simctl", "boot", simulator])
-        .status();
-
-    // Wait a moment for simulator to boot
-    std::thread::sleep(std::time::Duration::from_secs(2));
-
-    // Get IPA path
-    let ipa_path = project_dir.join(format!("bazel-bin/{}.ipa", target_name));
-
-    if !ipa_path.exists() {
-        anyhow::bail!("IPA not found at: {}", ipa_path.display());
-    }
-
-    // Install the app
-    println!("Installing app: {}", ipa_path.display());
-    let install_status = Command::new("xcrun")
-        .args(["simctl", "install", "booted", ipa_path.to_str().unwrap()])
-        .status()
-        .context("Failed to install app on simulator")?;
-
-    if !install_status.success() {
-        anyhow::bail!("Failed to install app on simulator");
-    }
-
-    // Launch the app
-    println!("Launching app: {}", bundle_id);
-    let launch_output = Command::new("xcrun")
-        .args(["simctl", "launch", "booted", bundle_id])
-        .output()
-        .context("Failed to launch app")?;
-
-    if !launch_output.status.success() {
-        anyhow::bail!(
-            "Failed to launch app: {}",
-            String::from_utf8_lossy(&launch_output.stderr)
-        );
-    }
-
-    let output_str = String::from_utf8_lossy(&launch_output.stdout);
-    println!("\n✓ App launched successfully!");
-    println!("Process ID: {}", output_str.trim());
-    println!("\nTip: Open Simulator.app to see the running app");
-
     Ok(())
 }
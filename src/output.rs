@@ -0,0 +1,221 @@
+//! Progress output: verbosity, warnings, phase timings, and the progress bar.
+
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::Serialize;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How much progress output catalyst writes to stderr. Command results, like
+/// the `list-targets` output, always go to stdout.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Verbosity {
+    /// Errors only
+    Quiet,
+    Normal,
+    /// Also the full command lines catalyst runs
+    Verbose,
+}
+
+static VERBOSITY: AtomicU8 = AtomicU8::new(Verbosity::Normal as u8);
+
+pub fn verbosity() -> Verbosity {
+    match VERBOSITY.load(Ordering::Relaxed) {
+        0 => Verbosity::Quiet,
+        1 => Verbosity::Normal,
+        _ => Verbosity::Verbose,
+    }
+}
+
+pub fn set_verbosity(verbosity: Verbosity) {
+    VERBOSITY.store(verbosity as u8, Ordering::Relaxed);
+}
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+/// Whether `--dry-run` is set: generated files are printed, as a diff
+/// against the existing file if there is one, instead of being written,
+/// and Bazel isn't invoked.
+pub fn dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+/// Progress output, hidden by `--quiet`.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::Normal {
+            $crate::output::suspend_progress(|| eprintln!($($arg)*));
+        }
+    };
+}
+
+/// Diagnostic output, shown only with `--verbose`.
+macro_rules! verbose {
+    ($($arg:tt)*) => {
+        if $crate::verbosity() >= $crate::Verbosity::Verbose {
+            $crate::output::suspend_progress(|| eprintln!($($arg)*));
+        }
+    };
+}
+
+/// A problem that doesn't stop catalyst, hidden by `--quiet`. Every warning
+/// is also recorded for the build summary.
+macro_rules! warning {
+    ($($arg:tt)*) => {{
+        let message = format!($($arg)*);
+        if $crate::verbosity() >= $crate::Verbosity::Normal {
+            $crate::output::suspend_progress(|| eprintln!("Warning: {}", message));
+        }
+        $crate::output::record_warning(message);
+    }};
+}
+
+/// The warnings printed since they were last taken.
+static WARNINGS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+pub(crate) fn record_warning(message: String) {
+    WARNINGS
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .push(message);
+}
+
+/// Returns the warnings printed since the last call, in order, and forgets
+/// them.
+pub fn take_warnings() -> Vec<String> {
+    std::mem::take(&mut *WARNINGS.lock().unwrap_or_else(|err| err.into_inner()))
+}
+
+pub(crate) static TIMINGS_ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// The phases timed since they were last taken, with `--timings`.
+static TIMINGS: Mutex<Vec<PhaseTiming>> = Mutex::new(Vec::new());
+
+/// How long a phase of the run took, like `tuist graph` or `bazel build`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PhaseTiming {
+    pub phase: &'static str,
+    pub secs: f64,
+}
+
+/// Whether `--timings` is set: the durations of the phases of the run are
+/// recorded, and printed at the end.
+pub fn set_timings(enabled: bool) {
+    TIMINGS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns the phases timed since the last call, in the order they ended,
+/// and forgets them.
+pub fn take_timings() -> Vec<PhaseTiming> {
+    std::mem::take(&mut *TIMINGS.lock().unwrap_or_else(|err| err.into_inner()))
+}
+
+/// Times a phase from its start until it's dropped, so a phase that fails
+/// is timed too.
+pub(crate) struct PhaseTimer {
+    phase: &'static str,
+    started: Instant,
+}
+
+impl PhaseTimer {
+    pub(crate) fn start(phase: &'static str) -> Self {
+        Self {
+            phase,
+            started: Instant::now(),
+        }
+    }
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        if TIMINGS_ENABLED.load(Ordering::Relaxed) {
+            TIMINGS
+                .lock()
+                .unwrap_or_else(|err| err.into_inner())
+                .push(PhaseTiming {
+                    phase: self.phase,
+                    secs: self.started.elapsed().as_secs_f64(),
+                });
+        }
+    }
+}
+
+/// The spinner or progress bar drawn on stderr, if any.
+static PROGRESS: Mutex<Option<ProgressBar>> = Mutex::new(None);
+
+/// Runs `print` with the progress bar, if one is drawn, cleared, so output
+/// lands above the bar rather than through it.
+pub(crate) fn suspend_progress<R>(print: impl FnOnce() -> R) -> R {
+    let progress = PROGRESS.lock().unwrap_or_else(|err| err.into_inner());
+    match progress.as_ref() {
+        Some(bar) => bar.suspend(print),
+        None => print(),
+    }
+}
+
+/// A spinner or progress bar on stderr for as long as it's alive. Nothing is
+/// drawn with `--quiet` or when stderr isn't a terminal, where the plain
+/// progress lines are all there is.
+pub(crate) struct Progress(Option<ProgressBar>);
+
+impl Progress {
+    /// A spinner with the elapsed time, for a long-running command.
+    pub(crate) fn spinner(message: &str) -> Self {
+        Self::draw(|| {
+            let spinner = ProgressBar::new_spinner().with_message(message.to_string());
+            spinner.set_style(
+                ProgressStyle::with_template("{spinner} {msg} ({elapsed})")
+                    .expect("valid progress template"),
+            );
+            spinner.enable_steady_tick(Duration::from_millis(100));
+            spinner
+        })
+    }
+
+    /// A bar counting `len` steps, e.g. `Generating project 47/120`.
+    pub(crate) fn bar(len: usize, prefix: &str) -> Self {
+        Self::draw(|| {
+            ProgressBar::new(len as u64)
+                .with_prefix(prefix.to_string())
+                .with_style(
+                    ProgressStyle::with_template("{prefix} {pos}/{len} {wide_msg}")
+                        .expect("valid progress template"),
+                )
+        })
+    }
+
+    fn draw(create: impl FnOnce() -> ProgressBar) -> Self {
+        if verbosity() < Verbosity::Normal || !std::io::stderr().is_terminal() {
+            return Self(None);
+        }
+        let bar = create();
+        *PROGRESS.lock().unwrap_or_else(|err| err.into_inner()) = Some(bar.clone());
+        Self(Some(bar))
+    }
+
+    pub(crate) fn is_drawn(&self) -> bool {
+        self.0.is_some()
+    }
+
+    /// Counts a finished step and names it.
+    pub(crate) fn advance(&self, step: &str) {
+        if let Some(bar) = &self.0 {
+            bar.set_message(step.to_string());
+            bar.inc(1);
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        if let Some(bar) = self.0.take() {
+            bar.finish_and_clear();
+            *PROGRESS.lock().unwrap_or_else(|err| err.into_inner()) = None;
+        }
+    }
+}
@@ -0,0 +1,366 @@
+//! Info.plist and entitlements files of the generated bundles.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::config::Platform;
+use crate::graph::{TuistProject, TuistTarget};
+use crate::starlark::starlark_string;
+use crate::workspace::write_generated_file;
+
+/// The kind of bundle an Info.plist is synthesized for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum PlistKind<'a> {
+    /// An app, with the `info_plist_template` to fill in, if any
+    App(Option<&'a str>),
+    Extension,
+    Framework,
+}
+
+/// The name of the `config_setting` that matches the Debug configuration,
+/// which `--config=debug` selects through `--compilation_mode=dbg`.
+const DEBUG_CONFIG_SETTING: &str = "catalyst_debug";
+
+/// A rule attribute that can differ between the Debug and Release
+/// configurations, like a bundle ID with a `.debug` suffix.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum ConfigurationValue {
+    Same(String),
+    Split { debug: String, release: String },
+}
+
+impl ConfigurationValue {
+    pub(crate) fn new(debug: String, release: String) -> Self {
+        if debug == release {
+            Self::Same(debug)
+        } else {
+            Self::Split { debug, release }
+        }
+    }
+
+    pub(crate) fn is_split(&self) -> bool {
+        matches!(self, Self::Split { .. })
+    }
+
+    /// Renders the value as a string, or a `select()` on the Debug
+    /// `config_setting` when it differs per configuration. Release is the
+    /// default branch, so other compilation modes get the Release value.
+    pub(crate) fn render(&self) -> String {
+        self.render_with(|value| starlark_string(value))
+    }
+
+    /// Like `render`, as a one-element list, e.g. for `infoplists`.
+    pub(crate) fn render_list(&self) -> String {
+        self.render_with(|value| format!("[{}]", starlark_string(value)))
+    }
+
+    fn render_with(&self, render: impl Fn(&str) -> String) -> String {
+        match self {
+            Self::Same(value) => render(value),
+            Self::Split { debug, release } => format!(
+                "select({{\n        {}: {},\n        \"//conditions:default\": {},\n    }})",
+                starlark_string(format!(":{}", DEBUG_CONFIG_SETTING)),
+                render(debug),
+                render(release)
+            ),
+        }
+    }
+}
+
+/// The `config_setting` that `ConfigurationValue` selects on, emitted once in
+/// each package that has a per-configuration value.
+pub(crate) fn debug_config_setting_rule() -> String {
+    format!(
+        "config_setting(\n    name = {},\n    values = {{\"compilation_mode\": \"dbg\"}},\n)\n\n",
+        starlark_string(DEBUG_CONFIG_SETTING)
+    )
+}
+
+/// Returns the Info.plist to put in `infoplists`. The target's own plist is
+/// referenced when the manifest declares one; otherwise a minimal one is
+/// synthesized next to the BUILD file and recorded in `written`, one per
+/// configuration when the bundle ID or display name differs between them.
+pub(crate) fn resolve_info_plist(
+    target: &TuistTarget,
+    project: &TuistProject,
+    package_dir: &Path,
+    kind: PlistKind<'_>,
+    written: &mut Vec<PathBuf>,
+) -> Result<ConfigurationValue> {
+    let project_path = Path::new(&project.path);
+    let existing = target
+        .info_plist_file(project_path)
+        .filter(|path| path.exists())
+        .and_then(|path| {
+            path.strip_prefix(project_path)
+                .ok()
+                .map(|rel| rel.display().to_string())
+        });
+
+    if let Some(path) = existing {
+        return Ok(ConfigurationValue::Same(path));
+    }
+
+    let mut write = |file_name: String, variant: &str| -> Result<String> {
+        let infoplist_path = package_dir.join(&file_name);
+        write_generated_file(
+            &infoplist_path,
+            synthesize_info_plist(target, kind, variant),
+        )
+        .context("Failed to write Info.plist")?;
+        written.push(infoplist_path);
+        Ok(file_name)
+    };
+    if !target.has_configuration_plists() {
+        let file_name = write(format!("{}-Info.plist", target.name), "debug")?;
+        return Ok(ConfigurationValue::Same(file_name));
+    }
+    Ok(ConfigurationValue::Split {
+        debug: write(format!("{}-Debug-Info.plist", target.name), "debug")?,
+        release: write(format!("{}-Release-Info.plist", target.name), "release")?,
+    })
+}
+
+/// Builds a minimal Info.plist for the configuration with the given variant,
+/// carrying over the version strings and display name from the graph when it
+/// exposes them. An app with an `info_plist_template` gets the template with
+/// its placeholders filled in instead.
+fn synthesize_info_plist(target: &TuistTarget, kind: PlistKind<'_>, variant: &str) -> String {
+    let version = target
+        .marketing_version()
+        .unwrap_or_else(|| "1.0".to_string());
+    let build = target.build_version().unwrap_or_else(|| "1".to_string());
+    if let PlistKind::App(Some(template)) = kind {
+        let bundle_id = target.configuration_bundle_id(variant);
+        return INFO_PLIST_PLACEHOLDERS
+            .iter()
+            .zip([bundle_id.as_str(), target.product_name(), &version, &build])
+            .fold(template.to_string(), |plist, (placeholder, value)| {
+                plist.replace(&format!("{{{{{}}}}}", placeholder), &xml_escape(value))
+            });
+    }
+    let display_name = target
+        .display_name(variant)
+        .map(|name| {
+            format!(
+                "    <key>CFBundleDisplayName</key>\n    <string>{}</string>\n",
+                xml_escape(&name)
+            )
+        })
+        .unwrap_or_default();
+
+    let (package_type, extra_keys) = match kind {
+        PlistKind::App(_) => (
+            "APPL",
+            match target.platform() {
+                Platform::Ios => match target.launch_storyboard() {
+                    Some(storyboard) => format!(
+                        "    <key>LSRequiresIPhoneOS</key>\n    <true/>\n    <key>UILaunchStoryboardName</key>\n    <string>{}</string>\n",
+                        storyboard
+                    ),
+                    None => "    <key>LSRequiresIPhoneOS</key>\n    <true/>\n    <key>UILaunchScreen</key>\n    <dict/>\n".to_string(),
+                },
+                Platform::Watchos => "    <key>WKApplication</key>\n    <true/>\n".to_string(),
+                Platform::Macos => String::new(),
+            },
+        ),
+        // The extension point can't be derived from the product, so it has to
+        // come from the NSExtension dictionary of the manifest
+        PlistKind::Extension => {
+            let mut keys = String::new();
+            match target.info_plist_entry("NSExtension") {
+                Some(extension) => {
+                    keys.push_str("    <key>NSExtension</key>\n");
+                    render_plist_value(extension, 1, &mut keys);
+                }
+                None => warning!(
+                    "{} has no NSExtension entry in its Info.plist; the extension won't load",
+                    target.name
+                ),
+            }
+            ("XPC!", keys)
+        }
+        PlistKind::Framework => ("FMWK", String::new()),
+    };
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+    <key>CFBundleDevelopmentRegion</key>
+    <string>en</string>
+{display_name}    <key>CFBundleExecutable</key>
+    <string>{name}</string>
+    <key>CFBundleIdentifier</key>
+    <string>{bundle_id}</string>
+    <key>CFBundleInfoDictionaryVersion</key>
+    <string>6.0</string>
+    <key>CFBundleName</key>
+    <string>{name}</string>
+    <key>CFBundlePackageType</key>
+    <string>{package_type}</string>
+    <key>CFBundleShortVersionString</key>
+    <string>{version}</string>
+    <key>CFBundleVersion</key>
+    <string>{build}</string>
+{extra_keys}</dict>
+</plist>
+"#,
+        name = xml_escape(target.product_name()),
+        bundle_id = target.configuration_bundle_id(variant),
+    )
+}
+
+/// The placeholders of an `info_plist_template`, written `{{name}}`: the
+/// bundle ID, the product name, and the marketing and build versions.
+const INFO_PLIST_PLACEHOLDERS: [&str; 4] = ["bundle_id", "name", "version", "build"];
+
+/// Reads the `info_plist_template`, failing on placeholders catalyst can't
+/// fill in, which would otherwise end up in every app's Info.plist.
+pub(crate) fn read_info_plist_template(path: &Path) -> Result<String> {
+    let template = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read Info.plist template {}", path.display()))?;
+    let unknown: BTreeSet<&str> = template
+        .split("{{")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("}}"))
+        .map(|(placeholder, _)| placeholder)
+        .filter(|placeholder| !INFO_PLIST_PLACEHOLDERS.contains(placeholder))
+        .collect();
+    if !unknown.is_empty() {
+        anyhow::bail!(
+            "Info.plist template {} has unknown placeholders {}; the known ones are {}",
+            path.display(),
+            unknown
+                .iter()
+                .map(|placeholder| format!("{{{{{}}}}}", placeholder))
+                .collect::<Vec<_>>()
+                .join(", "),
+            INFO_PLIST_PLACEHOLDERS
+                .iter()
+                .map(|placeholder| format!("{{{{{}}}}}", placeholder))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(template)
+}
+
+/// Returns the entitlements path to put in `entitlements`, if the target has
+/// any. Inline entitlements are written to a `.entitlements` plist next to the
+/// BUILD file and recorded in `written`.
+pub(crate) fn resolve_entitlements(
+    target: &TuistTarget,
+    project: &TuistProject,
+    package_dir: &Path,
+    written: &mut Vec<PathBuf>,
+) -> Result<Option<String>> {
+    let project_path = Path::new(&project.path);
+    if let Some(path) = target.entitlements_file(project_path) {
+        let relative = path
+            .strip_prefix(project_path)
+            .ok()
+            .filter(|_| path.exists());
+        if let Some(relative) = relative {
+            return Ok(Some(relative.display().to_string()));
+        }
+        warning!(
+            "entitlements file {} of {} not found; skipping it",
+            path.display(),
+            target.name
+        );
+        return Ok(None);
+    }
+
+    let Some(entries) = target.entitlements_dictionary() else {
+        return Ok(None);
+    };
+
+    let mut body = String::new();
+    for (key, value) in entries {
+        body.push_str(&format!("    <key>{}</key>\n", xml_escape(key)));
+        render_plist_value(value, 1, &mut body);
+    }
+
+    let file_name = format!("{}.entitlements", target.name);
+    let entitlements_path = package_dir.join(&file_name);
+    write_generated_file(
+        &entitlements_path,
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+{body}</dict>
+</plist>
+"#
+        ),
+    )
+    .context("Failed to write entitlements")?;
+    written.push(entitlements_path);
+
+    Ok(Some(file_name))
+}
+
+/// Appends `value` as plist XML at the given nesting depth. Tuist encodes plist
+/// values either bare or tagged with their type, like `{"boolean": true}`.
+fn render_plist_value(value: &serde_json::Value, depth: usize, out: &mut String) {
+    use serde_json::Value;
+
+    let indent = "    ".repeat(depth);
+    let tagged = value
+        .as_object()
+        .filter(|object| object.len() == 1)
+        .and_then(|object| object.iter().next())
+        .filter(|(tag, _)| {
+            matches!(
+                tag.as_str(),
+                "string" | "integer" | "real" | "boolean" | "array" | "dictionary"
+            )
+        });
+    if let Some((_, inner)) = tagged {
+        return render_plist_value(inner, depth, out);
+    }
+
+    match value {
+        Value::Bool(true) => out.push_str(&format!("{}<true/>\n", indent)),
+        Value::Bool(false) => out.push_str(&format!("{}<false/>\n", indent)),
+        Value::Number(number) if number.is_f64() => {
+            out.push_str(&format!("{}<real>{}</real>\n", indent, number))
+        }
+        Value::Number(number) => {
+            out.push_str(&format!("{}<integer>{}</integer>\n", indent, number))
+        }
+        Value::String(string) => out.push_str(&format!(
+            "{}<string>{}</string>\n",
+            indent,
+            xml_escape(string)
+        )),
+        Value::Array(items) => {
+            out.push_str(&format!("{}<array>\n", indent));
+            for item in items {
+                render_plist_value(item, depth + 1, out);
+            }
+            out.push_str(&format!("{}</array>\n", indent));
+        }
+        Value::Object(entries) => {
+            out.push_str(&format!("{}<dict>\n", indent));
+            for (key, item) in entries {
+                out.push_str(&format!("{}    <key>{}</key>\n", indent, xml_escape(key)));
+                render_plist_value(item, depth + 1, out);
+            }
+            out.push_str(&format!("{}</dict>\n", indent));
+        }
+        Value::Null => {}
+    }
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
@@ -0,0 +1,183 @@
+//! What a command did, for the summary and `--format json`.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::config::{GenerateOptions, OutputFormat};
+use crate::graph::{TuistGraph, TuistProject, TuistTarget};
+use crate::output::{take_timings, PhaseTiming, TIMINGS_ENABLED};
+
+/// What a build, run, test, or generate did, printed as JSON with
+/// `--format json`.
+#[derive(Debug, Default, Serialize)]
+pub struct BuildReport {
+    pub projects: Vec<ProjectReport>,
+    pub files_written: Vec<PathBuf>,
+    /// `None` when generation was skipped because nothing changed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub summary: Option<GenerationSummary>,
+    /// The build was skipped because nothing changed since the last one
+    pub up_to_date: bool,
+    /// `None` when Bazel didn't run or was killed by a signal
+    pub bazel_exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub launched_pid: Option<u32>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub duration_secs: f64,
+    /// The phases of the run with `--timings`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub timings: Vec<PhaseTiming>,
+}
+
+/// What generation saw: the projects and targets of the workspace, by kind,
+/// their source files, and the warnings printed along the way. Projects left
+/// unchanged since the last generation are counted too.
+#[derive(Debug, Default, Serialize)]
+pub struct GenerationSummary {
+    pub projects: usize,
+    pub apps: usize,
+    pub app_extensions: usize,
+    pub frameworks: usize,
+    pub libraries: usize,
+    pub tests: usize,
+    pub resource_bundles: usize,
+    pub source_files: usize,
+    pub warnings: Vec<String>,
+}
+
+impl GenerationSummary {
+    pub(crate) fn add_target(
+        &mut self,
+        target: &TuistTarget,
+        project: &TuistProject,
+        options: &GenerateOptions,
+    ) {
+        let count = match target.product.as_str() {
+            "app" => &mut self.apps,
+            "app_extension" => &mut self.app_extensions,
+            "framework" => &mut self.frameworks,
+            "unit_tests" | "ui_tests" => &mut self.tests,
+            "bundle" => &mut self.resource_bundles,
+            _ => &mut self.libraries,
+        };
+        *count += 1;
+        self.source_files += target.source_file_count(project, options);
+    }
+
+    fn merge(&mut self, other: GenerationSummary) {
+        self.projects += other.projects;
+        self.apps += other.apps;
+        self.app_extensions += other.app_extensions;
+        self.frameworks += other.frameworks;
+        self.libraries += other.libraries;
+        self.tests += other.tests;
+        self.resource_bundles += other.resource_bundles;
+        self.source_files += other.source_files;
+        self.warnings.extend(other.warnings);
+    }
+
+    /// Prints the summary as a table, with the warnings listed below it.
+    pub fn print(&self) {
+        let rows = [
+            ("Projects", self.projects),
+            ("Apps", self.apps),
+            ("App extensions", self.app_extensions),
+            ("Frameworks", self.frameworks),
+            ("Libraries", self.libraries),
+            ("Tests", self.tests),
+            ("Resource bundles", self.resource_bundles),
+            ("Source files", self.source_files),
+            ("Warnings", self.warnings.len()),
+        ];
+        info!("\nSummary:");
+        for (label, count) in rows {
+            info!("  {:<18}{:>6}", label, count);
+        }
+        for warning in &self.warnings {
+            info!("  - {}", warning);
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProjectReport {
+    pub name: String,
+    pub path: String,
+    pub targets: Vec<String>,
+}
+
+impl BuildReport {
+    /// Adds the report of another project root to this one: its projects and
+    /// files, its summary's counts and warnings, and Bazel's exit code when
+    /// it failed.
+    pub(crate) fn merge(&mut self, other: BuildReport) {
+        self.projects.extend(other.projects);
+        self.files_written.extend(other.files_written);
+        self.summary = match (self.summary.take(), other.summary) {
+            (Some(mut summary), Some(other)) => {
+                summary.merge(other);
+                Some(summary)
+            }
+            (summary, other) => summary.or(other),
+        };
+        self.up_to_date &= other.up_to_date;
+        if self.bazel_exit_code.is_none_or(|code| code == 0) {
+            self.bazel_exit_code = other.bazel_exit_code.or(self.bazel_exit_code);
+        }
+        self.launched_pid = self.launched_pid.or(other.launched_pid);
+    }
+
+    pub(crate) fn add_projects(&mut self, graph: &TuistGraph) -> Result<()> {
+        let (projects, _) = graph.parse_projects()?;
+        for project in projects {
+            let mut targets: Vec<String> = project.targets.keys().cloned().collect();
+            targets.sort();
+            self.projects.push(ProjectReport {
+                name: project.name,
+                path: project.path,
+                targets,
+            });
+        }
+        Ok(())
+    }
+
+    /// Prints the report in JSON mode, then passes `result` through so the
+    /// exit status still reflects failures.
+    pub fn finish(
+        mut self,
+        format: OutputFormat,
+        started: Instant,
+        result: Result<()>,
+    ) -> Result<()> {
+        self.timings = take_timings();
+        if format == OutputFormat::Json {
+            self.success = result.is_ok();
+            self.error = result.as_ref().err().map(|err| format!("{:#}", err));
+            self.duration_secs = started.elapsed().as_secs_f64();
+            println!("{}", serde_json::to_string_pretty(&self)?);
+        } else if TIMINGS_ENABLED.load(Ordering::Relaxed) {
+            let width = self
+                .timings
+                .iter()
+                .map(|timing| timing.phase.len())
+                .max()
+                .unwrap_or_default()
+                .max("total".len());
+            eprintln!("\nTimings:");
+            for timing in &self.timings {
+                eprintln!("  {:width$}  {:>8.2}s", timing.phase, timing.secs);
+            }
+            eprintln!(
+                "  {:width$}  {:>8.2}s",
+                "total",
+                started.elapsed().as_secs_f64()
+            );
+        }
+        result
+    }
+}
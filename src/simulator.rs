@@ -0,0 +1,887 @@
+//! Running apps in the iOS simulator with `catalyst run`.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use crate::cache::{get_catalyst_cache_dir, hash_file_contents};
+use crate::command::{bazel_color_args, CommandExt};
+use crate::config::{GenerateOptions, Platform};
+use crate::error::CatalystError;
+use crate::graph::{PathResolver, TuistGraph, TuistProject, TuistTarget};
+use crate::index::TargetIndex;
+use crate::output::{PhaseTimer, Progress};
+use crate::tuist::list_files;
+
+/// Simulator device `catalyst run` uses when none is configured.
+pub const DEFAULT_SIMULATOR: &str = "iPhone 16";
+
+/// Seconds `catalyst run` waits for a simulator to finish booting.
+pub const DEFAULT_BOOT_TIMEOUT_SECS: u64 = 60;
+
+/// How many times a simctl install or launch is attempted before giving up.
+const SIMCTL_ATTEMPTS: u32 = 3;
+
+/// The label of the app `catalyst run` launches, in the workspace generated
+/// for the project in `project_dir`, and its bundle ID in the configuration
+/// being built: the one `target_hint` names, or the only iOS app of the graph.
+/// Several iOS apps without a hint are an error listing them, rather than a
+/// pick that depends on the graph's order.
+pub fn find_app_target(
+    graph: &TuistGraph,
+    project_dir: &Path,
+    target_hint: Option<&str>,
+    options: &GenerateOptions,
+) -> Result<(String, String)> {
+    let mut projects = graph.projects()?;
+    let resolver = PathResolver::new(project_dir, &graph.path);
+    for project in &mut projects {
+        project.resolve_paths(&resolver);
+    }
+    let index = TargetIndex::new(&options.package_root(project_dir, graph), &projects);
+    let label = |project: &TuistProject, key: &String| {
+        let package = index.package(&project.path).unwrap_or_default();
+        format!("//{}:{}", package, key.to_lowercase())
+    };
+
+    let mut apps: Vec<(&String, &TuistTarget, String)> = projects
+        .iter()
+        .flat_map(|project| {
+            project
+                .targets
+                .iter()
+                .map(move |(key, target)| (key, target, label(project, key)))
+        })
+        .filter(|(_, target, _)| target.product == "app")
+        .collect();
+    apps.sort_by(|a, b| a.0.cmp(b.0));
+    let bundle_id = |key: &String, target: &TuistTarget| {
+        options
+            .targets
+            .get(key)
+            .and_then(|target| target.bundle_id.clone())
+            .unwrap_or_else(|| {
+                target.configuration_bundle_id(options.configuration.bazelrc_config())
+            })
+    };
+
+    // --target wins over catalyst.toml's default_run_target
+    if let Some(hint) = target_hint.or(options.default_run_target.as_deref()) {
+        let Some((key, target, label)) = apps
+            .iter()
+            .find(|(key, _, _)| key.eq_ignore_ascii_case(hint))
+        else {
+            return Err(CatalystError::NoAppTarget {
+                requested: Some(hint.to_string()),
+                available: apps.iter().map(|(key, _, _)| key.to_string()).collect(),
+            }
+            .into());
+        };
+        if target.platform() != Platform::Ios {
+            anyhow::bail!(
+                "{} is a {} app; catalyst can only run iOS apps in the simulator",
+                key,
+                target.platform().prefix()
+            );
+        }
+        return Ok((label.clone(), bundle_id(key, target)));
+    }
+
+    let ios_apps: Vec<&(&String, &TuistTarget, String)> = apps
+        .iter()
+        .filter(|(_, target, _)| target.platform() == Platform::Ios)
+        .collect();
+    match ios_apps[..] {
+        [] => Err(CatalystError::NoAppTarget {
+            requested: None,
+            available: apps.iter().map(|(key, _, _)| key.to_string()).collect(),
+        }
+        .into()),
+        [(key, target, label)] => Ok((label.clone(), bundle_id(key, target))),
+        _ => {
+            let names: Vec<&str> = ios_apps.iter().map(|(key, _, _)| key.as_str()).collect();
+            anyhow::bail!(
+                "The project has several iOS apps, so pick one with --target or catalyst.toml's default_run_target: {}",
+                names.join(", ")
+            )
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SimulatorList {
+    devices: HashMap<String, Vec<SimulatorDevice>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulatorDevice {
+    name: String,
+    udid: String,
+    state: String,
+    /// The runtime identifier the device is listed under, e.g.
+    /// `com.apple.CoreSimulator.SimRuntime.iOS-17-5`.
+    #[serde(skip)]
+    runtime: String,
+    /// Set for an ephemeral device, which is deleted with this value
+    #[serde(skip)]
+    ephemeral: Option<DeleteSimulator>,
+}
+
+/// Shuts down and deletes the ephemeral simulator with this UDID when dropped.
+#[derive(Debug)]
+struct DeleteSimulator(String);
+
+impl Drop for DeleteSimulator {
+    fn drop(&mut self) {
+        info!("Deleting simulator: {}", self.0);
+        for action in ["shutdown", "delete"] {
+            let _ = Command::new("xcrun")
+                .args(["simctl", action, &self.0])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .logged()
+                .status_timed();
+        }
+    }
+}
+
+impl SimulatorDevice {
+    fn is_booted(&self) -> bool {
+        self.state == "Booted"
+    }
+
+    /// The runtime as it's shown to users, e.g. `iOS 17.5`.
+    fn runtime_name(&self) -> String {
+        let runtime = self.runtime.rsplit('.').next().unwrap_or(&self.runtime);
+        match runtime.split_once('-') {
+            Some((os, version)) => format!("{} {}", os, version.replace('-', ".")),
+            None => runtime.to_string(),
+        }
+    }
+
+    /// The runtime's version components, used to prefer the newest runtime.
+    fn runtime_version(&self) -> Vec<u32> {
+        self.runtime
+            .rsplit('.')
+            .next()
+            .unwrap_or_default()
+            .split('-')
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    }
+}
+
+/// Lists the available simulator devices across all installed runtimes.
+pub(crate) fn list_simulators() -> Result<Vec<SimulatorDevice>> {
+    let output = Command::new("xcrun")
+        .args(["simctl", "list", "devices", "available", "--json"])
+        .logged()
+        .output_timed()
+        .context("Failed to execute xcrun simctl list")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "xcrun simctl list failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let list: SimulatorList =
+        serde_json::from_slice(&output.stdout).context("Failed to parse simulator list")?;
+    Ok(list
+        .devices
+        .into_iter()
+        .flat_map(|(runtime, devices)| {
+            devices.into_iter().map(move |device| SimulatorDevice {
+                runtime: runtime.clone(),
+                ..device
+            })
+        })
+        .collect())
+}
+
+/// The simulator `catalyst run` launches the app in.
+#[derive(Debug, Clone)]
+pub struct SimulatorQuery {
+    /// Device name or UDID
+    pub device: String,
+    /// iOS runtime version the device has to run, e.g. `17.5`
+    pub os: Option<String>,
+    /// Create a fresh device of the `device` type, a name like `iPhone 16` or
+    /// an identifier like `com.apple.CoreSimulator.SimDeviceType.iPhone-16`,
+    /// that is deleted when the returned device is dropped
+    pub ephemeral: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct RuntimeList {
+    runtimes: Vec<SimulatorRuntime>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SimulatorRuntime {
+    /// e.g. `com.apple.CoreSimulator.SimRuntime.iOS-17-5`
+    identifier: String,
+    /// e.g. `17.5`
+    version: String,
+    /// e.g. `iOS 17.5`
+    name: String,
+}
+
+impl SimulatorRuntime {
+    fn version_components(&self) -> Vec<u32> {
+        self.version
+            .split('.')
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    }
+}
+
+/// Lists the installed iOS simulator runtimes.
+fn list_ios_runtimes() -> Result<Vec<SimulatorRuntime>> {
+    let output = Command::new("xcrun")
+        .args(["simctl", "list", "runtimes", "available", "--json"])
+        .logged()
+        .output_timed()
+        .context("Failed to execute xcrun simctl list runtimes")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "xcrun simctl list runtimes failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let list: RuntimeList =
+        serde_json::from_slice(&output.stdout).context("Failed to parse runtime list")?;
+    Ok(list
+        .runtimes
+        .into_iter()
+        .filter(|runtime| runtime.name.starts_with("iOS "))
+        .collect())
+}
+
+/// Resolves `os` (`17.5`, `iOS 17.5`, or just `17`) to an installed iOS
+/// runtime. A version without a minor one picks the newest matching runtime;
+/// when none matches, the installed runtimes are listed.
+fn find_runtime(os: &str) -> Result<SimulatorRuntime> {
+    let os = os.trim();
+    let version = os
+        .get(..3)
+        .filter(|prefix| prefix.eq_ignore_ascii_case("ios"))
+        .map_or(os, |_| os[3..].trim_start());
+    let mut runtimes = list_ios_runtimes()?;
+
+    if let Some(index) = runtimes.iter().position(|r| r.version == version) {
+        return Ok(runtimes.swap_remove(index));
+    }
+    let prefix = format!("{}.", version);
+    if let Some(index) = runtimes
+        .iter()
+        .enumerate()
+        .filter(|(_, r)| r.version.starts_with(&prefix))
+        .max_by_key(|(_, r)| r.version_components())
+        .map(|(index, _)| index)
+    {
+        return Ok(runtimes.swap_remove(index));
+    }
+
+    if runtimes.is_empty() {
+        eprintln!("No iOS simulator runtimes are installed");
+    } else {
+        runtimes.sort_by_key(SimulatorRuntime::version_components);
+        eprintln!("Installed iOS runtimes:");
+        for runtime in &runtimes {
+            eprintln!("  {}", runtime.version);
+        }
+    }
+    anyhow::bail!(
+        "The iOS {} simulator runtime isn't installed; install it from Xcode's Platforms settings or pass one of the runtimes above with --simulator-os",
+        version
+    )
+}
+
+/// Creates a simulator called `name` of `device_type` on `runtime`.
+fn create_simulator(
+    name: &str,
+    device_type: &str,
+    runtime: &SimulatorRuntime,
+) -> Result<SimulatorDevice> {
+    info!("Creating simulator: {} ({})", name, runtime.name);
+    let output = Command::new("xcrun")
+        .args(["simctl", "create", name, device_type, &runtime.identifier])
+        .logged()
+        .output_timed()
+        .context("Failed to execute xcrun simctl create")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to create a {} simulator on {} (`xcrun simctl list devicetypes` lists the device types): {}",
+            device_type,
+            runtime.name,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(SimulatorDevice {
+        name: name.to_string(),
+        udid: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+        state: "Shutdown".to_string(),
+        runtime: runtime.identifier.clone(),
+        ephemeral: None,
+    })
+}
+
+/// Creates a simulator of the query's device type for a single run, on its
+/// runtime or the newest installed iOS one.
+fn create_ephemeral_simulator(query: &SimulatorQuery) -> Result<SimulatorDevice> {
+    let runtime = match &query.os {
+        Some(os) => find_runtime(os)?,
+        None => list_ios_runtimes()?
+            .into_iter()
+            .max_by_key(SimulatorRuntime::version_components)
+            .context("No iOS simulator runtimes are installed; install one from Xcode's Platforms settings")?,
+    };
+    let name = format!("catalyst-{}", std::process::id());
+    let mut device = create_simulator(&name, &query.device, &runtime)?;
+    device.ephemeral = Some(DeleteSimulator(device.udid.clone()));
+    Ok(device)
+}
+
+/// How `catalyst run` boots the simulator and launches the app.
+#[derive(Debug, Clone)]
+pub struct LaunchOptions {
+    /// How long to wait for the simulator to boot
+    pub boot_timeout: Duration,
+    /// Environment variables for the app
+    pub env: Vec<(String, String)>,
+    /// Arguments passed to the app after its bundle ID
+    pub args: Vec<String>,
+    pub steps: RunSteps,
+    /// Startup options of the Bazel commands that build the app and find it
+    pub bazel_startup_args: Vec<String>,
+}
+
+/// Which steps of `catalyst run` happen. The simulator is booted whichever
+/// are left out, and a step that relies on a skipped one checks that its
+/// result is there, e.g. launching without installing needs the app to be
+/// installed already.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunSteps {
+    /// Build the app target with Bazel
+    pub build: bool,
+    /// Install the built app on the simulator
+    pub install: bool,
+    /// Launch the installed app
+    pub launch: bool,
+}
+
+impl Default for RunSteps {
+    fn default() -> Self {
+        Self {
+            build: true,
+            install: true,
+            launch: true,
+        }
+    }
+}
+
+/// Whether `value` has the shape of a simulator UDID
+/// (`XXXXXXXX-XXXX-XXXX-XXXX-XXXXXXXXXXXX`).
+pub(crate) fn is_udid(value: &str) -> bool {
+    let groups: Vec<&str> = value.split('-').collect();
+    groups.len() == 5
+        && groups
+            .iter()
+            .zip([8, 4, 4, 4, 12])
+            .all(|(group, len)| group.len() == len && group.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+/// Finds the requested device by UDID or name. When several devices share the
+/// name, a booted one wins, then the one on the newest runtime; anything still
+/// ambiguous is an error listing the candidates' UDIDs.
+///
+/// With an OS version, only devices on that runtime qualify, and a named
+/// device that doesn't exist on it yet is created. An ephemeral query always
+/// creates a new device.
+fn find_simulator(query: &SimulatorQuery) -> Result<SimulatorDevice> {
+    if query.ephemeral {
+        return create_ephemeral_simulator(query);
+    }
+    let simulator = query.device.as_str();
+    let runtime = query.os.as_deref().map(find_runtime).transpose()?;
+    let mut devices = list_simulators()?;
+
+    if is_udid(simulator) {
+        let device = devices
+            .into_iter()
+            .find(|d| d.udid.eq_ignore_ascii_case(simulator))
+            .ok_or_else(|| CatalystError::SimulatorNotFound {
+                query: simulator.to_string(),
+            })?;
+        if let Some(runtime) = &runtime {
+            if device.runtime != runtime.identifier {
+                anyhow::bail!(
+                    "Simulator {} runs {}, not {}",
+                    simulator,
+                    device.runtime_name(),
+                    runtime.name
+                );
+            }
+        }
+        return Ok(device);
+    }
+
+    if let Some(runtime) = &runtime {
+        devices.retain(|d| d.runtime == runtime.identifier);
+        // The device keeps the requested name, so later runs reuse it
+        if !devices.iter().any(|d| d.name == simulator) {
+            return create_simulator(simulator, simulator, runtime);
+        }
+    }
+
+    let mut candidates: Vec<SimulatorDevice> = Vec::new();
+    let mut others = Vec::new();
+    for device in devices.drain(..) {
+        if device.name == simulator {
+            candidates.push(device);
+        } else {
+            others.push(device);
+        }
+    }
+
+    if candidates.is_empty() {
+        let mut names: Vec<&str> = others.iter().map(|d| d.name.as_str()).collect();
+        names.sort_unstable();
+        names.dedup();
+        eprintln!("Available simulators:");
+        for name in &names {
+            eprintln!("  {}", name);
+        }
+        return Err(CatalystError::SimulatorNotFound {
+            query: simulator.to_string(),
+        }
+        .into());
+    }
+
+    if candidates.iter().any(SimulatorDevice::is_booted) {
+        candidates.retain(SimulatorDevice::is_booted);
+    } else if let Some(newest) = candidates.iter().map(|d| d.runtime_version()).max() {
+        candidates.retain(|d| d.runtime_version() == newest);
+    }
+
+    if candidates.len() > 1 {
+        eprintln!("Simulators named '{}':", simulator);
+        for device in &candidates {
+            eprintln!(
+                "  {} ({}, {})",
+                device.udid,
+                device.runtime_name(),
+                device.state
+            );
+        }
+        anyhow::bail!(
+            "Several simulators are named '{}'. Pass the UDID of one of the devices above with --simulator",
+            simulator
+        );
+    }
+
+    let device = candidates.remove(0);
+    verbose!(
+        "Using simulator {} ({}, {})",
+        device.name,
+        device.runtime_name(),
+        device.udid
+    );
+    Ok(device)
+}
+
+pub fn run_in_simulator(
+    bazel: &Path,
+    project_dir: &Path,
+    label: &str,
+    bundle_id: &str,
+    simulator: &SimulatorQuery,
+    launch: &LaunchOptions,
+    build_args: &[String],
+) -> Result<(SimulatorDevice, Option<u32>)> {
+    info!("\n=== Launching App in Simulator ===");
+
+    // Check the device before spending time on a build
+    let timer = PhaseTimer::start("find simulator");
+    let device = find_simulator(simulator)?;
+    drop(timer);
+
+    // Build the specific target with Bazel
+    if launch.steps.build {
+        info!("Building target: {}", label);
+        let _timer = PhaseTimer::start("bazel build");
+        let spinner = Progress::spinner(&format!("Building {}", label));
+        Command::new(bazel)
+            .args(&launch.bazel_startup_args)
+            .arg("build")
+            .args(bazel_color_args())
+            .args(build_args)
+            .arg(label)
+            .current_dir(project_dir)
+            .logged()
+            .status_teed()
+            .context("Failed to build target with Bazel")?
+            .check_bazel_build(Some(label))?;
+        drop(spinner);
+    }
+
+    if device.is_booted() {
+        info!("Simulator already booted: {}", device.name);
+    } else {
+        info!("Booting simulator: {}", device.name);
+        let _timer = PhaseTimer::start("boot simulator");
+        let boot_status = Command::new("xcrun")
+            .args(["simctl", "boot", &device.udid])
+            .logged()
+            .status_timed()
+            .context("Failed to boot simulator")?;
+
+        if !boot_status.success() {
+            anyhow::bail!("Failed to boot simulator {}", device.name);
+        }
+
+        wait_for_boot(&device, launch.boot_timeout)?;
+    }
+
+    // Reinstalling an unchanged app only costs time, so it's skipped when the
+    // simulator still has the build installed last time
+    let unchanged = if launch.steps.install {
+        let _timer = PhaseTimer::start("install app");
+        let app_path = find_app_bundle(
+            bazel,
+            &launch.bazel_startup_args,
+            project_dir,
+            label,
+            build_args,
+        )
+        .with_context(|| {
+            if launch.steps.build {
+                format!("No build of {} to install", label)
+            } else {
+                format!(
+                    "No build of {} to install; run without --skip-build to build it",
+                    label
+                )
+            }
+        })?;
+        install_app(&device, bundle_id, &app_path)?
+    } else if is_app_installed(&device, bundle_id) {
+        info!("Using the installed app: {}", bundle_id);
+        true
+    } else {
+        anyhow::bail!(
+            "{} isn't installed on {}; run without --skip-install or --launch-only to install it",
+            bundle_id,
+            device.name
+        );
+    };
+
+    if !launch.steps.launch {
+        return Ok((device, None));
+    }
+    let timer = PhaseTimer::start("launch app");
+    let pid = launch_app(&device, bundle_id, launch, unchanged)?;
+    drop(timer);
+
+    Ok((device, Some(pid)))
+}
+
+/// Installs the `.ipa` archive or `.app` bundle at `app_path` on `device`,
+/// unless the simulator has it installed already. Returns whether the install
+/// was skipped.
+fn install_app(device: &SimulatorDevice, bundle_id: &str, app_path: &Path) -> Result<bool> {
+    let cache_dir = get_catalyst_cache_dir()?;
+    let mut installed = InstalledApps::load(&cache_dir);
+    // A bundle is a directory, hashed by the files in it
+    let files = if app_path.is_dir() {
+        let mut files = Vec::new();
+        list_files(app_path, &mut files);
+        files.retain(|path| path.is_file());
+        files.sort();
+        files
+    } else {
+        vec![app_path.to_path_buf()]
+    };
+    let app_hash = hash_file_contents(&files)
+        .with_context(|| format!("Failed to read {}", app_path.display()))?;
+    let unchanged = installed.hash_of(&device.udid, bundle_id) == Some(app_hash.as_str())
+        && is_app_installed(device, bundle_id);
+    if unchanged {
+        info!(
+            "App unchanged since the last install, relaunching: {}",
+            bundle_id
+        );
+    } else {
+        info!("Installing app: {}", app_path.display());
+        retry_simctl("install app on simulator", || {
+            Command::new("xcrun")
+                .args(["simctl", "install", &device.udid])
+                .arg(app_path)
+                .logged()
+                .output_timed()
+        })?;
+        // An ephemeral device is gone after this run
+        if device.ephemeral.is_none() {
+            installed.record(&device.udid, bundle_id, app_hash);
+            installed.save(&cache_dir)?;
+        }
+    }
+    Ok(unchanged)
+}
+
+/// Launches the installed app on `device` and returns its PID. `running`
+/// says the app may be running from before, so it's stopped first.
+fn launch_app(
+    device: &SimulatorDevice,
+    bundle_id: &str,
+    launch: &LaunchOptions,
+    running: bool,
+) -> Result<u32> {
+    info!("Launching app: {}", bundle_id);
+    let launch_output = retry_simctl("launch app", || {
+        let mut command = Command::new("xcrun");
+        command.args(["simctl", "launch"]);
+        // Installing stops a running copy of the app; without an install it
+        // has to be stopped for the launch to start it afresh
+        if running {
+            command.arg("--terminate-running-process");
+        }
+        command
+            .args([device.udid.as_str(), bundle_id])
+            .args(&launch.args);
+        // simctl hands variables with this prefix to the app, without it
+        for (key, value) in &launch.env {
+            command.env(format!("SIMCTL_CHILD_{}", key), value);
+        }
+        command.logged().output_timed()
+    })?;
+
+    let output_str = String::from_utf8_lossy(&launch_output.stdout);
+    parse_launch_pid(&output_str).with_context(|| {
+        format!(
+            "Unexpected output from simctl launch: {}",
+            output_str.trim()
+        )
+    })
+}
+
+/// The hash of the archive last installed for each app on each simulator, so
+/// `catalyst run` can skip installing a build the simulator already has.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct InstalledApps {
+    /// Keyed by `<simulator UDID>/<bundle ID>`
+    apps: BTreeMap<String, String>,
+}
+
+impl InstalledApps {
+    const FILE_NAME: &'static str = "installed.json";
+
+    fn load(cache_dir: &Path) -> Self {
+        fs::read_to_string(cache_dir.join(Self::FILE_NAME))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, cache_dir: &Path) -> Result<()> {
+        fs::create_dir_all(cache_dir)?;
+        fs::write(
+            cache_dir.join(Self::FILE_NAME),
+            serde_json::to_string_pretty(self)?,
+        )
+        .context("Failed to write installed app hashes")
+    }
+
+    fn hash_of(&self, udid: &str, bundle_id: &str) -> Option<&str> {
+        self.apps
+            .get(&format!("{}/{}", udid, bundle_id))
+            .map(String::as_str)
+    }
+
+    fn record(&mut self, udid: &str, bundle_id: &str, hash: String) {
+        self.apps.insert(format!("{}/{}", udid, bundle_id), hash);
+    }
+}
+
+/// Whether the app is installed on `device`, which it no longer is after the
+/// user deleted it or erased the simulator.
+fn is_app_installed(device: &SimulatorDevice, bundle_id: &str) -> bool {
+    Command::new("xcrun")
+        .args(["simctl", "get_app_container", &device.udid, bundle_id])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .logged()
+        .status_timed()
+        .is_ok_and(|status| status.success())
+}
+
+/// Polls the device list until `device` reports itself booted, since `simctl boot`
+/// returns before the simulator can accept installs.
+fn wait_for_boot(device: &SimulatorDevice, timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+    loop {
+        let booted = list_simulators()?
+            .iter()
+            .any(|d| d.udid == device.udid && d.is_booted());
+        if booted {
+            verbose!("Simulator booted in {:.1}s", start.elapsed().as_secs_f64());
+            return Ok(());
+        }
+        if start.elapsed() >= timeout {
+            anyhow::bail!(
+                "Simulator {} didn't finish booting within {}s (raise it with --boot-timeout)",
+                device.name,
+                timeout.as_secs()
+            );
+        }
+        std::thread::sleep(Duration::from_millis(500));
+    }
+}
+
+/// Runs a simctl command, retrying when it fails. Freshly booted simulators
+/// sometimes reject the first install or launch while their services start up.
+fn retry_simctl(action: &str, mut run: impl FnMut() -> Result<Output>) -> Result<Output> {
+    let mut attempt = 1;
+    loop {
+        let output = run().with_context(|| format!("Failed to {}", action))?;
+        if output.status.success() {
+            return Ok(output);
+        }
+
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        if attempt == SIMCTL_ATTEMPTS {
+            anyhow::bail!("Failed to {}: {}", action, stderr.trim());
+        }
+        warning!(
+            "Failed to {} (attempt {} of {}), retrying: {}",
+            action,
+            attempt,
+            SIMCTL_ATTEMPTS,
+            stderr.trim()
+        );
+        std::thread::sleep(Duration::from_secs(2));
+        attempt += 1;
+    }
+}
+
+/// Asks Bazel where the built app for `label` lives, since the path depends on
+/// the package the target is in and the configured output layout. The build
+/// arguments select the same configuration the app was built in. That's an
+/// `.ipa` archive, or an `.app` bundle for builds with tree artifact outputs,
+/// which simulator builds often turn on; simctl installs either.
+fn find_app_bundle(
+    bazel: &Path,
+    startup_args: &[String],
+    project_dir: &Path,
+    label: &str,
+    build_args: &[String],
+) -> Result<PathBuf> {
+    let output = Command::new(bazel)
+        .args(startup_args)
+        .args(["cquery", "--output=files"])
+        .args(build_args)
+        .arg(label)
+        .current_dir(project_dir)
+        .logged()
+        .output_timed()
+        .context("Failed to execute bazel cquery")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "bazel cquery failed for {}: {}",
+            label,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let mut outputs: Vec<PathBuf> = stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| line.ends_with(".ipa") || line.ends_with(".app"))
+        .map(|line| project_dir.join(line))
+        .collect();
+    if outputs.is_empty() {
+        anyhow::bail!("Bazel reported no .ipa or .app output for {}", label);
+    }
+    // The archive is what a regular build produces, so it wins when both exist
+    outputs.sort_by_key(|path| path.extension().and_then(|ext| ext.to_str()) != Some("ipa"));
+
+    match outputs.iter().find(|path| path.exists()) {
+        Some(path) => Ok(path.clone()),
+        None => {
+            let paths: Vec<String> = outputs
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            anyhow::bail!(
+                "Found neither an .ipa nor an .app for {} at {}. Simulator builds often yield an .app bundle instead of an .ipa (with --define=apple.experimental.tree_artifact_outputs=1), so the app may have been built with other flags than these; build it again",
+                label,
+                paths.join(", ")
+            )
+        }
+    }
+}
+
+/// Extracts the PID from `simctl launch` output, which looks like `com.example.app: 12345`.
+fn parse_launch_pid(output: &str) -> Option<u32> {
+    output.trim().rsplit(':').next()?.trim().parse().ok()
+}
+
+/// Blocks until a message on `interrupts`, e.g. from a Ctrl-C handler, to
+/// keep an ephemeral simulator alive while the app runs in it.
+pub fn wait_for_interrupt(interrupts: &mpsc::Receiver<()>) -> Result<()> {
+    let _ = interrupts.recv();
+    Ok(())
+}
+
+/// Prints the app's logs until the log process exits or a message arrives
+/// on `interrupts`. Ctrl-C in a terminal reaches the log process too, through
+/// the terminal's process group.
+pub fn stream_app_logs(
+    device: &SimulatorDevice,
+    pid: u32,
+    interrupts: &mpsc::Receiver<()>,
+) -> Result<()> {
+    info!("\n=== Streaming Logs (Ctrl-C to stop) ===");
+
+    let mut child = Command::new("xcrun")
+        .args([
+            "simctl",
+            "spawn",
+            &device.udid,
+            "log",
+            "stream",
+            "--style",
+            "compact",
+            "--predicate",
+            &format!("processID == {}", pid),
+        ])
+        .logged()
+        .spawn()
+        .context("Failed to execute xcrun simctl spawn log stream")?;
+
+    while child
+        .try_wait()
+        .context("Failed to wait for log stream")?
+        .is_none()
+    {
+        if interrupts.recv_timeout(Duration::from_millis(100)).is_ok() {
+            let _ = child.kill();
+            child.wait().context("Failed to wait for log stream")?;
+            break;
+        }
+    }
+    Ok(())
+}
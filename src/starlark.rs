@@ -0,0 +1,44 @@
+//! Rendering values as Starlark for the generated files.
+
+use crate::config::DeviceFamily;
+
+/// Quotes a value as a Starlark string literal, escaping backslashes,
+/// quotes, and control characters, so paths and names with unusual
+/// characters still produce valid BUILD, WORKSPACE, and MODULE.bazel files.
+pub(crate) fn starlark_string(value: impl std::fmt::Display) -> String {
+    let mut literal = String::from("\"");
+    for c in value.to_string().chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// Renders the `families` attribute value, e.g. `["iphone", "ipad"]`.
+pub(crate) fn render_families(families: &[DeviceFamily]) -> String {
+    let families: Vec<String> = families
+        .iter()
+        .map(|family| starlark_string(family.as_str()))
+        .collect();
+    format!("[{}]", families.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starlark_string_escapes_quotes_and_backslashes() {
+        assert_eq!(
+            starlark_string(r#"Sources/we"ird\name.swift"#),
+            r#""Sources/we\"ird\\name.swift""#
+        );
+    }
+}
@@ -0,0 +1,425 @@
+//! Finding Tuist projects and running `tuist graph` on them.
+
+use anyhow::{Context, Result};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeMap;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+use std::time::{Duration, SystemTime};
+
+use crate::cache::PackageStamps;
+use crate::command::CommandExt;
+use crate::error::CatalystError;
+use crate::graph::TuistGraph;
+use crate::output::{verbosity, PhaseTimer, Progress, Verbosity};
+use crate::report::BuildReport;
+
+/// The Tuist release the graph parsing is tested against.
+const TESTED_TUIST_VERSION: &str = "4.97.1";
+
+/// File names that make up a Tuist project definition.
+pub(crate) const TUIST_MANIFEST_FILES: &[&str] = &[
+    "Project.swift",
+    "Workspace.swift",
+    "Tuist.swift",
+    "Package.swift",
+    "Package.resolved",
+    ".tuist-version",
+];
+
+/// Hashes the path and contents of every Tuist manifest under `project_dir`,
+/// including everything inside `Tuist/` directories (helpers, Config.swift).
+/// Returns the hash of them all and the hash of each one's contents, by path
+/// relative to `project_dir`.
+pub(crate) fn hash_manifest_files(
+    project_dir: &Path,
+) -> Result<(String, BTreeMap<String, String>)> {
+    let mut manifests = Vec::new();
+    collect_manifest_files(project_dir, false, &mut manifests)?;
+    manifests.sort();
+
+    let mut hasher = DefaultHasher::new();
+    let mut files = BTreeMap::new();
+    for path in &manifests {
+        let rel_path = path.strip_prefix(project_dir).unwrap_or(path);
+        rel_path.hash(&mut hasher);
+        let content =
+            fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        content.hash(&mut hasher);
+
+        let mut file_hasher = DefaultHasher::new();
+        content.hash(&mut file_hasher);
+        files.insert(
+            rel_path.display().to_string(),
+            format!("{:016x}", file_hasher.finish()),
+        );
+    }
+
+    Ok((format!("{:016x}", hasher.finish()), files))
+}
+
+pub(crate) fn collect_manifest_files(
+    dir: &Path,
+    in_tuist_dir: bool,
+    manifests: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        // file_type() doesn't follow symlinks, so linked trees aren't walked
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            let skip = name.starts_with('.')
+                || name.starts_with("bazel-")
+                || matches!(name.as_str(), "Derived" | "DerivedData" | "node_modules");
+            if !skip {
+                collect_manifest_files(&entry.path(), in_tuist_dir || name == "Tuist", manifests)?;
+            }
+        } else if file_type.is_file()
+            && (in_tuist_dir || TUIST_MANIFEST_FILES.contains(&name.as_str()))
+        {
+            manifests.push(entry.path());
+        }
+    }
+
+    Ok(())
+}
+
+/// The directories at or below `dir` that hold a Tuist graph of their own,
+/// for repositories with several independent workspaces. A directory with a
+/// `Workspace.swift` or `Project.swift` is a root, and what's below it is
+/// left to its graph. `dir` itself is returned, as the one root, when it has
+/// a manifest or nothing below it does, so Tuist reports the problem.
+pub fn discover_project_roots(dir: &Path) -> Result<Vec<PathBuf>> {
+    fn is_root(dir: &Path) -> bool {
+        ["Workspace.swift", "Project.swift"]
+            .iter()
+            .any(|manifest| dir.join(manifest).is_file())
+    }
+
+    fn collect(dir: &Path, roots: &mut Vec<PathBuf>) -> Result<()> {
+        let mut entries = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read {}", dir.display()))?
+            .collect::<std::io::Result<Vec<_>>>()?;
+        entries.sort_by_key(|entry| entry.file_name());
+        for entry in entries {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // file_type() doesn't follow symlinks, so linked trees aren't walked
+            let skip = name.starts_with('.')
+                || name.starts_with("bazel-")
+                || matches!(
+                    name.as_str(),
+                    "Tuist" | "Derived" | "DerivedData" | "Pods" | "node_modules"
+                );
+            if skip || !entry.file_type()?.is_dir() {
+                continue;
+            }
+            if is_root(&entry.path()) {
+                roots.push(entry.path());
+            } else {
+                collect(&entry.path(), roots)?;
+            }
+        }
+        Ok(())
+    }
+
+    let mut roots = Vec::new();
+    if !is_root(dir) {
+        collect(dir, &mut roots)?;
+    }
+    if roots.is_empty() {
+        return Ok(vec![dir.to_path_buf()]);
+    }
+    info!(
+        "Found {} Tuist project root(s) in {}:\n  {}",
+        roots.len(),
+        dir.display(),
+        roots
+            .iter()
+            .map(|root| root.display().to_string())
+            .collect::<Vec<_>>()
+            .join("\n  ")
+    );
+    Ok(roots)
+}
+
+/// Runs `step` on each of `roots`, with a report of its own that's merged
+/// into `report`. A root that fails doesn't stop the others; the failures
+/// are listed together at the end.
+pub fn for_each_project_root(
+    roots: &[PathBuf],
+    report: &mut BuildReport,
+    mut step: impl FnMut(&Path, &mut BuildReport) -> Result<()>,
+) -> Result<()> {
+    if let [root] = roots {
+        return step(root, report);
+    }
+    let mut failed = Vec::new();
+    for (i, root) in roots.iter().enumerate() {
+        let mut root_report = BuildReport::default();
+        let result = step(root, &mut root_report);
+        if i == 0 {
+            *report = root_report;
+        } else {
+            report.merge(root_report);
+        }
+        if let Err(err) = result {
+            info!("Project root {} failed: {:#}", root.display(), err);
+            failed.push(format!("{}: {:#}", root.display(), err));
+        }
+    }
+    if !failed.is_empty() {
+        anyhow::bail!(
+            "{} of {} project roots failed:\n  {}",
+            failed.len(),
+            roots.len(),
+            failed.join("\n  ")
+        );
+    }
+    Ok(())
+}
+
+/// The sorted, deduplicated paths of every buildable folder in the graph.
+pub(crate) fn buildable_folder_paths(graph: &TuistGraph) -> Vec<&str> {
+    let mut folders: Vec<&str> = graph
+        .projects
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|project| project.get("targets")?.as_object())
+        .flat_map(|targets| targets.values())
+        .filter_map(|target| target.get("buildableFolders")?.as_array())
+        .flatten()
+        .filter_map(|folder| folder.get("path")?.as_str())
+        .collect();
+    folders.sort();
+    folders.dedup();
+    folders
+}
+
+/// Hashes the recursive file listing of each buildable folder in the graph.
+pub(crate) fn hash_buildable_folders(graph: &TuistGraph) -> String {
+    let mut hasher = DefaultHasher::new();
+    for folder in buildable_folder_paths(graph) {
+        folder.hash(&mut hasher);
+        let mut files = Vec::new();
+        list_files(Path::new(folder), &mut files);
+        files.sort();
+        files.hash(&mut hasher);
+    }
+
+    format!("{:016x}", hasher.finish())
+}
+
+/// Hashes the size and modification time of every file in the graph's
+/// buildable folders and of the xcconfig files recorded in `stamps`, which
+/// changes whenever a source or setting is edited.
+pub(crate) fn hash_source_stamps(graph: &TuistGraph, stamps: &PackageStamps) -> String {
+    let mut files = Vec::new();
+    for folder in buildable_folder_paths(graph) {
+        list_files(Path::new(folder), &mut files);
+    }
+    for stamp in stamps.packages.values() {
+        files.extend(stamp.xcconfigs.iter().cloned());
+    }
+    files.sort();
+    files.dedup();
+
+    let mut hasher = DefaultHasher::new();
+    for file in files {
+        let Ok(metadata) = file.metadata() else {
+            continue;
+        };
+        file.hash(&mut hasher);
+        metadata.len().hash(&mut hasher);
+        metadata.modified().ok().hash(&mut hasher);
+    }
+
+    format!("{:016x}", hasher.finish())
+}
+
+pub(crate) fn list_files(dir: &Path, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            list_files(&path, files);
+        }
+        files.push(path);
+    }
+}
+
+pub fn run_tuist_graph(tuist: &Path, project_dir: &Path) -> Result<TuistGraph> {
+    let _timer = PhaseTimer::start("tuist graph");
+    // A directory of this run's own for the graph output, so concurrent runs
+    // never read each other's graph. It's removed when dropped.
+    let output_dir = tempfile::Builder::new()
+        .prefix("tuist-graph-")
+        .tempdir()
+        .context("Failed to create a temporary directory for the Tuist graph")?;
+    let graph_file = output_dir.path().join("graph.json");
+
+    info!("Running tuist graph...");
+
+    let mut command = Command::new(tuist);
+    command
+        .args(["graph", "--format", "json", "--no-open", "--output-path"])
+        .arg(output_dir.path())
+        .current_dir(project_dir)
+        // Keep stdout free for command results such as `list-targets --json`
+        .stdout(if verbosity() == Verbosity::Quiet {
+            Stdio::null()
+        } else {
+            Stdio::piped()
+        });
+    let _spinner = Progress::spinner("Running tuist graph");
+    let started = SystemTime::now();
+    let teed = match command.logged().status_teed() {
+        Ok(teed) => teed,
+        Err(err)
+            if err
+                .downcast_ref::<std::io::Error>()
+                .is_some_and(|err| err.kind() == std::io::ErrorKind::NotFound) =>
+        {
+            return Err(CatalystError::TuistNotFound {
+                tuist: tuist.to_path_buf(),
+            }
+            .into());
+        }
+        Err(err) => {
+            return Err(err).with_context(|| format!("Failed to execute {} graph", tuist.display()))
+        }
+    };
+    teed.check("tuist graph command failed")?;
+
+    // Read the graph file
+    if !graph_file.is_file() {
+        anyhow::bail!(
+            "tuist graph succeeded but wrote no graph to {}",
+            graph_file.display()
+        );
+    }
+    // Allow for file systems that store modification times in whole seconds
+    let stale = fs::metadata(&graph_file)
+        .and_then(|metadata| metadata.modified())
+        .is_ok_and(|modified| modified + Duration::from_secs(2) < started);
+    if stale {
+        anyhow::bail!(
+            "{} predates this run of tuist graph, so it isn't the graph tuist just wrote",
+            graph_file.display()
+        );
+    }
+    let graph_content = fs::read_to_string(&graph_file).context("Failed to read graph file")?;
+    if graph_content.trim().is_empty() {
+        anyhow::bail!(
+            "tuist graph succeeded but wrote an empty graph to {}",
+            graph_file.display()
+        );
+    }
+
+    let tuist_version = tuist_version(tuist, project_dir);
+
+    // Check the shape up front, so that a graph format catalyst doesn't
+    // understand is reported together with the Tuist version that wrote it.
+    // Projects that fail to parse are reported when generating, which
+    // --keep-going lets continue without them.
+    let graph = parse_graph(
+        &graph_content,
+        "tuist graph JSON output",
+        tuist_version.as_deref(),
+    )?;
+    let graph = TuistGraph {
+        tuist_version,
+        ..graph
+    };
+
+    info!(
+        "Successfully parsed Tuist graph for project: {}",
+        graph.name
+    );
+
+    Ok(graph)
+}
+
+/// Parses graph JSON, checking the shape of its projects too. `what` names
+/// the JSON in errors.
+fn parse_graph(content: &str, what: &str, tuist_version: Option<&str>) -> Result<TuistGraph> {
+    serde_json::from_str::<TuistGraph>(content)
+        .map_err(|source| {
+            anyhow::Error::from(CatalystError::GraphParse {
+                what: what.to_string(),
+                source,
+            })
+        })
+        .and_then(|graph| graph.parse_projects().map(|_| graph))
+        .with_context(|| graph_format_hint(tuist_version))
+}
+
+/// Standard input, read the first time a graph is loaded from `-`, so every
+/// later load in the same run sees the same graph.
+static STDIN_GRAPH: OnceLock<std::io::Result<String>> = OnceLock::new();
+
+/// Reads a graph captured with `tuist graph --format json` from `path`, or
+/// from standard input for `-`.
+pub(crate) fn read_graph_file(path: &Path) -> Result<TuistGraph> {
+    let (content, what) = if path == Path::new("-") {
+        let content = STDIN_GRAPH.get_or_init(|| {
+            let mut content = String::new();
+            std::io::stdin()
+                .read_to_string(&mut content)
+                .map(|_| content)
+        });
+        let content = match content {
+            Ok(content) => content.clone(),
+            Err(err) => anyhow::bail!("Failed to read the Tuist graph from stdin: {}", err),
+        };
+        (content, "Tuist graph from stdin".to_string())
+    } else {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read the Tuist graph {}", path.display()))?;
+        (content, format!("Tuist graph {}", path.display()))
+    };
+    if content.trim().is_empty() {
+        anyhow::bail!("The {} is empty", what);
+    }
+    parse_graph(&content, &what, None)
+}
+
+/// The version reported by `tuist version`, if it can be determined.
+fn tuist_version(tuist: &Path, project_dir: &Path) -> Option<String> {
+    let output = Command::new(tuist)
+        .arg("version")
+        .current_dir(project_dir)
+        .logged()
+        .output_timed()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .map(str::to_string)
+}
+
+pub(crate) fn graph_format_hint(tuist_version: Option<&str>) -> String {
+    match tuist_version {
+        Some(version) if version != TESTED_TUIST_VERSION => format!(
+            "The Tuist graph format may have changed: catalyst supports Tuist {}, you have {}",
+            TESTED_TUIST_VERSION, version
+        ),
+        Some(version) => format!("Unexpected graph output from Tuist {}", version),
+        None => format!(
+            "Unexpected graph output from Tuist (catalyst supports Tuist {}; run `tuist version` to check yours)",
+            TESTED_TUIST_VERSION
+        ),
+    }
+}
@@ -0,0 +1,690 @@
+//! The workspace-wide files: WORKSPACE or MODULE.bazel, .bazelrc, and .bazelversion.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::cache::GeneratedManifest;
+use crate::config::{BuildFileName, Config, GenerateOptions, Platform, RuleSource};
+use crate::output::{dry_run, suspend_progress};
+use crate::starlark::starlark_string;
+
+/// File the generated Bazel settings are written to. The workspace's own
+/// `.bazelrc` imports it, so teams can keep their settings there.
+const CATALYST_BAZELRC: &str = "catalyst.bazelrc";
+
+/// Writes a generated file. A symlink at `path` (e.g. one created by
+/// `link_project_sources`) is replaced rather than written through, so a
+/// generated file never lands in the source tree by accident.
+pub(crate) fn write_generated_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if dry_run() {
+        return write_file(path, contents);
+    }
+    if path.is_symlink() {
+        fs::remove_file(path)?;
+    }
+    fs::write(path, contents)?;
+    Ok(())
+}
+
+/// Writes a file, or with `--dry-run` prints what would be written: the
+/// whole contents for a new file, and a diff against an existing one.
+pub(crate) fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    if !dry_run() {
+        fs::write(path, contents)?;
+        return Ok(());
+    }
+
+    let contents = String::from_utf8_lossy(contents.as_ref());
+    suspend_progress(|| match fs::read(path) {
+        Ok(existing) => {
+            let existing = String::from_utf8_lossy(&existing);
+            if existing == contents {
+                println!("==> {} (unchanged)", path.display());
+            } else {
+                print!("{}", unified_diff(path, &existing, &contents));
+            }
+        }
+        Err(_) => {
+            println!("==> {} (new)", path.display());
+            print!("{}", contents);
+            if !contents.ends_with('\n') {
+                println!();
+            }
+        }
+    });
+    Ok(())
+}
+
+/// Lines of unchanged context around each hunk of a dry-run diff.
+const DIFF_CONTEXT_LINES: usize = 3;
+
+/// A unified diff between two versions of `path`, from the longest common
+/// subsequence of their lines. Generated files are small enough for the
+/// quadratic table.
+fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut common = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    // Each edit with the old and new line numbers it starts at
+    let mut edits: Vec<(char, &str, usize, usize)> = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            edits.push((' ', old[i], i, j));
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || common[i][j + 1] >= common[i + 1][j]) {
+            edits.push(('+', new[j], i, j));
+            j += 1;
+        } else {
+            edits.push(('-', old[i], i, j));
+            i += 1;
+        }
+    }
+
+    let changes: Vec<usize> = (0..edits.len())
+        .filter(|&index| edits[index].0 != ' ')
+        .collect();
+    let mut diff = format!("--- {}\n+++ {}\n", path.display(), path.display());
+    let mut index = 0;
+    while index < changes.len() {
+        // Changes closer than twice the context share a hunk
+        let mut last = index;
+        while last + 1 < changes.len()
+            && changes[last + 1] - changes[last] <= 2 * DIFF_CONTEXT_LINES
+        {
+            last += 1;
+        }
+        let start = changes[index].saturating_sub(DIFF_CONTEXT_LINES);
+        let end = (changes[last] + DIFF_CONTEXT_LINES + 1).min(edits.len());
+        let hunk = &edits[start..end];
+
+        let old_count = hunk.iter().filter(|edit| edit.0 != '+').count();
+        let new_count = hunk.iter().filter(|edit| edit.0 != '-').count();
+        let (_, _, old_start, new_start) = hunk[0];
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            old_start + usize::from(old_count > 0),
+            old_count,
+            new_start + usize::from(new_count > 0),
+            new_count
+        ));
+        for (tag, line, _, _) in hunk {
+            diff.push(*tag);
+            diff.push_str(line);
+            diff.push('\n');
+        }
+        index = last + 1;
+    }
+    diff
+}
+
+/// Symlinks each top-level entry of the project into the output directory so
+/// the source paths referenced by the generated BUILD files resolve there.
+/// Entries the output directory already has (generated files, bazel-* output
+/// links) are left alone, except for the directories of nested packages, whose
+/// remaining entries are linked in turn. Returns the links that were created.
+#[cfg(unix)]
+pub(crate) fn link_project_sources(
+    project_dir: &Path,
+    workspace_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    let project_dir = project_dir.canonicalize()?;
+    let workspace_dir_canonical = workspace_dir.canonicalize()?;
+    let mut links = Vec::new();
+    link_directory(
+        &project_dir,
+        workspace_dir,
+        &workspace_dir_canonical,
+        &mut links,
+    )?;
+    Ok(links)
+}
+
+#[cfg(unix)]
+fn link_directory(
+    source_dir: &Path,
+    target_dir: &Path,
+    workspace_dir_canonical: &Path,
+    links: &mut Vec<PathBuf>,
+) -> Result<()> {
+    for entry in fs::read_dir(source_dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with("bazel-") {
+            continue;
+        }
+        // The output directory may itself live inside the project
+        if entry.path() == workspace_dir_canonical {
+            continue;
+        }
+
+        let link = target_dir.join(&name);
+        match link.symlink_metadata() {
+            // A package directory created for a nested project's BUILD file
+            Ok(metadata) if metadata.is_dir() && entry.file_type()?.is_dir() => {
+                link_directory(&entry.path(), &link, workspace_dir_canonical, links)?;
+            }
+            Ok(_) => {}
+            Err(_) => {
+                std::os::unix::fs::symlink(entry.path(), &link)
+                    .with_context(|| format!("Failed to link {}", link.display()))?;
+                links.push(link);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn link_project_sources(
+    _project_dir: &Path,
+    _workspace_dir: &Path,
+) -> Result<Vec<PathBuf>> {
+    anyhow::bail!("--output-dir requires symlink support, which is only available on Unix")
+}
+
+/// Pins the Bazel release in `.bazelversion`, which Bazelisk reads, so every
+/// machine builds with a Bazel the rule sets support. A `.bazelversion` that
+/// catalyst didn't write is left alone, with a warning when it pins another
+/// release. Out of tree, the project's own one is linked into the workspace
+/// instead.
+pub(crate) fn generate_bazelversion(
+    project_dir: &Path,
+    workspace_dir: &Path,
+    project_cache_dir: &Path,
+    options: &GenerateOptions,
+) -> Result<Option<PathBuf>> {
+    let path = workspace_dir.join(".bazelversion");
+    let manifest = GeneratedManifest::load(project_cache_dir)?;
+    let generated = manifest.contains(&path);
+    let existing = [path.clone(), project_dir.join(".bazelversion")]
+        .into_iter()
+        .find(|path| path.is_file() && !path.is_symlink());
+
+    if let Some(existing) = existing.filter(|_| !generated) {
+        let content = fs::read_to_string(&existing)
+            .with_context(|| format!("Failed to read {}", existing.display()))?;
+        let pinned = content.lines().next().unwrap_or_default().trim();
+        if pinned != options.bazel_version {
+            warning!(
+                "{} pins Bazel {}, but catalyst generates for Bazel {}; leaving it as is (set bazel_version in {} to change catalyst's)",
+                existing.display(),
+                pinned,
+                options.bazel_version,
+                Config::FILE_NAME
+            );
+        }
+        return Ok(None);
+    }
+
+    write_generated_file(&path, format!("{}\n", options.bazel_version))
+        .context("Failed to write .bazelversion file")?;
+    info!("Generated: {}", path.display());
+    Ok(Some(path))
+}
+
+/// Bazel prefers `BUILD.bazel` over a `BUILD` in the same package, and
+/// `WORKSPACE.bazel` over `WORKSPACE`, so a file under the other name would
+/// shadow or be shadowed by the generated one. One catalyst wrote before
+/// `build_file_name` changed is removed; any other is an error.
+pub(crate) fn remove_other_build_file(path: &Path, manifest: &GeneratedManifest) -> Result<()> {
+    if !path.is_file() {
+        return Ok(());
+    }
+    let generated = manifest.contains(path);
+    if !generated {
+        anyhow::bail!(
+            "{} exists and wasn't generated by catalyst; set build_file_name in {} to match it, or remove it",
+            path.display(),
+            Config::FILE_NAME
+        );
+    }
+
+    if dry_run() {
+        info!("Would remove: {}", path.display());
+    } else {
+        fs::remove_file(path).with_context(|| format!("Failed to remove {}", path.display()))?;
+        info!("Removed: {}", path.display());
+    }
+    Ok(())
+}
+
+pub(crate) fn generate_workspace_file(
+    workspace_dir: &Path,
+    options: &GenerateOptions,
+    local_profiles: bool,
+) -> Result<PathBuf> {
+    let workspace_path = workspace_dir.join(options.build_file_name.workspace());
+
+    let mut archives = options.rules_apple.repository("build_bazel_rules_apple")?;
+    let mut rule_sets = vec![&options.rules_apple];
+    // apple_rules_dependencies() only declares repositories that don't exist
+    // yet, so overrides declared before it win
+    for (rule_set, repo_name) in [
+        (&options.rules_swift, "build_bazel_rules_swift"),
+        (&options.apple_support, "build_bazel_apple_support"),
+    ] {
+        if rule_set.is_customized() {
+            archives.push('\n');
+            archives.push_str(&rule_set.repository(repo_name)?);
+            rule_sets.push(rule_set);
+        }
+    }
+    if options.with_xcodeproj {
+        rule_sets.push(&options.rules_xcodeproj);
+    }
+
+    // Only load the repository rules that are used, so a workspace that
+    // fetches everything from git doesn't reference http_archive
+    let mut repository_loads = String::new();
+    if rule_sets.iter().any(|rule_set| rule_set.source.is_none()) {
+        repository_loads
+            .push_str("load(\"@bazel_tools//tools/build_defs/repo:http.bzl\", \"http_archive\")\n");
+    }
+    if rule_sets
+        .iter()
+        .any(|rule_set| matches!(rule_set.source, Some(RuleSource::Git { .. })))
+    {
+        repository_loads.push_str(
+            "load(\"@bazel_tools//tools/build_defs/repo:git.bzl\", \"git_repository\")\n",
+        );
+    }
+
+    let xcodeproj = if options.with_xcodeproj {
+        format!(
+            r#"
+# rules_xcodeproj, for the xcodeproj target in the root BUILD file
+{}
+load(
+    "@rules_xcodeproj//xcodeproj:repositories.bzl",
+    "xcodeproj_rules_dependencies",
+)
+
+xcodeproj_rules_dependencies()
+"#,
+            options.rules_xcodeproj.http_archive("rules_xcodeproj")?
+        )
+    } else {
+        r#"
+# Optional: rules_xcodeproj for generating Xcode projects from Bazel targets.
+# Pass --with-xcodeproj to declare it here and add an xcodeproj target for the
+# app targets to the root BUILD file.
+"#
+        .to_string()
+    };
+
+    let workspace_content = format!(
+        r#"workspace(name = "catalyst_workspace")
+
+# Load Apple rules for building iOS/macOS apps
+{repository_loads}
+{archives}
+load(
+    "@build_bazel_rules_apple//apple:repositories.bzl",
+    "apple_rules_dependencies",
+)
+
+apple_rules_dependencies()
+
+load(
+    "@build_bazel_rules_swift//swift:repositories.bzl",
+    "swift_rules_dependencies",
+)
+
+swift_rules_dependencies()
+
+load(
+    "@build_bazel_rules_swift//swift:extras.bzl",
+    "swift_rules_extra_dependencies",
+)
+
+swift_rules_extra_dependencies()
+
+load(
+    "@build_bazel_apple_support//lib:repositories.bzl",
+    "apple_support_dependencies",
+)
+
+apple_support_dependencies()
+{profiles}{xcodeproj}"#,
+        profiles = if local_profiles {
+            r#"
+# Provisioning profiles installed on this machine, for device builds
+load("@build_bazel_rules_apple//apple:apple.bzl", "provisioning_profile_repository")
+
+provisioning_profile_repository(name = "local_provisioning_profiles")
+"#
+        } else {
+            ""
+        },
+    );
+
+    write_generated_file(&workspace_path, workspace_content)
+        .context("Failed to write WORKSPACE file")?;
+
+    info!("Generated: {}", workspace_path.display());
+
+    Ok(workspace_path)
+}
+
+pub(crate) fn generate_module_file(
+    workspace_dir: &Path,
+    options: &GenerateOptions,
+    local_profiles: bool,
+) -> Result<PathBuf> {
+    let module_path = workspace_dir.join("MODULE.bazel");
+
+    for name in [BuildFileName::Build, BuildFileName::BuildBazel] {
+        let workspace_path = workspace_dir.join(name.workspace());
+        if workspace_path.exists() {
+            warning!(
+                "{} exists; remove it to avoid mixing WORKSPACE and bzlmod configuration",
+                workspace_path.display()
+            );
+        }
+    }
+
+    // Rule sets fetched from git or a local checkout instead of the registry
+    let mut overrides = String::new();
+    for rule_set in [
+        &options.apple_support,
+        &options.rules_apple,
+        &options.rules_swift,
+    ] {
+        if let Some(module_override) = rule_set.module_override() {
+            overrides.push('\n');
+            overrides.push_str(&module_override);
+        }
+    }
+
+    // The repo names match the WORKSPACE repository names so the load
+    // statements in generated BUILD files work in both modes.
+    let module_content = format!(
+        r#"module(name = "catalyst_workspace")
+
+bazel_dep(name = "apple_support", version = "{apple_support_version}", repo_name = "build_bazel_apple_support")
+bazel_dep(name = "rules_apple", version = "{rules_apple_version}", repo_name = "build_bazel_rules_apple")
+bazel_dep(name = "rules_swift", version = "{rules_swift_version}", repo_name = "build_bazel_rules_swift")
+
+# Register the Xcode toolchain referenced by .bazelrc
+apple_cc_configure = use_extension(
+    "@build_bazel_apple_support//crosstool:setup.bzl",
+    "apple_cc_configure_extension",
+)
+use_repo(apple_cc_configure, "local_config_apple_cc")
+{profiles}{overrides}{xcodeproj}"#,
+        profiles = if local_profiles {
+            r#"
+# Provisioning profiles installed on this machine, for device builds
+provisioning_profile_repository = use_extension(
+    "@build_bazel_rules_apple//apple:apple.bzl",
+    "provisioning_profile_repository_extension",
+)
+use_repo(provisioning_profile_repository, "local_provisioning_profiles")
+"#
+        } else {
+            ""
+        },
+        apple_support_version = options.apple_support.version,
+        rules_apple_version = options.rules_apple.version,
+        rules_swift_version = options.rules_swift.version,
+        overrides = overrides,
+        xcodeproj = if options.with_xcodeproj {
+            format!(
+                "\nbazel_dep(name = \"rules_xcodeproj\", version = {}, dev_dependency = True)\n",
+                starlark_string(&options.rules_xcodeproj.version)
+            )
+        } else {
+            String::new()
+        },
+    );
+
+    check_module_lock(workspace_dir, options);
+    write_generated_file(&module_path, module_content)
+        .context("Failed to write MODULE.bazel file")?;
+
+    info!("Generated: {}", module_path.display());
+
+    Ok(module_path)
+}
+
+/// Warns when the workspace's `MODULE.bazel.lock` doesn't know the version of
+/// a rule set catalyst is about to declare, which Bazel would otherwise only
+/// report as an out-of-date lockfile on the next build. Rule sets fetched from
+/// git or a checkout aren't resolved from the registry, so they're skipped.
+fn check_module_lock(workspace_dir: &Path, options: &GenerateOptions) {
+    let lock_path = workspace_dir.join("MODULE.bazel.lock");
+    let Ok(content) = fs::read_to_string(&lock_path) else {
+        return;
+    };
+    let Ok(lock) = serde_json::from_str::<serde_json::Value>(&content) else {
+        verbose!("Couldn't parse {}; not checking it", lock_path.display());
+        return;
+    };
+
+    let mut rule_sets = vec![
+        &options.apple_support,
+        &options.rules_apple,
+        &options.rules_swift,
+    ];
+    if options.with_xcodeproj {
+        rule_sets.push(&options.rules_xcodeproj);
+    }
+    let drifted: Vec<String> = rule_sets
+        .into_iter()
+        .filter(|rule_set| rule_set.source.is_none())
+        .filter_map(|rule_set| {
+            let locked = locked_module_versions(&lock, rule_set.name);
+            if locked.is_empty() || locked.contains(&rule_set.version) {
+                return None;
+            }
+            let locked: Vec<&str> = locked.iter().map(String::as_str).collect();
+            Some(format!(
+                "  {}: {} in the lockfile, {} in MODULE.bazel",
+                rule_set.name,
+                locked.join(", "),
+                rule_set.version
+            ))
+        })
+        .collect();
+    if !drifted.is_empty() {
+        warning!(
+            "{} pins other rule set versions than catalyst declares, so Bazel will report it out of date; run `bazel mod deps` to refresh it:\n{}",
+            lock_path.display(),
+            drifted.join("\n")
+        );
+    }
+}
+
+/// The versions of `module` a `MODULE.bazel.lock` records: the ones in the
+/// registry's `modules/<name>/<version>/` file URLs, or in older lockfiles
+/// the `<name>@<version>` keys of the module graph.
+fn locked_module_versions(lock: &serde_json::Value, module: &str) -> BTreeSet<String> {
+    let mut versions = BTreeSet::new();
+    let segment = format!("/modules/{}/", module);
+    if let Some(hashes) = lock
+        .get("registryFileHashes")
+        .and_then(serde_json::Value::as_object)
+    {
+        for url in hashes.keys() {
+            if let Some((_, rest)) = url.split_once(&segment) {
+                if let Some((version, _)) = rest.split_once('/') {
+                    versions.insert(version.to_string());
+                }
+            }
+        }
+    }
+    let prefix = format!("{}@", module);
+    if let Some(graph) = lock
+        .get("moduleDepGraph")
+        .and_then(serde_json::Value::as_object)
+    {
+        versions.extend(
+            graph
+                .keys()
+                .filter_map(|key| key.strip_prefix(&prefix))
+                .map(str::to_string),
+        );
+    }
+    versions
+}
+
+/// Writes catalyst's Bazel settings to `catalyst.bazelrc` and makes sure the
+/// workspace's `.bazelrc` imports it. An existing `.bazelrc` is kept as is
+/// apart from the added import; out of tree, the generated `.bazelrc` imports
+/// the project's own one instead of replacing it.
+///
+/// The platform is only pinned when every target is built for the same one;
+/// in a mixed workspace each bundle rule picks its own.
+pub(crate) fn generate_bazelrc(
+    project_dir: &Path,
+    workspace_dir: &Path,
+    options: &GenerateOptions,
+    platforms: &BTreeSet<Platform>,
+) -> Result<Vec<PathBuf>> {
+    let bazelrc_path = workspace_dir.join(CATALYST_BAZELRC);
+
+    let mut build_settings = String::new();
+    if let [platform] = platforms.iter().collect::<Vec<_>>()[..] {
+        build_settings.push_str(&format!(
+            "build --apple_platform_type={}\n",
+            platform.prefix()
+        ));
+    }
+    if platforms.contains(&Platform::Ios) {
+        build_settings.push_str(&format!(
+            "build --ios_minimum_os={}\n",
+            options.minimum_os_version
+        ));
+    }
+
+    let mut bazelrc_content = format!(
+        r#"# Build settings
+{}
+# Use Xcode toolchain
+build --apple_crosstool_top=@local_config_apple_cc//:toolchain
+build --crosstool_top=@local_config_apple_cc//:toolchain
+build --host_crosstool_top=@local_config_apple_cc//:toolchain
+
+# Build configurations, selected with --config
+build:debug --compilation_mode=dbg
+build:release --compilation_mode=opt
+
+# Output settings
+build --verbose_failures
+build --announce_rc
+"#,
+        build_settings
+    );
+
+    // Settings from catalyst.toml, each in its own section. A line that's
+    // already in the file is left out.
+    let mut sections: Vec<(&str, Vec<String>)> = Vec::new();
+    if let Some(remote_cache) = &options.remote_cache {
+        sections.push((
+            "Remote cache",
+            vec![format!("build --remote_cache={}", remote_cache)],
+        ));
+    }
+    if let Some(command) = &options.workspace_status_command {
+        sections.push((
+            "Build stamping",
+            vec![format!("build --workspace_status_command={}", command)],
+        ));
+    }
+    let mut extra = Vec::new();
+    for (index, line) in options.extra_bazelrc.iter().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            anyhow::bail!(
+                "Entry {} of bazelrc in {} is empty",
+                index + 1,
+                Config::FILE_NAME
+            );
+        }
+        extra.push(line.to_string());
+    }
+    sections.push(("Extra settings", extra));
+
+    let mut seen: BTreeSet<String> = bazelrc_content.lines().map(str::to_string).collect();
+    for (title, lines) in sections {
+        let lines: Vec<String> = lines
+            .into_iter()
+            .filter(|line| seen.insert(line.clone()))
+            .collect();
+        if lines.is_empty() {
+            continue;
+        }
+        bazelrc_content.push_str(&format!("\n# {} from {}\n", title, Config::FILE_NAME));
+        for line in lines {
+            bazelrc_content.push_str(&line);
+            bazelrc_content.push('\n');
+        }
+    }
+
+    write_generated_file(&bazelrc_path, bazelrc_content)
+        .with_context(|| format!("Failed to write {} file", CATALYST_BAZELRC))?;
+
+    info!("Generated: {}", bazelrc_path.display());
+
+    let mut written = vec![bazelrc_path];
+    let import = format!("try-import %workspace%/{}", CATALYST_BAZELRC);
+    let main_path = workspace_dir.join(".bazelrc");
+    let project_bazelrc = project_dir.join(".bazelrc");
+
+    if workspace_dir != project_dir && project_bazelrc.exists() {
+        let project_bazelrc = project_bazelrc.canonicalize()?;
+        write_generated_file(
+            &main_path,
+            format!("import {}\n{}\n", project_bazelrc.display(), import),
+        )
+        .context("Failed to write .bazelrc file")?;
+        written.push(main_path);
+    } else if main_path.exists() {
+        let existing = fs::read_to_string(&main_path).context("Failed to read .bazelrc file")?;
+        if !existing.lines().any(|line| line.trim() == import) {
+            let separator = if existing.is_empty() || existing.ends_with('\n') {
+                ""
+            } else {
+                "\n"
+            };
+            write_file(
+                &main_path,
+                format!(
+                    "{}{}\n# Added by catalyst\n{}\n",
+                    existing, separator, import
+                ),
+            )
+            .context("Failed to update .bazelrc file")?;
+            info!(
+                "Added an import of {} to {}",
+                CATALYST_BAZELRC,
+                main_path.display()
+            );
+        }
+    } else {
+        write_generated_file(&main_path, format!("{}\n", import))
+            .context("Failed to write .bazelrc file")?;
+        info!("Generated: {}", main_path.display());
+        written.push(main_path);
+    }
+
+    Ok(written)
+}